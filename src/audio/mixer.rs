@@ -0,0 +1,450 @@
+use crate::database::SoundData;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Volume sliders. Every sound answers to `Master` and to the group it was played in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VolumeGroup {
+    Master,
+    Music,
+    Sfx,
+}
+
+/// A sound that was started on the mixer. Stays valid, but inert, once the sound ends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SoundHandle(u64);
+
+#[derive(Clone, Copy, Debug)]
+pub struct PlayInfo {
+    pub volume: f32,
+    /// -1 is fully left, 1 fully right.
+    pub pan: f32,
+    pub looping: bool,
+    /// Seconds to ramp up from silence. Starts at full volume when 0.
+    pub fade_in_secs: f32,
+}
+
+impl Default for PlayInfo {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            pan: 0.0,
+            looping: false,
+            fade_in_secs: 0.0,
+        }
+    }
+}
+
+struct Fade {
+    from: f32,
+    to: f32,
+    elapsed: u32,
+    length: u32,
+    stop: bool,
+}
+
+enum Source {
+    Clip(Arc<SoundData>),
+    // Samples are pushed in from the main thread as they're decoded.
+    Stream {
+        channels: u16,
+        buffer: VecDeque<i16>,
+        finished: bool,
+    },
+}
+
+impl Source {
+    fn channels(&self) -> usize {
+        match self {
+            Source::Clip(data) => data.channels.max(1) as usize,
+            Source::Stream { channels, .. } => (*channels).max(1) as usize,
+        }
+    }
+
+    fn frames(&self) -> usize {
+        match self {
+            Source::Clip(data) => data.samples.len() / self.channels(),
+            Source::Stream { buffer, .. } => buffer.len() / self.channels(),
+        }
+    }
+
+    // Stereo frame at `i`. Mono is played on both sides, anything past stereo is dropped.
+    fn frame(&self, i: usize) -> [f32; 2] {
+        let channels = self.channels();
+        let at = |c: usize| {
+            let s = match self {
+                Source::Clip(data) => data.samples[i * channels + c],
+                Source::Stream { buffer, .. } => buffer[i * channels + c],
+            };
+            s as f32 / 32768.0
+        };
+
+        match channels {
+            1 => [at(0); 2],
+            _ => [at(0), at(1)],
+        }
+    }
+}
+
+struct Voice {
+    handle: SoundHandle,
+    group: VolumeGroup,
+    source: Source,
+    // In source frames. Advances by `step` per output frame to resample.
+    position: f64,
+    step: f64,
+    volume: f32,
+    pan: f32,
+    looping: bool,
+    fade: Option<Fade>,
+    done: bool,
+}
+
+impl Voice {
+    fn next_frame(&mut self) -> Option<[f32; 2]> {
+        let frames = self.source.frames();
+        let i = self.position as usize;
+        let next = match (&self.source, self.looping) {
+            (Source::Clip(_), true) if frames > 0 => (i + 1) % frames,
+            _ => i + 1,
+        };
+
+        if i >= frames {
+            match &self.source {
+                Source::Clip(_) if self.looping && frames > 0 => {
+                    self.position -= frames as f64;
+                    return self.next_frame();
+                }
+                // Starved. Wait for more data instead of skipping ahead.
+                Source::Stream {
+                    finished: false, ..
+                } => return None,
+                _ => {
+                    self.done = true;
+                    return None;
+                }
+            }
+        }
+
+        let a = self.source.frame(i);
+        let b = match next < frames {
+            true => self.source.frame(next),
+            false => a,
+        };
+        let t = self.position.fract() as f32;
+        self.position += self.step;
+
+        Some([a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t])
+    }
+
+    fn advance_fade(&mut self) {
+        let fade = match self.fade.as_mut() {
+            Some(f) => f,
+            None => return,
+        };
+
+        fade.elapsed += 1;
+        let t = (fade.elapsed as f32 / fade.length.max(1) as f32).min(1.0);
+        self.volume = fade.from + (fade.to - fade.from) * t;
+        if t >= 1.0 {
+            self.done |= fade.stop;
+            self.fade = None;
+        }
+    }
+
+    // Drops stream samples that have been played so the buffer doesn't grow forever.
+    fn trim(&mut self) {
+        if let Source::Stream {
+            channels, buffer, ..
+        } = &mut self.source
+        {
+            let played = (self.position as usize).min(buffer.len() / (*channels).max(1) as usize);
+            buffer.drain(..played * (*channels).max(1) as usize);
+            self.position -= played as f64;
+        }
+    }
+}
+
+/// Mixes any number of sounds down to interleaved stereo at a fixed output rate.
+pub struct Mixer {
+    rate: u32,
+    master: f32,
+    music: f32,
+    sfx: f32,
+    voices: Vec<Voice>,
+    next_handle: u64,
+}
+
+impl Mixer {
+    pub fn new(rate: u32) -> Self {
+        Self {
+            rate,
+            master: 1.0,
+            music: 1.0,
+            sfx: 1.0,
+            voices: Vec::new(),
+            next_handle: 0,
+        }
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    fn start(
+        &mut self,
+        source: Source,
+        rate: u32,
+        group: VolumeGroup,
+        info: &PlayInfo,
+    ) -> SoundHandle {
+        self.next_handle += 1;
+        let handle = SoundHandle(self.next_handle);
+        let fade_frames = (info.fade_in_secs.max(0.0) * self.rate as f32) as u32;
+        self.voices.push(Voice {
+            handle,
+            group,
+            source,
+            position: 0.0,
+            step: rate as f64 / self.rate as f64,
+            volume: if fade_frames > 0 { 0.0 } else { info.volume },
+            pan: info.pan.clamp(-1.0, 1.0),
+            looping: info.looping,
+            fade: (fade_frames > 0).then_some(Fade {
+                from: 0.0,
+                to: info.volume,
+                elapsed: 0,
+                length: fade_frames,
+                stop: false,
+            }),
+            done: false,
+        });
+
+        handle
+    }
+
+    pub fn play(
+        &mut self,
+        sound: Arc<SoundData>,
+        group: VolumeGroup,
+        info: &PlayInfo,
+    ) -> SoundHandle {
+        let rate = sound.sample_rate;
+        self.start(Source::Clip(sound), rate, group, info)
+    }
+
+    /// Starts a sound whose samples arrive later through `push_stream`. Looping is up to
+    /// whoever pushes the samples.
+    pub fn play_stream(
+        &mut self,
+        channels: u16,
+        rate: u32,
+        group: VolumeGroup,
+        info: &PlayInfo,
+    ) -> SoundHandle {
+        let source = Source::Stream {
+            channels,
+            buffer: VecDeque::new(),
+            finished: false,
+        };
+
+        self.start(
+            source,
+            rate,
+            group,
+            &PlayInfo {
+                looping: false,
+                ..*info
+            },
+        )
+    }
+
+    /// Queues more samples for a stream. `finished` marks the end, after which the voice
+    /// stops once its buffer runs dry.
+    pub fn push_stream(&mut self, handle: SoundHandle, samples: &[i16], finished: bool) {
+        if let Some(Source::Stream {
+            buffer,
+            finished: end,
+            ..
+        }) = self.voice_mut(handle).map(|v| &mut v.source)
+        {
+            buffer.extend(samples);
+            *end |= finished;
+        }
+    }
+
+    /// Frames of a stream that are buffered but not played yet.
+    pub fn stream_backlog(&self, handle: SoundHandle) -> Option<usize> {
+        let voice = self.voices.iter().find(|v| v.handle == handle)?;
+        Some(
+            voice
+                .source
+                .frames()
+                .saturating_sub(voice.position as usize),
+        )
+    }
+
+    fn voice_mut(&mut self, handle: SoundHandle) -> Option<&mut Voice> {
+        self.voices.iter_mut().find(|v| v.handle == handle)
+    }
+
+    pub fn is_playing(&self, handle: SoundHandle) -> bool {
+        self.voices.iter().any(|v| v.handle == handle && !v.done)
+    }
+
+    pub fn stop(&mut self, handle: SoundHandle) {
+        self.voices.retain(|v| v.handle != handle);
+    }
+
+    /// Ramps the sound's volume over `secs`, stopping it at the end if `stop` is set.
+    pub fn fade_to(&mut self, handle: SoundHandle, volume: f32, secs: f32, stop: bool) {
+        let length = (secs.max(0.0) * self.rate as f32) as u32;
+        if let Some(voice) = self.voice_mut(handle) {
+            voice.fade = Some(Fade {
+                from: voice.volume,
+                to: volume,
+                elapsed: 0,
+                length,
+                stop,
+            });
+        }
+    }
+
+    pub fn set_pan(&mut self, handle: SoundHandle, pan: f32) {
+        if let Some(voice) = self.voice_mut(handle) {
+            voice.pan = pan.clamp(-1.0, 1.0);
+        }
+    }
+
+    pub fn set_volume(&mut self, handle: SoundHandle, volume: f32) {
+        if let Some(voice) = self.voice_mut(handle) {
+            voice.fade = None;
+            voice.volume = volume;
+        }
+    }
+
+    pub fn set_group_volume(&mut self, group: VolumeGroup, volume: f32) {
+        let volume = volume.max(0.0);
+        match group {
+            VolumeGroup::Master => self.master = volume,
+            VolumeGroup::Music => self.music = volume,
+            VolumeGroup::Sfx => self.sfx = volume,
+        }
+    }
+
+    pub fn group_volume(&self, group: VolumeGroup) -> f32 {
+        match group {
+            VolumeGroup::Master => self.master,
+            VolumeGroup::Music => self.music,
+            VolumeGroup::Sfx => self.sfx,
+        }
+    }
+
+    /// Adds every playing sound into `out`, interleaved stereo, and clamps the result.
+    pub fn mix(&mut self, out: &mut [f32]) {
+        for voice in self.voices.iter_mut() {
+            let group = match voice.group {
+                VolumeGroup::Music => self.music,
+                VolumeGroup::Sfx => self.sfx,
+                VolumeGroup::Master => 1.0,
+            };
+
+            for frame in out.chunks_exact_mut(2) {
+                if voice.done {
+                    break;
+                }
+
+                let sample = match voice.next_frame() {
+                    Some(s) => s,
+                    None => continue,
+                };
+
+                let gain = voice.volume * group * self.master;
+                frame[0] += sample[0] * gain * (1.0 - voice.pan).min(1.0);
+                frame[1] += sample[1] * gain * (1.0 + voice.pan).min(1.0);
+                voice.advance_fade();
+            }
+
+            voice.trim();
+        }
+
+        self.voices.retain(|v| !v.done);
+        for s in out.iter_mut() {
+            *s = s.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+/// Pan for a sound at `x` heard from `listener_x`. Sounds `half_width` or further to one
+/// side play entirely on that side.
+pub fn pan_from_position(x: f32, listener_x: f32, half_width: f32) -> f32 {
+    if half_width <= 0.0 {
+        return 0.0;
+    }
+
+    ((x - listener_x) / half_width).clamp(-1.0, 1.0)
+}
+
+#[test]
+fn test_mixer() {
+    let tone = Arc::new(SoundData {
+        channels: 1,
+        sample_rate: 100,
+        samples: vec![16384; 100],
+    });
+
+    let mut mixer = Mixer::new(100);
+    let left = PlayInfo {
+        pan: -1.0,
+        ..Default::default()
+    };
+    let a = mixer.play(tone.clone(), VolumeGroup::Sfx, &left);
+    let mut out = vec![0.0; 20];
+    mixer.mix(&mut out);
+    assert_eq!(out[0], 0.5);
+    assert_eq!(out[1], 0.0);
+
+    mixer.set_group_volume(VolumeGroup::Sfx, 0.5);
+    mixer.set_group_volume(VolumeGroup::Master, 0.5);
+    let mut out = vec![0.0; 2];
+    mixer.mix(&mut out);
+    assert_eq!(out[0], 0.125);
+
+    // A fade with `stop` set ends the sound once it's silent.
+    mixer.fade_to(a, 0.0, 0.1, true);
+    let mut out = vec![0.0; 40];
+    mixer.mix(&mut out);
+    assert!(!mixer.is_playing(a));
+    assert_eq!(&out[20..], &[0.0; 20]);
+
+    // Half-rate sounds get stretched, and looping ones wrap around.
+    let slow = Arc::new(SoundData {
+        sample_rate: 50,
+        ..(*tone).clone()
+    });
+    let looped = PlayInfo {
+        looping: true,
+        ..Default::default()
+    };
+    let b = mixer.play(slow, VolumeGroup::Music, &looped);
+    let mut out = vec![0.0; 2 * 500];
+    mixer.mix(&mut out);
+    assert!(mixer.is_playing(b));
+    assert_eq!(out[998], 0.25);
+    mixer.stop(b);
+
+    // Streams wait for data instead of ending.
+    let c = mixer.play_stream(1, 100, VolumeGroup::Music, &Default::default());
+    mixer.mix(&mut vec![0.0; 20]);
+    assert!(mixer.is_playing(c));
+    mixer.push_stream(c, &[16384; 30], false);
+    assert_eq!(mixer.stream_backlog(c), Some(30));
+    mixer.mix(&mut vec![0.0; 20]);
+    assert_eq!(mixer.stream_backlog(c), Some(20));
+    mixer.push_stream(c, &[], true);
+    mixer.mix(&mut vec![0.0; 100]);
+    assert!(!mixer.is_playing(c));
+
+    assert_eq!(pan_from_position(150.0, 100.0, 100.0), 0.5);
+    assert_eq!(pan_from_position(-500.0, 100.0, 100.0), -1.0);
+}
@@ -0,0 +1,234 @@
+pub mod mixer;
+pub use mixer::*;
+
+use crate::database::{Database, Error, SoundStream};
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+const SAMPLE_RATE: i32 = 48000;
+const BUFFER_FRAMES: u16 = 1024;
+
+impl AudioCallback for Mixer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        out.fill(0.0);
+        self.mix(out);
+    }
+}
+
+struct MusicStream {
+    handle: SoundHandle,
+    stream: SoundStream,
+    looping: bool,
+    finished: bool,
+}
+
+/// Plays sounds from the database through SDL's audio device. Short sounds are played
+/// from memory, music is decoded a piece at a time during `update`.
+pub struct Audio {
+    device: AudioDevice<Mixer>,
+    streams: Vec<MusicStream>,
+    music: Option<SoundHandle>,
+}
+
+impl Audio {
+    pub fn new(sdl_ctx: &mut sdl2::Sdl) -> Self {
+        Self::try_new(sdl_ctx).unwrap()
+    }
+
+    /// Opens the default output device. Set `SDL_AUDIODRIVER` to `dummy` or `disk` to run
+    /// without a sound card.
+    pub fn try_new(sdl_ctx: &mut sdl2::Sdl) -> Result<Self, Error> {
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(2),
+            samples: Some(BUFFER_FRAMES),
+        };
+
+        let device = sdl_ctx
+            .audio()?
+            .open_playback(None, &desired, |spec| Mixer::new(spec.freq as u32))?;
+        device.resume();
+
+        Ok(Self {
+            device,
+            streams: Vec::new(),
+            music: None,
+        })
+    }
+
+    /// Plays a sound effect in the `Sfx` group, loading it first if needed.
+    pub fn play_sound(
+        &mut self,
+        db: &mut Database,
+        name: &str,
+        info: &PlayInfo,
+    ) -> Result<SoundHandle, Error> {
+        let sound = match &db.fetch_sound(name)?.loaded {
+            Some(s) => s.clone(),
+            None => return Err(Error::lookup(name)),
+        };
+
+        Ok(self.device.lock().play(sound, VolumeGroup::Sfx, info))
+    }
+
+    /// Streams a sound as music. Whatever music was playing fades out over `fade_in_secs`
+    /// while the new one fades in.
+    pub fn play_music(
+        &mut self,
+        db: &Database,
+        name: &str,
+        info: &PlayInfo,
+    ) -> Result<SoundHandle, Error> {
+        let stream = db.open_sound_stream(name)?;
+        self.stop_music(info.fade_in_secs);
+
+        let handle = self.device.lock().play_stream(
+            stream.channels(),
+            stream.sample_rate(),
+            VolumeGroup::Music,
+            info,
+        );
+        self.streams.push(MusicStream {
+            handle,
+            stream,
+            looping: info.looping,
+            finished: false,
+        });
+        self.music = Some(handle);
+        self.update();
+
+        Ok(handle)
+    }
+
+    pub fn music(&self) -> Option<SoundHandle> {
+        self.music
+    }
+
+    pub fn stop_music(&mut self, fade_out_secs: f32) {
+        if let Some(music) = self.music.take() {
+            self.fade_out(music, fade_out_secs);
+        }
+    }
+
+    /// Keeps music streams fed. Call once a frame.
+    pub fn update(&mut self) {
+        for music in self.streams.iter_mut() {
+            let backlog = match self.device.lock().stream_backlog(music.handle) {
+                Some(b) => b,
+                None => {
+                    music.finished = true;
+                    continue;
+                }
+            };
+
+            // Decode outside the lock so the audio thread isn't kept waiting.
+            let wanted = music.stream.sample_rate() as usize / 2;
+            let channels = music.stream.channels().max(1) as usize;
+            let mut samples = Vec::new();
+            let mut rewound = false;
+            while backlog + samples.len() / channels < wanted && !music.finished {
+                match music.stream.read(&mut samples) {
+                    Ok(true) => rewound = false,
+                    // Rewinding twice in a row means there's nothing to loop.
+                    Ok(false) if music.looping && !rewound => match music.stream.rewind() {
+                        Ok(()) => rewound = true,
+                        Err(e) => {
+                            println!("Failed to loop music: {}", e);
+                            music.finished = true;
+                        }
+                    },
+                    Ok(false) => music.finished = true,
+                    Err(e) => {
+                        println!("Failed to stream music: {}", e);
+                        music.finished = true;
+                    }
+                }
+            }
+
+            self.device
+                .lock()
+                .push_stream(music.handle, &samples, music.finished);
+        }
+
+        self.streams.retain(|m| !m.finished);
+    }
+
+    pub fn is_playing(&mut self, sound: SoundHandle) -> bool {
+        self.device.lock().is_playing(sound)
+    }
+
+    pub fn stop(&mut self, sound: SoundHandle) {
+        self.device.lock().stop(sound);
+    }
+
+    pub fn fade_out(&mut self, sound: SoundHandle, secs: f32) {
+        self.device.lock().fade_to(sound, 0.0, secs, true);
+    }
+
+    pub fn fade_to(&mut self, sound: SoundHandle, volume: f32, secs: f32) {
+        self.device.lock().fade_to(sound, volume, secs, false);
+    }
+
+    pub fn set_sound_volume(&mut self, sound: SoundHandle, volume: f32) {
+        self.device.lock().set_volume(sound, volume);
+    }
+
+    /// -1 is fully left, 1 fully right. See `pan_from_position` for sounds placed in the world.
+    pub fn set_pan(&mut self, sound: SoundHandle, pan: f32) {
+        self.device.lock().set_pan(sound, pan);
+    }
+
+    pub fn set_volume(&mut self, group: VolumeGroup, volume: f32) {
+        self.device.lock().set_group_volume(group, volume);
+    }
+
+    pub fn volume(&mut self, group: VolumeGroup) -> f32 {
+        self.device.lock().group_volume(group)
+    }
+}
+
+#[test]
+fn test_audio() {
+    use crate::database::MemoryFileSystem;
+
+    let samples: Vec<i16> = (0..48000).map(|i| ((i % 100) * 300) as i16).collect();
+    let fs = MemoryFileSystem::new()
+        .with("beep.wav", crate::database::test_wav(1, 48000, &samples))
+        .with("shoyu.json", r#"{"audio_cfg": "audio.json"}"#)
+        .with(
+            "audio.json",
+            r#"{"sounds": [{"name": "beep", "path": "beep.wav"}]}"#,
+        );
+
+    // No sound card needed.
+    std::env::set_var("SDL_AUDIODRIVER", "dummy");
+    let mut sdl = sdl2::init().unwrap();
+    let mut audio = Audio::new(&mut sdl);
+    let mut db = Database::with_filesystem(std::sync::Arc::new(fs)).unwrap();
+
+    let beep = audio
+        .play_sound(&mut db, "beep", &Default::default())
+        .unwrap();
+    assert!(audio.is_playing(beep));
+    audio.stop(beep);
+    assert!(!audio.is_playing(beep));
+
+    let looped = PlayInfo {
+        looping: true,
+        ..Default::default()
+    };
+    let music = audio.play_music(&db, "beep", &looped).unwrap();
+    audio.update();
+    assert_eq!(audio.music(), Some(music));
+    assert!(audio.is_playing(music));
+
+    audio.set_volume(VolumeGroup::Music, 0.5);
+    assert_eq!(audio.volume(VolumeGroup::Music), 0.5);
+    assert!(audio
+        .play_sound(&mut db, "missing", &Default::default())
+        .is_err());
+
+    audio.stop_music(0.0);
+    assert_eq!(audio.music(), None);
+}
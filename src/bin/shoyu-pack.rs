@@ -0,0 +1,28 @@
+use shoyu::database::pack::pack_directory;
+use std::env;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 && !(args.len() == 4 && args[3] == "--no-compress") {
+        println!(
+            "Usage: {} <path_to_database> <archive> [--no-compress]",
+            args[0]
+        );
+        return ExitCode::from(2);
+    }
+
+    match pack_directory(&args[1], &args[2], args.len() == 3) {
+        Ok(stats) => {
+            println!(
+                "Packed {} files into {} ({} bytes, {} before compression)",
+                stats.files, args[2], stats.packed_size, stats.size
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            println!("Failed to pack {}: {}", args[1], e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use shoyu::validate::validate_database;
+use std::env;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 && !(args.len() == 4 && args[2] == "--json") {
+        println!(
+            "Usage: {} <path_to_database> [--json <report.json | ->]",
+            args[0]
+        );
+        return ExitCode::from(2);
+    }
+
+    let report = validate_database(&args[1]);
+    match args.get(3).map(|s| s.as_str()) {
+        Some("-") => println!("{}", report.to_json()),
+        Some(out) => {
+            println!("{}", report);
+            if let Err(e) = std::fs::write(out, report.to_json()) {
+                println!("Failed to write JSON report to {}: {}", out, e);
+                return ExitCode::from(2);
+            }
+        }
+        None => println!("{}", report),
+    }
+
+    match report.is_ok() {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
+}
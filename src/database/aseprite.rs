@@ -0,0 +1,155 @@
+use super::atlas::*;
+use super::error::*;
+use super::json::*;
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+pub struct AsepriteTag {
+    pub name: String,
+    pub from: u32,
+    pub to: u32,
+    #[serde(default)]
+    pub direction: String,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AsepriteMeta {
+    pub image: String,
+    pub size: Option<AtlasSize>,
+    #[serde(default)]
+    pub frame_tags: Vec<AsepriteTag>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AsepriteExport {
+    #[serde(deserialize_with = "deserialize_frames")]
+    pub frames: Vec<AtlasFrame>,
+    pub meta: AsepriteMeta,
+}
+
+const DEFAULT_FRAME_MS: u32 = 100;
+
+fn tag_to_animation(tag: &AsepriteTag, frames: &[AtlasFrame]) -> SpriteSheetJSONAnimation {
+    let forward: Vec<u32> = (tag.from..=tag.to).collect();
+    let ids: Vec<u32> = match tag.direction.as_str() {
+        "reverse" => forward.into_iter().rev().collect(),
+        // Don't repeat the end frames when bouncing back.
+        "pingpong" => {
+            let back = forward.iter().rev().skip(1);
+            let back: Vec<u32> = back
+                .take(forward.len().saturating_sub(2))
+                .copied()
+                .collect();
+            forward.into_iter().chain(back).collect()
+        }
+        "pingpong_reverse" => {
+            let rev: Vec<u32> = forward.iter().rev().copied().collect();
+            let back: Vec<u32> = forward
+                .iter()
+                .skip(1)
+                .take(forward.len().saturating_sub(2))
+                .copied()
+                .collect();
+            rev.into_iter().chain(back).collect()
+        }
+        _ => forward,
+    };
+
+    SpriteSheetJSONAnimation {
+        name: tag.name.clone(),
+        frames: ids
+            .into_iter()
+            .map(|id| SpriteSheetJSONAnimationFrame {
+                id,
+                duration_ms: frames
+                    .get(id as usize)
+                    .and_then(|f| f.duration)
+                    .unwrap_or(DEFAULT_FRAME_MS) as f32,
+            })
+            .collect(),
+    }
+}
+
+impl AsepriteExport {
+    /// Converts the export into a sprite sheet entry. `json_path` is the export's path
+    /// relative to the database root; the image path in `meta` is relative to it.
+    pub fn to_sprite_sheet(&self, name: &str, json_path: &str) -> SpriteSheetJSONEntry {
+        let sprites = self
+            .frames
+            .iter()
+            .enumerate()
+            .map(|(id, f)| f.to_sprite(id as u32))
+            .collect();
+
+        let animations = self
+            .meta
+            .frame_tags
+            .iter()
+            .map(|t| tag_to_animation(t, &self.frames))
+            .collect();
+
+        SpriteSheetJSONEntry {
+            name: name.to_string(),
+            image_path: relative_image_path(json_path, &self.meta.image),
+            sprites: Some(sprites),
+            auto_gen: None,
+            animations: Some(animations),
+            texture: None,
+        }
+    }
+}
+
+pub fn parse_aseprite(
+    name: &str,
+    json_path: &str,
+    json_data: &str,
+) -> Result<SpriteSheetJSONEntry, Error> {
+    let export: AsepriteExport = serde_json::from_str(json_data)?;
+    Ok(export.to_sprite_sheet(name, json_path))
+}
+
+#[test]
+fn test_aseprite_formats() {
+    let hash = r#"{
+        "frames": {
+            "hero 0.aseprite": { "frame": {"x": 0, "y": 0, "w": 8, "h": 8}, "duration": 50 },
+            "hero 10.aseprite": { "frame": {"x": 8, "y": 0, "w": 8, "h": 8}, "duration": 60 },
+            "hero 2.aseprite": { "frame": {"x": 16, "y": 0, "w": 8, "h": 8} }
+        },
+        "meta": {
+            "image": "hero.png",
+            "frameTags": [{"name": "bounce", "from": 0, "to": 2, "direction": "pingpong"}]
+        }
+    }"#;
+
+    let sheet = parse_aseprite("hero", "chars/hero.json", hash).unwrap();
+    let sprites = sheet.sprites.unwrap();
+    assert_eq!(sheet.image_path, "chars/hero.png");
+    assert_eq!(sprites[1].name, "hero 10.aseprite");
+    assert_eq!(sprites[1].bounds.x, 8);
+
+    let anim = &sheet.animations.unwrap()[0];
+    let ids: Vec<u32> = anim.frames.iter().map(|f| f.id).collect();
+    assert_eq!(ids, vec![0, 1, 2, 1]);
+    assert_eq!(anim.frames[1].duration_ms, 60.0);
+    assert_eq!(anim.frames[2].duration_ms, DEFAULT_FRAME_MS as f32);
+
+    let array = r#"{
+        "frames": [
+            { "filename": "a", "frame": {"x": 0, "y": 0, "w": 4, "h": 4}, "duration": 10 },
+            { "filename": "b", "frame": {"x": 4, "y": 0, "w": 4, "h": 4}, "duration": 20 }
+        ],
+        "meta": { "image": "a.png", "frameTags": [{"name": "back", "from": 0, "to": 1, "direction": "reverse"}] }
+    }"#;
+
+    let sheet = parse_aseprite("a", "a.json", array).unwrap();
+    assert_eq!(sheet.image_path, "a.png");
+    assert_eq!(sheet.sprites.unwrap()[1].name, "b");
+    let ids: Vec<u32> = sheet.animations.unwrap()[0]
+        .frames
+        .iter()
+        .map(|f| f.id)
+        .collect();
+    assert_eq!(ids, vec![1, 0]);
+}
@@ -0,0 +1,189 @@
+use super::error::*;
+use super::json::*;
+use dashi::Rect2D;
+use serde::de::{Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+pub struct AtlasRect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+pub struct AtlasSize {
+    pub w: u32,
+    pub h: u32,
+}
+
+/// One frame of a TexturePacker style atlas. Aseprite exports the same layout.
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AtlasFrame {
+    #[serde(default)]
+    pub filename: String,
+    pub frame: AtlasRect,
+    #[serde(default)]
+    pub rotated: bool,
+    #[serde(default)]
+    pub trimmed: bool,
+    pub sprite_source_size: Option<AtlasRect>,
+    pub source_size: Option<AtlasSize>,
+    pub duration: Option<u32>,
+}
+
+impl AtlasFrame {
+    pub fn to_sprite(&self, id: u32) -> SpriteSheetJSONSprite {
+        // Rotated frames are stored 90 degrees clockwise, so they cover h x w in the image.
+        let (w, h) = match self.rotated {
+            true => (self.frame.h, self.frame.w),
+            false => (self.frame.w, self.frame.h),
+        };
+
+        let trim = match (self.trimmed, self.sprite_source_size, self.source_size) {
+            (true, Some(offset), Some(source)) => Some(SpriteSheetJSONTrim {
+                offset: [offset.x, offset.y],
+                source_size: [source.w, source.h],
+            }),
+            _ => None,
+        };
+
+        SpriteSheetJSONSprite {
+            name: self.filename.clone(),
+            id,
+            bounds: Rect2D {
+                x: self.frame.x,
+                y: self.frame.y,
+                w,
+                h,
+            },
+            trim,
+            rotated: Some(self.rotated),
+        }
+    }
+}
+
+// Frames come either as an array or as an object keyed by filename. Ids are assigned by
+// position, so the object form must keep document order.
+pub(crate) fn deserialize_frames<'de, D: Deserializer<'de>>(
+    de: D,
+) -> Result<Vec<AtlasFrame>, D::Error> {
+    struct FramesVisitor;
+
+    impl<'de> Visitor<'de> for FramesVisitor {
+        type Value = Vec<AtlasFrame>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "an array or map of atlas frames")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut frames = Vec::new();
+            while let Some(frame) = seq.next_element::<AtlasFrame>()? {
+                frames.push(frame);
+            }
+            Ok(frames)
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut frames = Vec::new();
+            while let Some((name, mut frame)) = map.next_entry::<String, AtlasFrame>()? {
+                frame.filename = name;
+                frames.push(frame);
+            }
+            Ok(frames)
+        }
+    }
+
+    de.deserialize_any(FramesVisitor)
+}
+
+/// Resolves an image referenced by an atlas file against the atlas file's own directory.
+pub(crate) fn relative_image_path(json_path: &str, image: &str) -> String {
+    let dir = Path::new(json_path).parent().unwrap_or(Path::new(""));
+    dir.join(image).to_string_lossy().to_string()
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TexturePackerMeta {
+    pub image: String,
+    pub size: Option<AtlasSize>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TexturePackerExport {
+    #[serde(deserialize_with = "deserialize_frames")]
+    pub frames: Vec<AtlasFrame>,
+    pub meta: TexturePackerMeta,
+}
+
+impl TexturePackerExport {
+    /// Converts the atlas into a sprite sheet entry. Frame ids follow file order.
+    pub fn to_sprite_sheet(&self, name: &str, json_path: &str) -> SpriteSheetJSONEntry {
+        SpriteSheetJSONEntry {
+            name: name.to_string(),
+            image_path: relative_image_path(json_path, &self.meta.image),
+            sprites: Some(
+                self.frames
+                    .iter()
+                    .enumerate()
+                    .map(|(id, f)| f.to_sprite(id as u32))
+                    .collect(),
+            ),
+            auto_gen: None,
+            animations: None,
+            texture: None,
+        }
+    }
+}
+
+pub fn parse_texture_packer(
+    name: &str,
+    json_path: &str,
+    json_data: &str,
+) -> Result<SpriteSheetJSONEntry, Error> {
+    let export: TexturePackerExport = serde_json::from_str(json_data)?;
+    Ok(export.to_sprite_sheet(name, json_path))
+}
+
+#[test]
+fn test_texture_packer() {
+    let hash = r#"{
+        "frames": {
+            "coin.png": {
+                "frame": {"x": 2, "y": 2, "w": 10, "h": 20},
+                "rotated": true,
+                "trimmed": true,
+                "spriteSourceSize": {"x": 3, "y": 1, "w": 10, "h": 20},
+                "sourceSize": {"w": 16, "h": 24}
+            },
+            "gem.png": {
+                "frame": {"x": 30, "y": 2, "w": 8, "h": 8},
+                "rotated": false,
+                "trimmed": false,
+                "spriteSourceSize": {"x": 0, "y": 0, "w": 8, "h": 8},
+                "sourceSize": {"w": 8, "h": 8}
+            }
+        },
+        "meta": { "image": "items.png", "size": {"w": 64, "h": 64} }
+    }"#;
+
+    let sheet = parse_texture_packer("items", "atlases/items.json", hash).unwrap();
+    assert_eq!(sheet.image_path, "atlases/items.png");
+
+    let sprites = sheet.sprites.unwrap();
+    let coin = &sprites[0];
+    assert_eq!(coin.name, "coin.png");
+    assert_eq!((coin.bounds.w, coin.bounds.h), (20, 10));
+    let trim = coin.trim.as_ref().unwrap();
+    assert_eq!(trim.offset, [3, 1]);
+    assert_eq!(trim.source_size, [16, 24]);
+
+    assert_eq!(sprites[1].id, 1);
+    assert!(sprites[1].trim.is_none());
+    assert_eq!(sprites[1].rotated, Some(false));
+}
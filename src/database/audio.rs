@@ -0,0 +1,316 @@
+use super::error::*;
+use super::json::*;
+use super::vfs::{FileReader, FileSystem};
+use lewton::inside_ogg::OggStreamReader;
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+// Samples per channel read from a WAV file at a time when streaming.
+const WAV_CHUNK_FRAMES: usize = 4096;
+
+/// Decoded PCM, interleaved 16-bit.
+#[derive(Clone, Debug, Default)]
+pub struct SoundData {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub samples: Vec<i16>,
+}
+
+impl SoundData {
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    pub fn duration_secs(&self) -> f32 {
+        self.frames() as f32 / self.sample_rate.max(1) as f32
+    }
+}
+
+pub struct SoundEntry {
+    pub cfg: AudioJSONEntry,
+    // Shared so sounds that are playing survive the entry being unloaded.
+    pub loaded: Option<Arc<SoundData>>,
+}
+
+impl SoundEntry {
+    pub fn try_load(&mut self, fs: &dyn FileSystem) -> Result<(), Error> {
+        let sound = load_sound(fs, &self.cfg.path).with_entry(&self.cfg.name)?;
+        self.loaded = Some(Arc::new(sound));
+        Ok(())
+    }
+
+    pub fn unload(&mut self) {
+        self.loaded = None;
+    }
+
+    /// Bytes of decoded samples currently held.
+    pub fn memory_size(&self) -> usize {
+        self.loaded.as_ref().map_or(0, |s| s.samples.len() * 2)
+    }
+}
+
+pub fn parse_sounds(info: AudioJSON) -> HashMap<String, SoundEntry> {
+    info.sounds
+        .into_iter()
+        .map(|cfg| (cfg.name.clone(), SoundEntry { cfg, loaded: None }))
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum WavEncoding {
+    Int(u16),
+    Float,
+}
+
+enum StreamSource {
+    Wav {
+        file: Box<dyn FileReader>,
+        encoding: WavEncoding,
+        // Where the samples start and how many bytes of them there are.
+        start: u64,
+        size: usize,
+        remaining: usize,
+    },
+    Ogg(Box<OggStreamReader<Box<dyn FileReader>>>),
+}
+
+/// Decodes a WAV or Ogg Vorbis file a piece at a time, for music that shouldn't sit fully
+/// decoded in memory.
+pub struct SoundStream {
+    channels: u16,
+    sample_rate: u32,
+    source: StreamSource,
+}
+
+impl SoundStream {
+    /// Opens the file and reads its header. The format is taken from the file's contents,
+    /// not its extension.
+    pub fn open(fs: &dyn FileSystem, path: &str) -> Result<Self, Error> {
+        let mut file = fs.open(path)?;
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic).with_path(path)?;
+        file.seek(SeekFrom::Start(0)).with_path(path)?;
+
+        match &magic {
+            b"RIFF" => Self::open_wav(file).with_path(path),
+            b"OggS" => {
+                let ogg = OggStreamReader::new(file).with_path(path)?;
+                Ok(Self {
+                    channels: ogg.ident_hdr.audio_channels as u16,
+                    sample_rate: ogg.ident_hdr.audio_sample_rate,
+                    source: StreamSource::Ogg(Box::new(ogg)),
+                })
+            }
+            _ => Err(Error::loading("not a WAV or Ogg Vorbis file").with_path(path)),
+        }
+    }
+
+    fn open_wav(mut file: Box<dyn FileReader>) -> Result<Self, Error> {
+        let mut header = [0u8; 12];
+        file.read_exact(&mut header)?;
+        if &header[8..12] != b"WAVE" {
+            return Err(Error::loading("RIFF file isn't a WAVE"));
+        }
+
+        let mut format: Option<(WavEncoding, u16, u32)> = None;
+        loop {
+            let mut chunk = [0u8; 8];
+            file.read_exact(&mut chunk)?;
+            let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as usize;
+
+            match &chunk[0..4] {
+                b"fmt " => {
+                    let mut fmt = vec![0u8; size];
+                    file.read_exact(&mut fmt)?;
+                    if fmt.len() < 16 {
+                        return Err(Error::loading("WAV format chunk is too short"));
+                    }
+
+                    let u16_at = |i: usize| u16::from_le_bytes([fmt[i], fmt[i + 1]]);
+                    let rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+                    // WAVE_FORMAT_EXTENSIBLE keeps the real format tag in its sub-format GUID.
+                    let tag = match u16_at(0) {
+                        0xFFFE if fmt.len() >= 26 => u16_at(24),
+                        tag => tag,
+                    };
+                    let encoding = match (tag, u16_at(14)) {
+                        (1, bits @ (8 | 16 | 24 | 32)) => WavEncoding::Int(bits),
+                        (3, 32) => WavEncoding::Float,
+                        (tag, bits) => {
+                            let msg = format!("unsupported WAV encoding {} at {} bits", tag, bits);
+                            return Err(Error::loading(msg));
+                        }
+                    };
+
+                    format = Some((encoding, u16_at(2), rate));
+                }
+                b"data" => {
+                    let (encoding, channels, sample_rate) = match format {
+                        Some(f) => f,
+                        None => return Err(Error::loading("WAV data comes before its format")),
+                    };
+
+                    return Ok(Self {
+                        channels,
+                        sample_rate,
+                        source: StreamSource::Wav {
+                            start: file.stream_position()?,
+                            file,
+                            encoding,
+                            size,
+                            remaining: size,
+                        },
+                    });
+                }
+                _ => {
+                    // Chunks are padded to an even size.
+                    file.seek(SeekFrom::Current((size + size % 2) as i64))?;
+                }
+            }
+        }
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Starts over from the beginning of the file.
+    pub fn rewind(&mut self) -> Result<(), Error> {
+        match &mut self.source {
+            StreamSource::Ogg(ogg) => ogg.seek_absgp_pg(0)?,
+            StreamSource::Wav {
+                file,
+                start,
+                size,
+                remaining,
+                ..
+            } => {
+                file.seek(SeekFrom::Start(*start))?;
+                *remaining = *size;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Appends the next piece of the file to `out`. Returns false once there's nothing left.
+    pub fn read(&mut self, out: &mut Vec<i16>) -> Result<bool, Error> {
+        match &mut self.source {
+            StreamSource::Ogg(ogg) => match ogg.read_dec_packet_itl()? {
+                Some(samples) => {
+                    out.extend(samples);
+                    Ok(true)
+                }
+                None => Ok(false),
+            },
+            StreamSource::Wav {
+                file,
+                encoding,
+                remaining,
+                ..
+            } => {
+                let width = match encoding {
+                    WavEncoding::Int(bits) => *bits as usize / 8,
+                    WavEncoding::Float => 4,
+                };
+                let frame = width * self.channels.max(1) as usize;
+                let len = (*remaining).min(WAV_CHUNK_FRAMES * frame) / frame * frame;
+                if len == 0 {
+                    return Ok(false);
+                }
+
+                let mut bytes = vec![0u8; len];
+                file.read_exact(&mut bytes)?;
+                *remaining -= len;
+                out.extend(bytes.chunks_exact(width).map(|s| wav_sample(*encoding, s)));
+                Ok(true)
+            }
+        }
+    }
+}
+
+fn wav_sample(encoding: WavEncoding, bytes: &[u8]) -> i16 {
+    match encoding {
+        // 8-bit WAV is the only unsigned one.
+        WavEncoding::Int(8) => ((bytes[0] as i16) - 128) << 8,
+        WavEncoding::Int(_) => {
+            let n = bytes.len();
+            i16::from_le_bytes([bytes[n - 2], bytes[n - 1]])
+        }
+        WavEncoding::Float => {
+            let v = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            (v.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+        }
+    }
+}
+
+/// Decodes a whole WAV or Ogg Vorbis file.
+pub fn load_sound(fs: &dyn FileSystem, path: &str) -> Result<SoundData, Error> {
+    let mut stream = SoundStream::open(fs, path)?;
+    let mut samples = Vec::new();
+    while stream.read(&mut samples).with_path(path)? {}
+
+    Ok(SoundData {
+        channels: stream.channels,
+        sample_rate: stream.sample_rate,
+        samples,
+    })
+}
+
+#[cfg(test)]
+pub(crate) fn test_wav(channels: u16, rate: u32, samples: &[i16]) -> Vec<u8> {
+    let data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+    let mut wav = Vec::new();
+    wav.extend(b"RIFF");
+    wav.extend((36 + data.len() as u32).to_le_bytes());
+    wav.extend(b"WAVEfmt ");
+    wav.extend(16u32.to_le_bytes());
+    wav.extend(1u16.to_le_bytes());
+    wav.extend(channels.to_le_bytes());
+    wav.extend(rate.to_le_bytes());
+    wav.extend((rate * channels as u32 * 2).to_le_bytes());
+    wav.extend((channels * 2).to_le_bytes());
+    wav.extend(16u16.to_le_bytes());
+    wav.extend(b"data");
+    wav.extend((data.len() as u32).to_le_bytes());
+    wav.extend(data);
+    wav
+}
+
+#[test]
+fn test_load_sound() {
+    use super::vfs::MemoryFileSystem;
+
+    let samples: Vec<i16> = (0..20000).map(|i| (i % 200) as i16 * 100).collect();
+    let fs = MemoryFileSystem::new()
+        .with("a.wav", test_wav(2, 22050, &samples))
+        .with("b.wav", "definitely not audio");
+
+    let sound = load_sound(&fs, "a.wav").unwrap();
+    assert_eq!((sound.channels, sound.sample_rate), (2, 22050));
+    assert_eq!(sound.samples, samples);
+    assert_eq!(sound.frames(), 10000);
+
+    // Streaming hands the same samples over in pieces.
+    let mut stream = SoundStream::open(&fs, "a.wav").unwrap();
+    let mut out = Vec::new();
+    let mut reads = 0;
+    while stream.read(&mut out).unwrap() {
+        reads += 1;
+    }
+    assert_eq!(out, samples);
+    assert!(reads > 1);
+
+    stream.rewind().unwrap();
+    let mut again = Vec::new();
+    while stream.read(&mut again).unwrap() {}
+    assert_eq!(again, samples);
+
+    let bad = load_sound(&fs, "b.wav").unwrap_err();
+    assert!(bad.to_string().contains("not a WAV"));
+}
@@ -0,0 +1,508 @@
+use super::error::*;
+
+/// Block compressed formats the loaders understand. Every block covers 4x4 pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockFormat {
+    /// RGB with 1 bit alpha, 8 bytes a block. DXT1 in older tools.
+    Bc1,
+    /// RGBA with smooth alpha, 16 bytes a block. DXT5 in older tools.
+    Bc3,
+    /// High quality RGBA, 16 bytes a block.
+    Bc7,
+}
+
+impl BlockFormat {
+    pub fn block_size(&self) -> usize {
+        match self {
+            BlockFormat::Bc1 => 8,
+            BlockFormat::Bc3 | BlockFormat::Bc7 => 16,
+        }
+    }
+
+    /// Bytes of one image of the given size. Sizes no file could hold come out as
+    /// `usize::MAX`.
+    pub fn image_size(&self, size: [u32; 2]) -> usize {
+        let blocks_x = size[0].div_ceil(4).max(1) as usize;
+        let blocks_y = size[1].div_ceil(4).max(1) as usize;
+        blocks_x.saturating_mul(blocks_y).saturating_mul(self.block_size())
+    }
+}
+
+type Block = [[u8; 4]; 16];
+
+/// Decodes a whole image to RGBA8 on the CPU.
+pub fn decode_blocks(format: BlockFormat, data: &[u8], size: [u32; 2]) -> Result<Vec<u8>, Error> {
+    if data.len() < format.image_size(size) {
+        let msg = format!(
+            "{:?} data for {}x{} needs {} bytes, there are {}",
+            format,
+            size[0],
+            size[1],
+            format.image_size(size),
+            data.len()
+        );
+        return Err(Error::loading(msg));
+    }
+
+    let [width, height] = size.map(|s| s as usize);
+    let blocks_x = width.div_ceil(4).max(1);
+    let mut out = vec![0u8; width * height * 4];
+    for (i, block) in data
+        .chunks_exact(format.block_size())
+        .take(format.image_size(size) / format.block_size())
+        .enumerate()
+    {
+        let pixels = match format {
+            BlockFormat::Bc1 => decode_bc1(block.try_into().unwrap(), false),
+            BlockFormat::Bc3 => decode_bc3(block.try_into().unwrap()),
+            BlockFormat::Bc7 => decode_bc7(block.try_into().unwrap()),
+        };
+
+        // Blocks reach past the edge of images that aren't a multiple of 4.
+        let (bx, by) = ((i % blocks_x) * 4, (i / blocks_x) * 4);
+        for (p, pixel) in pixels.iter().enumerate() {
+            let (x, y) = (bx + p % 4, by + p / 4);
+            if x < width && y < height {
+                let dst = (y * width + x) * 4;
+                out[dst..dst + 4].copy_from_slice(pixel);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+fn rgb565(c: u16) -> [u8; 4] {
+    let r = ((c >> 11) & 31) as u8;
+    let g = ((c >> 5) & 63) as u8;
+    let b = (c & 31) as u8;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+        255,
+    ]
+}
+
+// BC3 blocks always use the four color palette.
+fn decode_bc1(block: &[u8; 8], four_colors: bool) -> Block {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+
+    let mix = |a: u8, b: u8, wa: u32, wb: u32| ((a as u32 * wa + b as u32 * wb) / (wa + wb)) as u8;
+    let mut palette = [e0, e1, [0; 4], [0; 4]];
+    for c in 0..3 {
+        if four_colors || c0 > c1 {
+            palette[2][c] = mix(e0[c], e1[c], 2, 1);
+            palette[3][c] = mix(e0[c], e1[c], 1, 2);
+        } else {
+            palette[2][c] = mix(e0[c], e1[c], 1, 1);
+        }
+    }
+    palette[2][3] = 255;
+    // The fourth entry is transparent black in three color blocks.
+    palette[3][3] = if four_colors || c0 > c1 { 255 } else { 0 };
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|i| palette[((indices >> (2 * i)) & 3) as usize])
+}
+
+fn decode_bc3(block: &[u8; 16]) -> Block {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut alphas = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for (i, a) in alphas.iter_mut().enumerate().skip(2) {
+            *a = ((8 - i as u32) * a0 + (i as u32 - 1) * a1) / 7;
+        }
+    } else {
+        for (i, a) in alphas.iter_mut().enumerate().take(6).skip(2) {
+            *a = ((6 - i as u32) * a0 + (i as u32 - 1) * a1) / 5;
+        }
+    }
+
+    let mut indices = [0u8; 8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(indices);
+
+    let mut pixels = decode_bc1(block[8..].try_into().unwrap(), true);
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        pixel[3] = alphas[((indices >> (3 * i)) & 7) as usize] as u8;
+    }
+
+    pixels
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    index2_bits: u32,
+}
+
+const fn bc7_mode(f: [u32; 10]) -> Bc7Mode {
+    Bc7Mode {
+        subsets: f[0] as usize,
+        partition_bits: f[1],
+        rotation_bits: f[2],
+        index_selection_bits: f[3],
+        color_bits: f[4],
+        alpha_bits: f[5],
+        endpoint_pbits: f[6] == 1,
+        shared_pbits: f[7] == 1,
+        index_bits: f[8],
+        index2_bits: f[9],
+    }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    bc7_mode([3, 4, 0, 0, 4, 0, 1, 0, 3, 0]),
+    bc7_mode([2, 6, 0, 0, 6, 0, 0, 1, 3, 0]),
+    bc7_mode([3, 6, 0, 0, 5, 0, 0, 0, 2, 0]),
+    bc7_mode([2, 6, 0, 0, 7, 0, 1, 0, 2, 0]),
+    bc7_mode([1, 0, 2, 1, 5, 6, 0, 0, 2, 3]),
+    bc7_mode([1, 0, 2, 0, 7, 8, 0, 0, 2, 2]),
+    bc7_mode([1, 0, 0, 0, 7, 7, 1, 0, 4, 0]),
+    bc7_mode([2, 6, 0, 0, 5, 5, 1, 0, 2, 0]),
+];
+
+// Pixels in the second subset of each two subset partition, one bit per pixel.
+const BC7_PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800,
+    0xFFE8, 0xFF00, 0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC,
+    0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718,
+    0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+// Subset of every pixel in each three subset partition, two bits per pixel.
+const BC7_PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+// The pixel of the second subset whose index is stored with one bit less.
+const BC7_ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+// Same for the second and third subsets of three subset partitions.
+const BC7_ANCHORS_3A: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+
+const BC7_ANCHORS_3B: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+const BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+// Reads a block's fields from the lowest bit up.
+struct Bits(u128);
+
+impl Bits {
+    fn take(&mut self, count: u32) -> u32 {
+        let value = (self.0 & ((1u128 << count) - 1)) as u32;
+        self.0 >>= count;
+        value
+    }
+}
+
+fn bc7_interpolate(e0: u32, e1: u32, index_bits: u32, index: u32) -> u8 {
+    let w = match index_bits {
+        2 => BC7_WEIGHTS_2[index as usize],
+        3 => BC7_WEIGHTS_3[index as usize],
+        _ => BC7_WEIGHTS_4[index as usize],
+    };
+    (((64 - w) * e0 + w * e1 + 32) >> 6) as u8
+}
+
+fn decode_bc7(block: &[u8; 16]) -> Block {
+    let mut bits = Bits(u128::from_le_bytes(*block));
+    // The mode is the number of zero bits before the first one.
+    let mode = match (0..8).find(|_| bits.take(1) == 1) {
+        Some(m) => &BC7_MODES[m],
+        None => return [[0; 4]; 16],
+    };
+
+    let partition = bits.take(mode.partition_bits) as usize;
+    let rotation = bits.take(mode.rotation_bits);
+    let index_selection = bits.take(mode.index_selection_bits);
+
+    let endpoints = mode.subsets * 2;
+    let mut ep = [[0u32; 4]; 6];
+    for c in 0..3 {
+        for e in ep.iter_mut().take(endpoints) {
+            e[c] = bits.take(mode.color_bits);
+        }
+    }
+    for e in ep.iter_mut().take(endpoints) {
+        e[3] = bits.take(mode.alpha_bits);
+    }
+
+    // P-bits add one more low bit to every channel of an endpoint.
+    let has_pbits = mode.endpoint_pbits || mode.shared_pbits;
+    if has_pbits {
+        let mut pbits = [0u32; 6];
+        if mode.endpoint_pbits {
+            for p in pbits.iter_mut().take(endpoints) {
+                *p = bits.take(1);
+            }
+        } else {
+            for s in 0..mode.subsets {
+                let p = bits.take(1);
+                pbits[s * 2] = p;
+                pbits[s * 2 + 1] = p;
+            }
+        }
+
+        for (e, p) in ep.iter_mut().zip(pbits).take(endpoints) {
+            for c in e.iter_mut() {
+                *c = (*c << 1) | p;
+            }
+        }
+    }
+
+    let expand = |value: u32, count: u32| match count {
+        0 => 255,
+        _ => {
+            let v = value << (8 - count);
+            v | (v >> count)
+        }
+    };
+    let color_bits = mode.color_bits + has_pbits as u32;
+    let alpha_bits = match mode.alpha_bits {
+        0 => 0,
+        a => a + has_pbits as u32,
+    };
+    for e in ep.iter_mut().take(endpoints) {
+        for c in &mut e[..3] {
+            *c = expand(*c, color_bits);
+        }
+        e[3] = expand(e[3], alpha_bits);
+    }
+
+    let subset_of = |i: usize| match mode.subsets {
+        2 => ((BC7_PARTITIONS_2[partition] >> i) & 1) as usize,
+        3 => BC7_PARTITIONS_3[partition][i] as usize,
+        _ => 0,
+    };
+    let is_anchor = |i: usize| {
+        i == 0
+            || match mode.subsets {
+                2 => i == BC7_ANCHORS_2[partition] as usize,
+                3 => {
+                    i == BC7_ANCHORS_3A[partition] as usize
+                        || i == BC7_ANCHORS_3B[partition] as usize
+                }
+                _ => false,
+            }
+    };
+
+    let mut indices = [0u32; 16];
+    for (i, index) in indices.iter_mut().enumerate() {
+        *index = bits.take(mode.index_bits - is_anchor(i) as u32);
+    }
+    let mut indices2 = [0u32; 16];
+    if mode.index2_bits > 0 {
+        for (i, index) in indices2.iter_mut().enumerate() {
+            *index = bits.take(mode.index2_bits - (i == 0) as u32);
+        }
+    }
+
+    std::array::from_fn(|i| {
+        let s = subset_of(i);
+        let (e0, e1) = (ep[s * 2], ep[s * 2 + 1]);
+        // Modes 4 and 5 index color and alpha separately, mode 4 can swap the two sets.
+        let (color, alpha) = match (mode.index2_bits, index_selection) {
+            (0, _) => ((mode.index_bits, indices[i]), (mode.index_bits, indices[i])),
+            (_, 0) => (
+                (mode.index_bits, indices[i]),
+                (mode.index2_bits, indices2[i]),
+            ),
+            _ => (
+                (mode.index2_bits, indices2[i]),
+                (mode.index_bits, indices[i]),
+            ),
+        };
+
+        let mut pixel = [0u8; 4];
+        for c in 0..3 {
+            pixel[c] = bc7_interpolate(e0[c], e1[c], color.0, color.1);
+        }
+        pixel[3] = bc7_interpolate(e0[3], e1[3], alpha.0, alpha.1);
+
+        match rotation {
+            1 => pixel.swap(0, 3),
+            2 => pixel.swap(1, 3),
+            3 => pixel.swap(2, 3),
+            _ => {}
+        }
+        pixel
+    })
+}
+
+/// A mode 6 BC7 block going from opaque red in the first pixel to transparent black, with
+/// pixel `i` using index `i`.
+#[cfg(test)]
+pub(crate) fn test_bc7_block() -> [u8; 16] {
+    // Fields are packed from the lowest bit up.
+    let mut bits = 0u128;
+    let mut len = 0;
+    let mut put = |value: u32, count: u32| {
+        bits |= (value as u128) << len;
+        len += count;
+    };
+
+    put(1 << 6, 7);
+    // R, G, B and A of both endpoints, then their p-bits.
+    for value in [127, 0, 0, 0, 0, 0, 127, 0] {
+        put(value, 7);
+    }
+    put(1, 1);
+    put(0, 1);
+    put(0, 3);
+    for i in 1..16 {
+        put(i, 4);
+    }
+    assert_eq!(len, 128);
+    bits.to_le_bytes()
+}
+
+#[test]
+fn test_bcn() {
+    // Red to blue with four colors, then a three color block with a transparent pixel.
+    let bc1 = [
+        0x00,
+        0xF8,
+        0x1F,
+        0x00,
+        0b11_10_01_00,
+        0,
+        0,
+        0,
+        0x1F,
+        0x00,
+        0x00,
+        0xF8,
+        0b11_10_01_00,
+        0,
+        0,
+        0,
+    ];
+    let rgba = decode_blocks(BlockFormat::Bc1, &bc1, [8, 4]).unwrap();
+    let pixel = |x: usize, y: usize| &rgba[(y * 8 + x) * 4..(y * 8 + x) * 4 + 4];
+    assert_eq!(pixel(0, 0), [255, 0, 0, 255]);
+    assert_eq!(pixel(1, 0), [0, 0, 255, 255]);
+    assert_eq!(pixel(2, 0), [170, 0, 85, 255]);
+    assert_eq!(pixel(4, 0), [0, 0, 255, 255]);
+    assert_eq!(pixel(6, 0), [127, 0, 127, 255]);
+    assert_eq!(pixel(7, 0), [0, 0, 0, 0]);
+
+    let mut bc3 = [0u8; 16];
+    bc3[0] = 255;
+    bc3[1] = 0;
+    // Pixel 0 takes alpha index 0, pixel 1 index 1, pixel 2 index 2.
+    bc3[2] = 0b10_001_000;
+    bc3[8..].copy_from_slice(&bc1[..8]);
+    let rgba = decode_blocks(BlockFormat::Bc3, &bc3, [4, 4]).unwrap();
+    assert_eq!(
+        &rgba[..12],
+        &[255, 0, 0, 255, 0, 0, 255, 0, 170, 0, 85, 218]
+    );
+
+    let rgba = decode_blocks(BlockFormat::Bc7, &test_bc7_block(), [3, 3]).unwrap();
+    assert_eq!(rgba.len(), 3 * 3 * 4);
+    assert_eq!(&rgba[..4], &[255, 1, 1, 255]);
+    // Index 2 weighs the second endpoint 9/64.
+    let w = 9;
+    let mix = |a: u32| ((64 - w) * a + 32) >> 6;
+    assert_eq!(
+        &rgba[8..12],
+        &[mix(255) as u8, mix(1) as u8, mix(1) as u8, mix(255) as u8]
+    );
+    // Pixel 5 sits at x = 1, y = 1 and uses index 5.
+    let w5 = BC7_WEIGHTS_4[5];
+    assert_eq!(rgba[(3 + 1) * 4], (((64 - w5) * 255 + 32) >> 6) as u8);
+
+    assert!(decode_blocks(BlockFormat::Bc7, &[0; 15], [4, 4]).is_err());
+    // Mode 8 doesn't exist and decodes to nothing.
+    assert_eq!(decode_bc7(&[0; 16]), [[0; 4]; 16]);
+}
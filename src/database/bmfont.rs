@@ -0,0 +1,381 @@
+use super::atlas::relative_image_path;
+use super::error::*;
+use super::load_funcs::*;
+use super::vfs::FileSystem;
+use super::{TTFont, GLYPH_PAGE_SIZE};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BMFontChar {
+    pub id: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub xoffset: i32,
+    pub yoffset: i32,
+    pub xadvance: i32,
+    pub page: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BMFontKerning {
+    pub first: u32,
+    pub second: u32,
+    pub amount: i32,
+}
+
+/// Contents of an AngelCode BMFont descriptor. Sizes are in pixels of the page images.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BMFontDescriptor {
+    /// Negative when the font was generated with "match char height".
+    pub size: i32,
+    pub line_height: u32,
+    /// Distance from the top of a line to the baseline.
+    pub base: u32,
+    pub scale_w: u32,
+    pub scale_h: u32,
+    /// Page image file names, relative to the descriptor.
+    pub pages: Vec<String>,
+    pub chars: Vec<BMFontChar>,
+    pub kernings: Vec<BMFontKerning>,
+}
+
+// Splits `key=value` pairs, keeping quoted values with spaces in them together.
+pub(super) fn parse_attributes(text: &str) -> HashMap<&str, &str> {
+    let mut attrs = HashMap::new();
+    let mut rest = text.trim_start();
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].trim();
+        let after = &rest[eq + 1..];
+        let (value, next) = match after.strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            },
+            None => match after.find(char::is_whitespace) {
+                Some(end) => (&after[..end], &after[end..]),
+                None => (after, ""),
+            },
+        };
+
+        attrs.insert(key, value);
+        rest = next.trim_start();
+    }
+
+    attrs
+}
+
+fn build_descriptor<'a>(
+    records: impl Iterator<Item = (&'a str, HashMap<&'a str, &'a str>)>,
+) -> Option<BMFontDescriptor> {
+    let mut desc = BMFontDescriptor::default();
+    let mut has_common = false;
+    for (tag, attrs) in records {
+        let int = |key: &str| -> Option<i32> { attrs.get(key)?.parse().ok() };
+        let uint = |key: &str| -> Option<u32> { attrs.get(key)?.parse().ok() };
+
+        match tag {
+            "info" => desc.size = int("size").unwrap_or(0),
+            "common" => {
+                desc.line_height = uint("lineHeight")?;
+                desc.base = uint("base")?;
+                desc.scale_w = uint("scaleW")?;
+                desc.scale_h = uint("scaleH")?;
+                has_common = true;
+            }
+            "page" => {
+                let id = uint("id")? as usize;
+                if desc.pages.len() <= id {
+                    desc.pages.resize(id + 1, String::new());
+                }
+                desc.pages[id] = attrs.get("file")?.to_string();
+            }
+            "char" => desc.chars.push(BMFontChar {
+                id: uint("id")?,
+                x: uint("x")?,
+                y: uint("y")?,
+                width: uint("width")?,
+                height: uint("height")?,
+                xoffset: int("xoffset")?,
+                yoffset: int("yoffset")?,
+                xadvance: int("xadvance")?,
+                page: uint("page").unwrap_or(0),
+            }),
+            "kerning" => desc.kernings.push(BMFontKerning {
+                first: uint("first")?,
+                second: uint("second")?,
+                amount: int("amount")?,
+            }),
+            _ => {}
+        }
+    }
+
+    has_common.then_some(desc)
+}
+
+fn parse_text(text: &str) -> Option<BMFontDescriptor> {
+    build_descriptor(text.lines().filter_map(|line| {
+        let line = line.trim();
+        let (tag, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        (!tag.is_empty()).then(|| (tag, parse_attributes(rest)))
+    }))
+}
+
+fn parse_xml(text: &str) -> Option<BMFontDescriptor> {
+    // Only the self describing elements matter, so a tag scanner is all that's needed.
+    build_descriptor(text.split('<').skip(1).filter_map(|element| {
+        let element = element.split('>').next()?.trim_end_matches('/');
+        if element.starts_with(['?', '!', '/']) {
+            return None;
+        }
+
+        let (tag, rest) = element
+            .split_once(char::is_whitespace)
+            .unwrap_or((element, ""));
+        Some((tag, parse_attributes(rest)))
+    }))
+}
+
+struct BinaryReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BinaryReader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.data.len() < n {
+            return None;
+        }
+
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn i16(&mut self) -> Option<i16> {
+        Some(i16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+}
+
+fn parse_binary(data: &[u8]) -> Option<BMFontDescriptor> {
+    let mut desc = BMFontDescriptor::default();
+    let mut has_common = false;
+    let mut reader = BinaryReader { data: &data[4..] };
+    while !reader.data.is_empty() {
+        let kind = reader.u8()?;
+        let size = reader.u32()? as usize;
+        let mut block = BinaryReader {
+            data: reader.take(size)?,
+        };
+
+        match kind {
+            1 => desc.size = block.i16()? as i32,
+            2 => {
+                desc.line_height = block.u16()? as u32;
+                desc.base = block.u16()? as u32;
+                desc.scale_w = block.u16()? as u32;
+                desc.scale_h = block.u16()? as u32;
+                has_common = true;
+            }
+            3 => {
+                desc.pages = block
+                    .data
+                    .split(|b| *b == 0)
+                    .filter(|name| !name.is_empty())
+                    .map(|name| String::from_utf8_lossy(name).to_string())
+                    .collect();
+            }
+            4 => {
+                while !block.data.is_empty() {
+                    let mut c = BinaryReader {
+                        data: block.take(20)?,
+                    };
+                    desc.chars.push(BMFontChar {
+                        id: c.u32()?,
+                        x: c.u16()? as u32,
+                        y: c.u16()? as u32,
+                        width: c.u16()? as u32,
+                        height: c.u16()? as u32,
+                        xoffset: c.i16()? as i32,
+                        yoffset: c.i16()? as i32,
+                        xadvance: c.i16()? as i32,
+                        page: c.u8()? as u32,
+                    });
+                }
+            }
+            5 => {
+                while !block.data.is_empty() {
+                    desc.kernings.push(BMFontKerning {
+                        first: block.u32()?,
+                        second: block.u32()?,
+                        amount: block.i16()? as i32,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    has_common.then_some(desc)
+}
+
+/// Parses a BMFont descriptor in any of its three flavours: text, XML or binary (version 3).
+pub fn parse_bmfont(data: &[u8]) -> Option<BMFontDescriptor> {
+    if data.starts_with(b"BMF") {
+        return match data.get(3) {
+            Some(3) => parse_binary(data),
+            _ => None,
+        };
+    }
+
+    let text = std::str::from_utf8(data).ok()?;
+    match text.trim_start().starts_with('<') {
+        true => parse_xml(text),
+        false => parse_text(text),
+    }
+}
+
+/// Loads a BMFont descriptor along with its page images and packs the glyphs into a font.
+pub fn load_bmfont(fs: &dyn FileSystem, path: &str) -> Result<TTFont, Error> {
+    let data = fs.read(path)?;
+    let desc = parse_bmfont(&data)
+        .ok_or_else(|| Error::loading("not a valid BMFont descriptor").with_path(path))?;
+    if desc.pages.is_empty() {
+        return Err(Error::loading("BMFont descriptor lists no pages").with_path(path));
+    }
+
+    let images = desc
+        .pages
+        .iter()
+        .map(|page| load_image_rgba8(fs, &relative_image_path(path, page)))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let [w, h] = GLYPH_PAGE_SIZE;
+    Ok(TTFont::from_bitmap(&desc, &images, w, h))
+}
+
+#[test]
+fn test_bmfont() {
+    let text = "info face=\"Pixel Sans\" size=-12 bold=0\n\
+                common lineHeight=14 base=11 scaleW=64 scaleH=64 pages=1 packed=0\n\
+                page id=0 file=\"pixel sans_0.png\"\n\
+                chars count=2\n\
+                char id=65 x=1 y=2 width=5 height=7 xoffset=0 yoffset=4 xadvance=6 page=0 chnl=15\n\
+                char id=86 x=7 y=2 width=5 height=7 xoffset=-1 yoffset=4 xadvance=6 page=0 chnl=15\n\
+                kernings count=1\n\
+                kerning first=65 second=86 amount=-1\n";
+
+    let desc = parse_bmfont(text.as_bytes()).unwrap();
+    assert_eq!(desc.size, -12);
+    assert_eq!((desc.line_height, desc.base), (14, 11));
+    assert_eq!(desc.pages, vec!["pixel sans_0.png".to_string()]);
+    assert_eq!(desc.chars.len(), 2);
+    assert_eq!(desc.chars[1].xoffset, -1);
+    assert_eq!(desc.kernings[0].amount, -1);
+
+    let xml = "<?xml version=\"1.0\"?>\n<font>\n  <info face=\"Pixel Sans\" size=\"-12\"/>\n\
+               <common lineHeight=\"14\" base=\"11\" scaleW=\"64\" scaleH=\"64\" pages=\"1\"/>\n\
+               <pages><page id=\"0\" file=\"pixel sans_0.png\" /></pages>\n\
+               <chars count=\"2\">\n\
+               <char id=\"65\" x=\"1\" y=\"2\" width=\"5\" height=\"7\" xoffset=\"0\" yoffset=\"4\" xadvance=\"6\" page=\"0\" chnl=\"15\" />\n\
+               <char id=\"86\" x=\"7\" y=\"2\" width=\"5\" height=\"7\" xoffset=\"-1\" yoffset=\"4\" xadvance=\"6\" page=\"0\" chnl=\"15\" />\n\
+               </chars>\n<kernings count=\"1\"><kerning first=\"65\" second=\"86\" amount=\"-1\" /></kernings>\n</font>\n";
+    assert_eq!(parse_bmfont(xml.as_bytes()).unwrap(), desc);
+
+    let mut bin: Vec<u8> = b"BMF\x03".to_vec();
+    let mut block = |kind: u8, bytes: Vec<u8>| {
+        bin.push(kind);
+        bin.extend((bytes.len() as u32).to_le_bytes());
+        bin.extend(bytes);
+    };
+    block(
+        1,
+        [
+            (-12i16).to_le_bytes().to_vec(),
+            vec![0; 12],
+            b"Pixel Sans\0".to_vec(),
+        ]
+        .concat(),
+    );
+    block(
+        2,
+        [14u16, 11, 64, 64, 1]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .chain([0; 5])
+            .collect(),
+    );
+    block(3, b"pixel sans_0.png\0".to_vec());
+    let chars = desc.chars.iter().flat_map(|c| {
+        let mut b = c.id.to_le_bytes().to_vec();
+        for v in [c.x, c.y, c.width, c.height] {
+            b.extend((v as u16).to_le_bytes());
+        }
+        for v in [c.xoffset, c.yoffset, c.xadvance] {
+            b.extend((v as i16).to_le_bytes());
+        }
+        b.extend([c.page as u8, 15]);
+        b
+    });
+    block(4, chars.collect());
+    block(
+        5,
+        [65u32.to_le_bytes(), 86u32.to_le_bytes()]
+            .concat()
+            .into_iter()
+            .chain((-1i16).to_le_bytes())
+            .collect(),
+    );
+    assert_eq!(parse_bmfont(&bin).unwrap(), desc);
+
+    // Fully opaque pages take coverage from the red channel.
+    let mut bytes = vec![0u8; 64 * 64 * 4];
+    bytes.chunks_mut(4).for_each(|p| p[3] = 255);
+    bytes[(2 * 64 + 1) * 4] = 200;
+    let page = ImageLoadInfo {
+        size: [64, 64],
+        format: dashi::Format::RGBA8,
+        bytes,
+        mips: Vec::new(),
+    };
+
+    let mut font = TTFont::from_bitmap(&desc, &[page], 128, 128);
+    assert_eq!(font.kerning('A', 'V'), -1.0);
+    assert_eq!(font.advance('V'), 6.0);
+    assert_eq!(
+        (font.ascent, font.descent, font.line_height()),
+        (11.0, -3.0, 14.0)
+    );
+    let a = font.glyph('A').unwrap().bounds;
+    assert_eq!((a.w, a.h), (5, 7));
+    assert_eq!(font.pages[0].bitmap[(a.y * 128 + a.x) as usize], 200);
+    assert!(!font.has_glyph('Z'));
+
+    // A char whose rect runs off its page is skipped instead of reading past it.
+    let mut broken = desc.clone();
+    broken.chars[1].x = 62;
+    let page = ImageLoadInfo {
+        size: [64, 64],
+        format: dashi::Format::RGBA8,
+        bytes: vec![255; 64 * 64 * 4],
+        mips: Vec::new(),
+    };
+    let mut font = TTFont::from_bitmap(&broken, &[page], 128, 128);
+    assert!(font.glyph('A').is_some());
+    assert!(!font.has_glyph('V'));
+
+    assert!(parse_bmfont(b"BMF\x02").is_none());
+    assert!(parse_bmfont(b"info size=12\n").is_none());
+}
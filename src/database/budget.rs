@@ -0,0 +1,183 @@
+use super::*;
+
+/// Bytes of decoded asset data held in RAM, per category.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub sprites: usize,
+    pub sprite_sheets: usize,
+    pub fonts: usize,
+    pub sounds: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.sprites + self.sprite_sheets + self.fonts + self.sounds
+    }
+}
+
+/// The limit, plus when each entry was last fetched.
+#[derive(Default)]
+pub(crate) struct MemoryBudget {
+    limit: Option<usize>,
+    clock: u64,
+    last_used: HashMap<(AssetKind, String), u64>,
+}
+
+impl MemoryBudget {
+    pub(crate) fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            ..Default::default()
+        }
+    }
+}
+
+impl Database {
+    /// Caps how much decoded asset data is kept in RAM, in bytes. Going over it unloads the
+    /// least recently fetched sprites, sprite sheets, fonts and sounds, which are decoded
+    /// again the next time they're fetched. Sounds that are playing keep their own copy.
+    /// Fonts that a renderer's font handle still draws with count towards the budget but
+    /// aren't evicted, unloading them wouldn't free anything.
+    pub fn set_memory_budget(&mut self, bytes: Option<usize>) {
+        self.budget.limit = bytes;
+        self.enforce_budget(None);
+    }
+
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.budget.limit
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            sprites: self.sprites.values().map(|e| e.memory_size()).sum(),
+            sprite_sheets: self.sprite_sheets.values().map(|e| e.memory_size()).sum(),
+            fonts: self.ttfs.values().map(|e| e.memory_size()).sum(),
+            sounds: self.sounds.values().map(|e| e.memory_size()).sum(),
+        }
+    }
+
+    // Marks the entry as just used, then evicts others if it pushed usage over the budget.
+    pub(crate) fn touch(&mut self, kind: AssetKind, name: &str) {
+        self.budget.clock += 1;
+        let now = self.budget.clock;
+        self.budget.last_used.insert((kind, name.to_string()), now);
+        self.enforce_budget(Some((kind, name)));
+    }
+
+    fn enforce_budget(&mut self, keep: Option<(AssetKind, &str)>) {
+        let limit = match self.budget.limit {
+            Some(l) => l,
+            None => return,
+        };
+
+        let mut usage = self.memory_usage().total();
+        if usage <= limit {
+            return;
+        }
+
+        let sprites = self
+            .sprites
+            .iter()
+            .map(|(n, e)| (AssetKind::Sprite, n, e.memory_size()));
+        let sheets = self
+            .sprite_sheets
+            .iter()
+            .map(|(n, e)| (AssetKind::SpriteSheet, n, e.memory_size()));
+        let fonts = self
+            .ttfs
+            .iter()
+            .filter(|(_, e)| e.loaded.as_ref().is_some_and(|f| Arc::strong_count(f) == 1))
+            .map(|(n, e)| (AssetKind::TTF, n, e.memory_size()));
+        let sounds = self
+            .sounds
+            .iter()
+            .map(|(n, e)| (AssetKind::Sound, n, e.memory_size()));
+
+        let mut candidates: Vec<(u64, AssetKind, String, usize)> = sprites
+            .chain(sheets)
+            .chain(fonts)
+            .chain(sounds)
+            .filter(|(kind, name, size)| *size > 0 && keep != Some((*kind, name.as_str())))
+            .map(|(kind, name, size)| {
+                let used = self.budget.last_used.get(&(kind, name.clone()));
+                (used.copied().unwrap_or(0), kind, name.clone(), size)
+            })
+            .collect();
+        candidates.sort_by(|a, b| (a.0, &a.2).cmp(&(b.0, &b.2)));
+
+        for (_, kind, name, size) in candidates {
+            if usage <= limit {
+                break;
+            }
+
+            match kind {
+                AssetKind::Sprite => self.sprites.get_mut(&name).unwrap().unload(),
+                AssetKind::SpriteSheet => self.sprite_sheets.get_mut(&name).unwrap().unload(),
+                AssetKind::TTF => self.ttfs.get_mut(&name).unwrap().unload(),
+                AssetKind::Sound => self.sounds.get_mut(&name).unwrap().unload(),
+            }
+
+            self.budget.last_used.remove(&(kind, name));
+            usage -= size;
+        }
+    }
+}
+
+#[test]
+fn test_memory_budget() {
+    let fs = MemoryFileSystem::new()
+        .with("a.png", test_png(16, 16))
+        .with("pixel_0.png", test_png(16, 16))
+        .with("fonts.json", r#"{"fonts": [{"name": "pixel", "path": "pixel.fnt"}]}"#)
+        .with(
+            "pixel.fnt",
+            "common lineHeight=14 base=11 scaleW=16 scaleH=16 pages=1\n\
+             page id=0 file=\"pixel_0.png\"\n",
+        );
+    fs.insert(
+        "shoyu.json",
+        r#"{"sprite_cfg": "sprites.json", "bmfont_cfg": "fonts.json", "memory_budget_mb": 1}"#,
+    );
+    fs.insert(
+        "sprites.json",
+        r#"{"sprites": [
+            {"name": "a", "image_path": "a.png"},
+            {"name": "b", "image_path": "a.png"},
+            {"name": "c", "image_path": "a.png"}
+        ]}"#,
+    );
+
+    let mut db = Database::with_filesystem(Arc::new(fs)).unwrap();
+    assert_eq!(db.memory_budget(), Some(1024 * 1024));
+
+    // Room for two 16x16 RGBA images.
+    db.set_memory_budget(Some(2048));
+    db.fetch_sprite("a").unwrap();
+    db.fetch_sprite("b").unwrap();
+    db.fetch_sprite("c").unwrap();
+    assert!(!db.is_sprite_loaded("a"));
+    assert!(db.is_sprite_loaded("b") && db.is_sprite_loaded("c"));
+
+    // Fetching brings it back and pushes out whatever went unused the longest.
+    assert_eq!(db.fetch_sprite("a").unwrap().memory_size(), 1024);
+    assert!(!db.is_sprite_loaded("b"));
+    assert_eq!(db.memory_usage().sprites, 2048);
+
+    db.set_memory_budget(None);
+    db.fetch_sprite("b").unwrap();
+    assert_eq!(db.memory_usage().total(), 3072);
+
+    db.set_memory_budget(Some(1024));
+    assert_eq!(db.memory_usage().total(), 1024);
+    assert!(db.is_sprite_loaded("b"));
+
+    // A font stays while a font handle holds it, and goes once nothing does.
+    db.set_memory_budget(None);
+    let font = db.fetch_ttf("pixel").unwrap().loaded.clone().unwrap();
+    db.set_memory_budget(Some(0));
+    assert!(db.is_ttf_loaded("pixel"));
+    drop(font);
+    db.set_memory_budget(Some(0));
+    assert!(!db.is_ttf_loaded("pixel"));
+    assert_eq!(db.memory_usage().total(), 0);
+}
@@ -0,0 +1,331 @@
+use super::bcn::{decode_blocks, BlockFormat};
+use super::error::*;
+use super::load_funcs::ImageLoadInfo;
+use super::vfs::FileSystem;
+
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+const DDS_HEADER_SIZE: usize = 128;
+const DDS_DX10_HEADER_SIZE: usize = 20;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_FOURCC: u32 = 0x4;
+
+const KTX2_IDENTIFIER: &[u8; 12] = b"\xABKTX 20\xBB\r\n\x1A\n";
+const KTX2_HEADER_SIZE: usize = 80;
+const KTX2_LEVEL_SIZE: usize = 24;
+
+/// Block compressed image data with its mip chain, straight out of a DDS or KTX2 file.
+pub struct CompressedImage {
+    pub format: BlockFormat,
+    pub size: [u32; 2],
+    /// Every mip level, largest first.
+    pub levels: Vec<Vec<u8>>,
+}
+
+fn container_error(message: impl Into<String>) -> Error {
+    Error::loading(format!("bad compressed texture: {}", message.into()))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32, Error> {
+    match data.get(at..at + 4) {
+        Some(b) => Ok(u32::from_le_bytes(b.try_into().unwrap())),
+        None => Err(container_error("file is truncated")),
+    }
+}
+
+fn read_u64(data: &[u8], at: usize) -> Result<u64, Error> {
+    match data.get(at..at + 8) {
+        Some(b) => Ok(u64::from_le_bytes(b.try_into().unwrap())),
+        None => Err(container_error("file is truncated")),
+    }
+}
+
+/// Whether the bytes are a DDS or KTX2 file, which `load_image_rgba8` decodes itself.
+pub fn is_compressed_texture(data: &[u8]) -> bool {
+    data.starts_with(DDS_MAGIC) || data.starts_with(KTX2_IDENTIFIER)
+}
+
+impl CompressedImage {
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        if data.starts_with(DDS_MAGIC) {
+            Self::parse_dds(data)
+        } else if data.starts_with(KTX2_IDENTIFIER) {
+            Self::parse_ktx2(data)
+        } else {
+            Err(container_error("not a DDS or KTX2 file"))
+        }
+    }
+
+    /// Width and height of a mip level.
+    pub fn level_size(&self, level: usize) -> [u32; 2] {
+        self.size.map(|s| (s >> level).max(1))
+    }
+
+    // Files with more levels than the size has would shift the size to nothing.
+    fn check_levels(&self, count: u32) -> Result<(), Error> {
+        if self.size.contains(&0) {
+            return Err(container_error(format!(
+                "{}x{} image has no pixels",
+                self.size[0], self.size[1]
+            )));
+        }
+
+        let max = u32::BITS - self.size[0].max(self.size[1]).max(1).leading_zeros();
+        match count > max {
+            true => Err(container_error(format!(
+                "{} mip levels for a {}x{} image",
+                count, self.size[0], self.size[1]
+            ))),
+            false => Ok(()),
+        }
+    }
+
+    fn parse_dds(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < DDS_HEADER_SIZE {
+            return Err(container_error("file is truncated"));
+        }
+
+        let flags = read_u32(data, 8)?;
+        let size = [read_u32(data, 16)?, read_u32(data, 12)?];
+        let mip_count = match flags & DDSD_MIPMAPCOUNT {
+            0 => 1,
+            _ => read_u32(data, 28)?.max(1),
+        };
+
+        if read_u32(data, 80)? & DDPF_FOURCC == 0 {
+            return Err(container_error("uncompressed DDS files aren't supported"));
+        }
+
+        let (format, offset) = match &data[84..88] {
+            b"DXT1" => (BlockFormat::Bc1, DDS_HEADER_SIZE),
+            b"DXT5" => (BlockFormat::Bc3, DDS_HEADER_SIZE),
+            b"DX10" => {
+                let format = match read_u32(data, DDS_HEADER_SIZE)? {
+                    71 | 72 => BlockFormat::Bc1,
+                    77 | 78 => BlockFormat::Bc3,
+                    98 | 99 => BlockFormat::Bc7,
+                    f => return Err(container_error(format!("unsupported DXGI format {}", f))),
+                };
+                (format, DDS_HEADER_SIZE + DDS_DX10_HEADER_SIZE)
+            }
+            f => {
+                let f = String::from_utf8_lossy(f);
+                return Err(container_error(format!("unsupported DDS format {}", f)));
+            }
+        };
+
+        // The levels follow each other right after the headers.
+        let mut image = Self {
+            format,
+            size,
+            levels: Vec::new(),
+        };
+        image.check_levels(mip_count)?;
+        let mut offset = offset;
+        for level in 0..mip_count as usize {
+            let len = format.image_size(image.level_size(level));
+            match offset.checked_add(len).and_then(|end| data.get(offset..end)) {
+                Some(bytes) => image.levels.push(bytes.to_vec()),
+                None => return Err(container_error(format!("mip level {} is cut off", level))),
+            }
+            offset += len;
+        }
+
+        Ok(image)
+    }
+
+    fn parse_ktx2(data: &[u8]) -> Result<Self, Error> {
+        let format = match read_u32(data, 12)? {
+            131..=134 => BlockFormat::Bc1,
+            137 | 138 => BlockFormat::Bc3,
+            145 | 146 => BlockFormat::Bc7,
+            f => return Err(container_error(format!("unsupported Vulkan format {}", f))),
+        };
+        let size = [read_u32(data, 20)?, read_u32(data, 24)?.max(1)];
+        let level_count = read_u32(data, 40)?.max(1);
+        if read_u32(data, 44)? != 0 {
+            return Err(container_error(
+                "supercompressed KTX2 files aren't supported",
+            ));
+        }
+
+        let mut image = Self {
+            format,
+            size,
+            levels: Vec::new(),
+        };
+        image.check_levels(level_count)?;
+        for level in 0..level_count as usize {
+            let index = KTX2_HEADER_SIZE + level * KTX2_LEVEL_SIZE;
+            let offset = read_u64(data, index)?;
+            // Arrays and cube maps keep their other images after the first one.
+            let len = format.image_size(image.level_size(level)) as u64;
+            if read_u64(data, index + 8)? < len {
+                return Err(container_error(format!("mip level {} is too small", level)));
+            }
+            let end = match offset.checked_add(len) {
+                Some(end) => end,
+                None => return Err(container_error(format!("mip level {} is out of range", level))),
+            };
+            match end <= data.len() as u64 {
+                true => image.levels.push(data[offset as usize..end as usize].to_vec()),
+                false => return Err(container_error(format!("mip level {} is cut off", level))),
+            }
+        }
+
+        Ok(image)
+    }
+
+    /// Decompresses every level to RGBA8 on the CPU.
+    pub fn decode(&self) -> Result<ImageLoadInfo<u8>, Error> {
+        let mut levels = self.levels.iter().enumerate();
+        let decode = |(i, level): (usize, &Vec<u8>)| {
+            decode_blocks(self.format, level, self.level_size(i))
+        };
+        Ok(ImageLoadInfo {
+            size: self.size,
+            format: dashi::Format::RGBA8,
+            bytes: decode(levels.next().unwrap())?,
+            mips: levels.map(decode).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Reads a DDS or KTX2 file with BC1, BC3 or BC7 data without decompressing it.
+///
+/// Nothing uploads the blocks yet: dashi has no block compressed image formats, so the
+/// loaders decompress every file with `CompressedImage::decode` and the textures take as
+/// much VRAM as a PNG of the same size.
+pub fn load_compressed(fs: &dyn FileSystem, path: &str) -> Result<CompressedImage, Error> {
+    CompressedImage::parse(&fs.read(path)?).with_path(path)
+}
+
+#[test]
+fn test_compressed_textures() {
+    use super::bcn::test_bc7_block;
+    use super::json::{TextureColorSpace, TextureJSON};
+    use super::load_funcs::{load_image_rgba8, load_texture};
+    use super::vfs::MemoryFileSystem;
+
+    let dds_header = |fourcc: &[u8; 4], size: u32, mips: u32| {
+        let mut header = vec![0u8; DDS_HEADER_SIZE];
+        header[..4].copy_from_slice(DDS_MAGIC);
+        header[4..8].copy_from_slice(&124u32.to_le_bytes());
+        header[8..12].copy_from_slice(&(0x1007 | DDSD_MIPMAPCOUNT).to_le_bytes());
+        header[12..16].copy_from_slice(&size.to_le_bytes());
+        header[16..20].copy_from_slice(&size.to_le_bytes());
+        header[28..32].copy_from_slice(&mips.to_le_bytes());
+        header[76..80].copy_from_slice(&32u32.to_le_bytes());
+        header[80..84].copy_from_slice(&DDPF_FOURCC.to_le_bytes());
+        header[84..88].copy_from_slice(fourcc);
+        header
+    };
+
+    // Solid red 8x8 with a full mip chain, four blocks and then one for each smaller level.
+    let red = [0x00, 0xF8, 0x00, 0xF8, 0, 0, 0, 0];
+    let mut dxt1 = dds_header(b"DXT1", 8, 4);
+    dxt1.extend(red.repeat(4 + 1 + 1 + 1));
+
+    // Half transparent red 4x4 with its own two smaller levels.
+    let half_red = [128, 128, 0, 0, 0, 0, 0, 0, 0x00, 0xF8, 0x00, 0xF8, 0, 0, 0, 0];
+    let mut dxt5 = dds_header(b"DXT5", 4, 3);
+    dxt5.extend(half_red.repeat(3));
+
+    let mut dx10 = dds_header(b"DX10", 4, 1);
+    dx10.extend(98u32.to_le_bytes());
+    dx10.extend([0; 16]);
+    dx10.extend(test_bc7_block());
+
+    let mut ktx2 = vec![0u8; KTX2_HEADER_SIZE + 2 * KTX2_LEVEL_SIZE];
+    ktx2[..12].copy_from_slice(KTX2_IDENTIFIER);
+    ktx2[12..16].copy_from_slice(&145u32.to_le_bytes());
+    ktx2[20..24].copy_from_slice(&2u32.to_le_bytes());
+    ktx2[24..28].copy_from_slice(&2u32.to_le_bytes());
+    ktx2[40..44].copy_from_slice(&2u32.to_le_bytes());
+    // Smallest level first in the file, the index still lists the largest first.
+    let level = |k: &mut Vec<u8>, i: usize, offset: usize| {
+        let at = KTX2_HEADER_SIZE + i * KTX2_LEVEL_SIZE;
+        k[at..at + 8].copy_from_slice(&(offset as u64).to_le_bytes());
+        k[at + 8..at + 16].copy_from_slice(&16u64.to_le_bytes());
+        k[at + 16..at + 24].copy_from_slice(&16u64.to_le_bytes());
+    };
+    let base = ktx2.len();
+    level(&mut ktx2, 1, base);
+    level(&mut ktx2, 0, base + 16);
+    ktx2.extend([0; 16]);
+    ktx2.extend(test_bc7_block());
+
+    let mut truncated = dds_header(b"DXT5", 4, 1);
+    truncated.extend([0; 8]);
+    let mut supercompressed = ktx2.clone();
+    supercompressed[44] = 2;
+    // Offsets and sizes that would overflow, and an image with no pixels.
+    let mut wrapping = ktx2.clone();
+    wrapping[KTX2_HEADER_SIZE..KTX2_HEADER_SIZE + 8].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+    let mut huge = ktx2.clone();
+    huge[20..28].copy_from_slice(&[0xFF; 8]);
+    huge[40..44].copy_from_slice(&1u32.to_le_bytes());
+    let mut empty = ktx2.clone();
+    empty[20..24].copy_from_slice(&0u32.to_le_bytes());
+
+    let fs = MemoryFileSystem::new()
+        .with("a.dds", dxt1)
+        .with("alpha.dds", dxt5)
+        .with("b.dds", dx10)
+        .with("c.ktx2", ktx2)
+        .with("truncated.dds", truncated)
+        .with("zstd.ktx2", supercompressed)
+        .with("bc2.dds", dds_header(b"DXT3", 4, 1))
+        .with("wrapping.ktx2", wrapping)
+        .with("huge.ktx2", huge)
+        .with("empty.ktx2", empty)
+        .with("empty.dds", dds_header(b"DXT1", 0, 1));
+
+    let img = load_compressed(&fs, "a.dds").unwrap();
+    assert_eq!(img.format, BlockFormat::Bc1);
+    assert_eq!(img.levels.len(), 4);
+    assert_eq!(img.level_size(3), [1, 1]);
+
+    let img = load_image_rgba8(&fs, "a.dds").unwrap();
+    assert_eq!(img.size, [8, 8]);
+    assert_eq!(img.mip_levels(), 4);
+    assert_eq!(img.bytes, [255, 0, 0, 255].repeat(64));
+    assert_eq!(img.mips[0], [255, 0, 0, 255].repeat(16));
+    assert_eq!(img.mips[2], [255, 0, 0, 255]);
+    // The file's mip chain stays even when the entry doesn't ask for mipmaps.
+    let texture = TextureJSON::default();
+    assert_eq!(load_texture(&fs, "a.dds", &texture).unwrap().mip_levels(), 4);
+
+    // Every level the file brings is premultiplied, not just the first.
+    let texture = TextureJSON {
+        premultiply_alpha: true,
+        color_space: TextureColorSpace::Linear,
+        ..Default::default()
+    };
+    let img = load_texture(&fs, "alpha.dds", &texture).unwrap();
+    assert_eq!(img.bytes, [128, 0, 0, 128].repeat(16));
+    assert_eq!(img.mips, [[128, 0, 0, 128].repeat(4), vec![128, 0, 0, 128]]);
+
+    let img = load_image_rgba8(&fs, "b.dds").unwrap();
+    assert_eq!(&img.bytes[..4], &[255, 1, 1, 255]);
+
+    let ktx2 = load_compressed(&fs, "c.ktx2").unwrap();
+    assert_eq!(ktx2.format, BlockFormat::Bc7);
+    assert_eq!(ktx2.levels[0], test_bc7_block());
+    let img = ktx2.decode().unwrap();
+    assert_eq!((img.size, img.mip_levels()), ([2, 2], 2));
+    assert_eq!(&img.bytes[..4], &[255, 1, 1, 255]);
+
+    let broken = [
+        "truncated.dds",
+        "zstd.ktx2",
+        "bc2.dds",
+        "wrapping.ktx2",
+        "huge.ktx2",
+        "empty.ktx2",
+        "empty.dds",
+    ];
+    for path in broken {
+        let e = load_image_rgba8(&fs, path).err().unwrap();
+        assert_eq!(e.path(), Some(path));
+    }
+}
@@ -1,4 +1,7 @@
 use std::fmt;
+
+type Source = Box<dyn std::error::Error + Send + Sync>;
+
 #[derive(Debug)]
 pub struct SlotError {}
 
@@ -7,10 +10,22 @@ pub struct LookupError {
     pub entry: String,
 }
 
+/// Something couldn't be read, decoded or created. Carries whatever is known about where
+/// it happened, plus the error underneath it when there is one.
 #[derive(Debug)]
 pub struct LoadingError {
-    pub entry: String,
-    pub path: String,
+    pub entry: Option<String>,
+    pub path: Option<String>,
+    /// Line and column in the file, both starting at 1. Set for JSON errors.
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+    pub source: Option<Source>,
+}
+
+/// A GPU resource couldn't be created.
+#[derive(Debug)]
+pub struct GpuError {
+    pub message: String,
 }
 
 impl fmt::Display for SlotError {
@@ -31,55 +46,208 @@ impl fmt::Display for LookupError {
 
 impl fmt::Display for LoadingError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "Failed to load requested entry {} in database! Attempted path: {}",
-            self.entry, self.path
-        )
+        write!(f, "Failed to load")?;
+        if let Some(entry) = self.entry.as_ref() {
+            write!(f, " entry {}", entry)?;
+        }
+        if let Some(path) = self.path.as_ref() {
+            write!(f, " from {}", path)?;
+        }
+        if let Some((line, column)) = self.position {
+            write!(f, " at line {}, column {}", line, column)?;
+        }
+
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GPU error: {}", self.message)
+    }
+}
+
+impl std::error::Error for SlotError {}
+impl std::error::Error for LookupError {}
+impl std::error::Error for GpuError {}
+
+impl std::error::Error for LoadingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|s| s.as_ref() as &(dyn std::error::Error + 'static))
     }
 }
+
 #[derive(Debug)]
 pub enum Error {
     LookupError(LookupError),
     LoadingError(LoadingError),
     SlotError(),
+    GpuError(GpuError),
+}
+
+impl Error {
+    pub fn lookup(entry: &str) -> Self {
+        Error::LookupError(LookupError {
+            entry: entry.to_string(),
+        })
+    }
+
+    pub fn loading(message: impl Into<String>) -> Self {
+        Error::LoadingError(LoadingError {
+            entry: None,
+            path: None,
+            position: None,
+            message: message.into(),
+            source: None,
+        })
+    }
+
+    fn caused_by(message: String, position: Option<(usize, usize)>, source: Source) -> Self {
+        Error::LoadingError(LoadingError {
+            entry: None,
+            path: None,
+            position,
+            message,
+            source: Some(source),
+        })
+    }
+
+    /// Names the entry being loaded, unless a more specific one is already set.
+    pub fn with_entry(mut self, entry: &str) -> Self {
+        if let Error::LoadingError(e) = &mut self {
+            e.entry.get_or_insert_with(|| entry.to_string());
+        }
+        self
+    }
+
+    /// Names the file being read, unless a more specific one is already set.
+    pub fn with_path(mut self, path: &str) -> Self {
+        if let Error::LoadingError(e) = &mut self {
+            e.path.get_or_insert_with(|| path.to_string());
+        }
+        self
+    }
+
+    /// Puts the mount's name in front of the path, so it's clear which of several
+    /// stacked databases the file is in.
+    pub fn in_mount(mut self, mount: &str) -> Self {
+        if let Error::LoadingError(e) = &mut self {
+            e.path = Some(match e.path.take() {
+                Some(path) => format!("{}:{}", mount, path),
+                None => mount.to_string(),
+            });
+        }
+        self
+    }
+
+    pub fn entry(&self) -> Option<&str> {
+        match self {
+            Error::LookupError(e) => Some(&e.entry),
+            Error::LoadingError(e) => e.entry.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Error::LoadingError(e) => e.path.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Line and column of the error inside its file, when known.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match self {
+            Error::LoadingError(e) => e.position,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::LookupError(e) => e.fmt(f),
+            Error::LoadingError(e) => e.fmt(f),
+            Error::SlotError() => SlotError {}.fmt(f),
+            Error::GpuError(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::LoadingError(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+/// Attaches where an error happened on its way up.
+pub trait ErrorContext<T> {
+    fn with_entry(self, entry: &str) -> Result<T, Error>;
+    fn with_path(self, path: &str) -> Result<T, Error>;
+}
+
+impl<T, E: Into<Error>> ErrorContext<T> for Result<T, E> {
+    fn with_entry(self, entry: &str) -> Result<T, Error> {
+        self.map_err(|e| e.into().with_entry(entry))
+    }
+
+    fn with_path(self, path: &str) -> Result<T, Error> {
+        self.map_err(|e| e.into().with_path(path))
+    }
 }
 
 impl From<String> for Error {
     fn from(value: String) -> Self {
-        return Error::LoadingError(LoadingError {
-            entry: "[UNKNOWN]".to_string(),
-            path: value,
-        });
+        Error::loading(value)
     }
 }
 
 impl From<image::ImageError> for Error {
     fn from(value: image::ImageError) -> Self {
-        return Error::LoadingError(LoadingError {
-            entry: "[UNKNOWN]".to_string(),
-            path: value.to_string(),
-        });
+        Error::caused_by(value.to_string(), None, Box::new(value))
     }
 }
 
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
-        return Error::LoadingError(LoadingError {
-            entry: "[UNKNOWN]".to_string(),
-            path: value.to_string(),
-        });
+        Error::caused_by(value.to_string(), None, Box::new(value))
+    }
+}
+
+impl From<std::string::FromUtf8Error> for Error {
+    fn from(value: std::string::FromUtf8Error) -> Self {
+        Error::caused_by(value.to_string(), None, Box::new(value))
     }
 }
 
 impl From<serde_json::Error> for Error {
     fn from(value: serde_json::Error) -> Self {
-        return Error::LoadingError(LoadingError {
-            entry: "JSON FILE".to_string(),
-            path: value.to_string(),
-        });
+        // serde_json reports line 0 for errors that aren't tied to a spot in the input.
+        let position = Some((value.line(), value.column())).filter(|(l, _)| *l > 0);
+        Error::caused_by(value.to_string(), position, Box::new(value))
+    }
+}
+
+impl From<lewton::VorbisError> for Error {
+    fn from(value: lewton::VorbisError) -> Self {
+        Error::caused_by(value.to_string(), None, Box::new(value))
+    }
+}
+
+impl From<dashi::GPUError> for Error {
+    fn from(value: dashi::GPUError) -> Self {
+        Error::GpuError(GpuError {
+            message: format!("{:?}", value),
+        })
     }
 }
+
 //impl From<ash::vk::Result> for GPUError {
 //    fn from(res: ash::vk::Result) -> Self {
 //        return GPUError::VulkanError(VulkanError { res });
@@ -91,3 +259,28 @@ impl From<serde_json::Error> for Error {
 //        return GPUError::LoadingError(res);
 //    }
 //}
+
+#[test]
+fn test_error_context() {
+    use std::error::Error as _;
+
+    let json = serde_json::from_str::<Vec<u32>>("[1,\n 2,\n x]").unwrap_err();
+    let err = Err::<(), _>(json)
+        .with_path("sprites.json")
+        .with_entry("hero")
+        .with_entry("outer")
+        .unwrap_err();
+
+    assert_eq!(err.entry(), Some("hero"));
+    assert_eq!(err.path(), Some("sprites.json"));
+    assert_eq!(err.position(), Some((3, 2)));
+    assert!(err.source().is_some());
+    assert!(err
+        .to_string()
+        .starts_with("Failed to load entry hero from sprites.json at line 3, column 2: "));
+
+    let missing = std::fs::read("/definitely/not/here").with_path("/definitely/not/here");
+    let err = missing.unwrap_err();
+    assert!(err.source().unwrap().is::<std::io::Error>());
+    assert_eq!(Error::lookup("x").entry(), Some("x"));
+}
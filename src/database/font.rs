@@ -1,28 +1,141 @@
+use super::bmfont::BMFontDescriptor;
+use super::error::*;
+use super::load_funcs::ImageLoadInfo;
+use crate::utils::RectPacker;
 use dashi::Rect2D;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+/// Size of each glyph atlas page for fonts loaded through the database.
+pub const GLYPH_PAGE_SIZE: [u32; 2] = [1280, 1024];
+/// Distance field spread used when a font entry doesn't set one.
+pub const DEFAULT_SDF_SPREAD: u32 = 6;
+const GLYPH_PADDING: u32 = 1;
+
+/// How glyphs are stored in the atlas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GlyphMode {
+    /// Plain coverage. Looks best drawn at the size it was rasterized at.
+    Coverage,
+    /// Signed distance field reaching `spread` pixels to either side of the outline. Stays
+    /// sharp at any scale.
+    Sdf { spread: u32 },
+}
 
 #[derive(Debug)]
 pub struct Glyph {
     pub bounds: Rect2D, // where this glyph is in the atlas
+    pub page: u32,      // which atlas page `bounds` points into
     pub advance: f32,
     pub bearing_x: f32,
     pub bearing_y: f32,
 }
 
+pub struct GlyphPage {
+    pub bitmap: Vec<u8>,
+    packer: RectPacker,
+    dirty: Vec<Rect2D>,
+}
+
+impl GlyphPage {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            bitmap: vec![0u8; (width * height) as usize],
+            packer: RectPacker::new(width, height, GLYPH_PADDING),
+            dirty: Vec::new(),
+        }
+    }
+}
+
+// A glyph's coverage before it's placed in an atlas. Positions are in pixels.
+struct RawGlyph {
+    coverage: Vec<u8>,
+    width: u32,
+    height: u32,
+    xmin: i32,
+    ymin: i32,
+    advance: f32,
+}
+
+// Whitespace and control characters missing from every font just take up no room.
+fn shows_tofu(ch: char) -> bool {
+    !(ch.is_whitespace() || ch.is_control())
+}
+
+enum GlyphSource {
+    Outline(fontdue::Font),
+    // Bitmap fonts come with every glyph up front, so there's nothing to rasterize.
+    Bitmap(HashMap<(char, char), f32>),
+}
+
+/// Glyph cache for a TrueType font. Glyphs get rasterized the first time they are asked
+/// for, and a new atlas page is started whenever the current ones are full. Bitmap fonts
+/// are copied into the same kind of pages, so both draw the same way.
+///
+/// Characters the font doesn't have are taken from its fallbacks, in order, and drawn in
+/// this font's own pages. When none of them have it either a hollow "tofu" box is drawn.
 pub struct TTFont {
+    source: GlyphSource,
+    pub font_size: f32,
+    pub mode: GlyphMode,
+    // Line metrics in pixels at `font_size`. Descent is negative, below the baseline.
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
     pub glyphs: HashMap<char, Glyph>,
-    pub atlas: Option<Vec<u8>>, // Cache for the entire glyph atlas as a single image
+    pub pages: Vec<GlyphPage>,
     pub atlas_width: u32,
     pub atlas_height: u32,
+    rejected: HashSet<char>,
+    fallbacks: Vec<TTFont>,
 }
 
 impl TTFont {
-    /// Creates a new TTFont instance by loading a font from a specified file path.
+    /// Creates a new TTFont instance by loading a font from a specified file path. `range`
+    /// is rasterized up front, everything else on demand.
     pub fn new(file_path: &str, width: u32, height: u32, font_size: f32, range: &[char]) -> Self {
-        // Create a new bitmap (initialized to zero)
-        let mut bitmap = vec![0u8; (width * height) as usize];
+        Self::with_mode(
+            file_path,
+            width,
+            height,
+            font_size,
+            GlyphMode::Coverage,
+            range,
+        )
+    }
 
-        let font_data = std::fs::read(file_path).unwrap();
+    pub fn with_mode(
+        file_path: &str,
+        width: u32,
+        height: u32,
+        font_size: f32,
+        mode: GlyphMode,
+        range: &[char],
+    ) -> Self {
+        Self::try_with_mode(file_path, width, height, font_size, mode, range).unwrap()
+    }
+
+    /// Same as `with_mode`, but a missing or malformed font file comes back as an error.
+    pub fn try_with_mode(
+        file_path: &str,
+        width: u32,
+        height: u32,
+        font_size: f32,
+        mode: GlyphMode,
+        range: &[char],
+    ) -> Result<Self, Error> {
+        let font_data = std::fs::read(file_path).with_path(file_path)?;
+        Self::try_from_bytes(font_data, width, height, font_size, mode, range).with_path(file_path)
+    }
+
+    /// Same as `try_with_mode`, for a font file that's already in memory.
+    pub fn try_from_bytes(
+        font_data: Vec<u8>,
+        width: u32,
+        height: u32,
+        font_size: f32,
+        mode: GlyphMode,
+        range: &[char],
+    ) -> Result<Self, Error> {
         let font = fontdue::Font::from_bytes(
             font_data,
             fontdue::FontSettings {
@@ -30,64 +143,550 @@ impl TTFont {
                 ..Default::default()
             },
         )
-        .unwrap();
+        .map_err(Error::loading)?;
 
-        // Coordinates to keep track of where to draw the next glyph
-        let mut cursor_x: u32 = font_size as u32;
-        let mut cursor_y: u32 = font_size as u32;
-        let mut glyph_map = HashMap::new();
-        let mut max_height_in_line: f32 = 0.0; // Track the tallest glyph in the line
+        // Fonts without a horizontal header get treated as sitting entirely above the baseline.
+        let (ascent, descent, line_gap) = match font.horizontal_line_metrics(font_size) {
+            Some(m) => (m.ascent, m.descent, m.line_gap),
+            None => (font_size, 0.0, 0.0),
+        };
+
+        let mut ttf = Self {
+            source: GlyphSource::Outline(font),
+            font_size,
+            mode,
+            ascent,
+            descent,
+            line_gap,
+            glyphs: HashMap::new(),
+            pages: vec![GlyphPage::new(width, height)],
+            atlas_width: width,
+            atlas_height: height,
+            rejected: HashSet::new(),
+            fallbacks: Vec::new(),
+        };
 
         for ch in range {
-            let (metrics, bitmap_data) =
-                font.rasterize_indexed(font.lookup_glyph_index(*ch), font_size);
-            {
-                // Update the max height for the current line
-                max_height_in_line =
-                    max_height_in_line.max(metrics.height as f32 - metrics.ymin as f32);
-
-                // Draw the glyph bitmap at the calculated position
-                for (i, &pixel) in bitmap_data.iter().enumerate() {
-                    let px = i % metrics.width;
-                    let py = i / metrics.width;
-                    let x = (cursor_x as usize) + px;
-                    let y = (cursor_y  as i32) as usize + py;
-
-                    bitmap[y * width as usize + x] = pixel;
+            ttf.glyph(*ch);
+        }
+
+        Ok(ttf)
+    }
+
+    /// Builds a font out of a BMFont descriptor and its page images. Coverage comes from
+    /// each page's alpha, or from its red channel when the page is fully opaque.
+    pub fn from_bitmap(
+        desc: &BMFontDescriptor,
+        images: &[ImageLoadInfo<u8>],
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let coverage: Vec<Vec<u8>> = images
+            .iter()
+            .map(|img| {
+                let opaque = img.bytes.chunks(4).all(|p| p[3] == 255);
+                let channel = if opaque { 0 } else { 3 };
+                img.bytes.chunks(4).map(|p| p[channel]).collect()
+            })
+            .collect();
+
+        let kerning = desc
+            .kernings
+            .iter()
+            .filter_map(|k| {
+                let pair = (char::from_u32(k.first)?, char::from_u32(k.second)?);
+                Some((pair, k.amount as f32))
+            })
+            .collect();
+
+        let base = desc.base as f32;
+        let mut ttf = Self {
+            source: GlyphSource::Bitmap(kerning),
+            font_size: desc.size.unsigned_abs() as f32,
+            mode: GlyphMode::Coverage,
+            ascent: base,
+            descent: base - desc.line_height as f32,
+            line_gap: 0.0,
+            glyphs: HashMap::new(),
+            pages: vec![GlyphPage::new(width, height)],
+            atlas_width: width,
+            atlas_height: height,
+            rejected: HashSet::new(),
+            fallbacks: Vec::new(),
+        };
+
+        for c in &desc.chars {
+            let ch = match char::from_u32(c.id) {
+                Some(ch) => ch,
+                None => continue,
+            };
+
+            let (src, size) = match (coverage.get(c.page as usize), images.get(c.page as usize)) {
+                (Some(src), Some(img)) => (src, img.size),
+                _ => {
+                    println!("BMFont glyph {} points at missing page {}!", c.id, c.page);
+                    continue;
                 }
+            };
 
-                // Store the glyph boundary
-                glyph_map.insert(
-                    *ch,
-                    Glyph {
-                        bounds: Rect2D {
-                            x: cursor_x as u32,
-                            y: cursor_y as u32,
-                            w: metrics.width as u32,
-                            h: metrics.height as u32,
-                        },
-                        advance: metrics.advance_width / (width as f32),
-                        bearing_x: metrics.xmin as f32 / width as f32,
-                        bearing_y: metrics.ymin as f32 / height as f32,
-                    },
-                );
-                // Move the cursor forward by the advance width
-                cursor_x += (metrics.advance_width + metrics.xmin as f32) as u32;
-
-                // If the cursor_x exceeds the bitmap width, move to the next line
-                if cursor_x >= width {
-                    cursor_x = font_size as u32;
-                    cursor_y += max_height_in_line as u32 + font_size as u32; // Move down by the tallest glyph in the line
-                    max_height_in_line = 0.0; // Reset for the next line
+            let inside = c.x as u64 + c.width as u64 <= size[0] as u64
+                && c.y as u64 + c.height as u64 <= size[1] as u64;
+            if !inside {
+                println!("BMFont glyph {} reaches outside page {}!", c.id, c.page);
+                continue;
+            }
+
+            let (page, bounds) = match c.width == 0 || c.height == 0 {
+                true => (0, Rect2D::default()),
+                false => match ttf.allocate(c.width, c.height) {
+                    Some((page, rect)) => {
+                        let src_stride = size[0] as usize;
+                        let target = &mut ttf.pages[page as usize];
+                        for row in 0..c.height as usize {
+                            let from = (c.y as usize + row) * src_stride + c.x as usize;
+                            let to = (rect.y as usize + row) * width as usize + rect.x as usize;
+                            target.bitmap[to..to + c.width as usize]
+                                .copy_from_slice(&src[from..from + c.width as usize]);
+                        }
+
+                        target.dirty.push(rect);
+                        (page, rect)
+                    }
+                    None => {
+                        println!(
+                            "BMFont glyph {} doesn't fit in a {}x{} page!",
+                            c.id, width, height
+                        );
+                        continue;
+                    }
+                },
+            };
+
+            // BMFont measures down from the top of the line, glyphs here sit on the baseline.
+            let ymin = base - c.yoffset as f32 - c.height as f32;
+            ttf.glyphs.insert(
+                ch,
+                Glyph {
+                    bounds,
+                    page,
+                    advance: c.xadvance as f32 / width as f32,
+                    bearing_x: c.xoffset as f32 / width as f32,
+                    bearing_y: ymin / height as f32,
+                },
+            );
+        }
+
+        ttf
+    }
+
+    /// Looks up a glyph, rasterizing it into the atlas if this is the first time it's used.
+    /// Returns `None` for glyphs too large to ever fit on a page, and for whitespace or
+    /// control characters that no font in the chain has.
+    pub fn glyph(&mut self, ch: char) -> Option<&Glyph> {
+        if !self.glyphs.contains_key(&ch) && !self.rejected.contains(&ch) {
+            match self.rasterize(ch) {
+                Some(g) => {
+                    self.glyphs.insert(ch, g);
+                }
+                None => {
+                    self.rejected.insert(ch);
                 }
             }
         }
 
-        Self {
-            glyphs: glyph_map,
-            atlas: Some(bitmap),
-            atlas_width: width,
-            atlas_height: height,
+        self.glyphs.get(&ch)
+    }
+
+    /// Rasterizes the characters this font or one of its fallbacks has. Unlike `glyph`,
+    /// characters nobody has don't get a tofu box each.
+    pub fn preload(&mut self, chars: &[char]) {
+        for ch in chars {
+            if self.has_glyph(*ch) || self.fallbacks.iter().any(|f| f.has_glyph(*ch)) {
+                self.glyph(*ch);
+            }
+        }
+    }
+
+    /// Adds a font to look in for characters this one lacks. Fallbacks are searched in the
+    /// order they were added and should be loaded at this font's size in coverage mode.
+    pub fn add_fallback(&mut self, font: TTFont) {
+        self.fallbacks.push(font);
+    }
+
+    /// Distance from one baseline to the next, in pixels.
+    pub fn line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+
+    /// Whether this font itself has the character, without looking at the fallbacks.
+    pub fn has_glyph(&self, ch: char) -> bool {
+        match &self.source {
+            GlyphSource::Outline(f) => f.lookup_glyph_index(ch) != 0,
+            GlyphSource::Bitmap(_) => self.glyphs.contains_key(&ch),
+        }
+    }
+
+    /// Horizontal advance of a character in pixels. Doesn't rasterize anything.
+    pub fn advance(&self, ch: char) -> f32 {
+        self.own_advance(ch)
+            .or_else(|| self.fallbacks.iter().find_map(|f| f.own_advance(ch)))
+            .unwrap_or_else(|| match shows_tofu(ch) {
+                true => self.tofu().advance,
+                false => 0.0,
+            })
+    }
+
+    fn own_advance(&self, ch: char) -> Option<f32> {
+        if let Some(g) = self.glyphs.get(&ch) {
+            return Some(g.advance * self.atlas_width as f32);
+        }
+
+        match &self.source {
+            GlyphSource::Outline(f) if self.has_glyph(ch) => {
+                Some(f.metrics(ch, self.font_size).advance_width)
+            }
+            _ => None,
+        }
+    }
+
+    /// Horizontal kerning between two characters in pixels. Usually negative.
+    pub fn kerning(&self, left: char, right: char) -> f32 {
+        match &self.source {
+            GlyphSource::Outline(f) => f
+                .horizontal_kern(left, right, self.font_size)
+                .unwrap_or(0.0),
+            GlyphSource::Bitmap(pairs) => pairs.get(&(left, right)).copied().unwrap_or(0.0),
+        }
+    }
+
+    /// Bytes held by the glyph pages, fallbacks included.
+    pub fn memory_size(&self) -> usize {
+        let own: usize = self.pages.iter().map(|p| p.bitmap.len()).sum();
+        own + self.fallbacks.iter().map(|f| f.memory_size()).sum::<usize>()
+    }
+
+    /// Regions of each page written since the last call, as `(page, region)` pairs.
+    pub fn take_dirty(&mut self) -> Vec<(u32, Rect2D)> {
+        self.pages
+            .iter_mut()
+            .enumerate()
+            .flat_map(|(i, p)| p.dirty.drain(..).map(move |r| (i as u32, r)))
+            .collect()
+    }
+
+    fn allocate(&mut self, w: u32, h: u32) -> Option<(u32, Rect2D)> {
+        let spot = self
+            .pages
+            .iter_mut()
+            .enumerate()
+            .find_map(|(i, p)| Some((i as u32, p.packer.insert(w, h)?)));
+
+        if spot.is_some() {
+            return spot;
+        }
+
+        let mut page = GlyphPage::new(self.atlas_width, self.atlas_height);
+        let rect = page.packer.insert(w, h)?;
+        self.pages.push(page);
+        Some((self.pages.len() as u32 - 1, rect))
+    }
+
+    // Coverage for a character this font has, whether it's cached or not.
+    fn raw_glyph(&self, ch: char) -> Option<RawGlyph> {
+        if !self.has_glyph(ch) {
+            return None;
+        }
+
+        match &self.source {
+            GlyphSource::Outline(f) => {
+                let (metrics, coverage) =
+                    f.rasterize_indexed(f.lookup_glyph_index(ch), self.font_size);
+                Some(RawGlyph {
+                    coverage,
+                    width: metrics.width as u32,
+                    height: metrics.height as u32,
+                    xmin: metrics.xmin,
+                    ymin: metrics.ymin,
+                    advance: metrics.advance_width,
+                })
+            }
+            GlyphSource::Bitmap(_) => {
+                let g = self.glyphs.get(&ch)?;
+                let page = &self.pages[g.page as usize];
+                let b = g.bounds;
+                let coverage = (0..b.h as usize)
+                    .flat_map(|row| {
+                        let from = (b.y as usize + row) * self.atlas_width as usize + b.x as usize;
+                        page.bitmap[from..from + b.w as usize].iter().copied()
+                    })
+                    .collect();
+
+                Some(RawGlyph {
+                    coverage,
+                    width: b.w,
+                    height: b.h,
+                    xmin: (g.bearing_x * self.atlas_width as f32).round() as i32,
+                    ymin: (g.bearing_y * self.atlas_height as f32).round() as i32,
+                    advance: g.advance * self.atlas_width as f32,
+                })
+            }
+        }
+    }
+
+    // A hollow box about the size of a lowercase letter.
+    fn tofu(&self) -> RawGlyph {
+        let size = self.font_size.max(8.0);
+        let (w, h) = ((size * 0.5).round() as u32, (size * 0.7).round() as u32);
+        let border = (size / 16.0).round().max(1.0) as u32;
+        let margin = (size * 0.08).round() as i32;
+
+        let coverage = (0..h)
+            .flat_map(|y| (0..w).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let edge = x < border || y < border || x >= w - border || y >= h - border;
+                if edge {
+                    255
+                } else {
+                    0
+                }
+            })
+            .collect();
+
+        RawGlyph {
+            coverage,
+            width: w,
+            height: h,
+            xmin: margin,
+            ymin: 0,
+            advance: (w as i32 + 2 * margin) as f32,
         }
     }
+
+    fn rasterize(&mut self, ch: char) -> Option<Glyph> {
+        let raw = self
+            .raw_glyph(ch)
+            .or_else(|| self.fallbacks.iter().find_map(|f| f.raw_glyph(ch)))
+            .or_else(|| shows_tofu(ch).then(|| self.tofu()))?;
+
+        let (mut w, mut h) = (raw.width, raw.height);
+        let mut bitmap_data = raw.coverage;
+
+        // Distance fields need room around the outline for the falloff.
+        let mut pad = 0;
+        if let GlyphMode::Sdf { spread } = self.mode {
+            if w > 0 && h > 0 {
+                bitmap_data = coverage_to_sdf(&bitmap_data, w, h, spread);
+                (w, h, pad) = (w + 2 * spread, h + 2 * spread, spread);
+            }
+        }
+
+        // Whitespace has metrics but nothing to put in the atlas.
+        let (page, bounds) = match w == 0 || h == 0 {
+            true => (0, Rect2D::default()),
+            false => {
+                let (page, rect) = match self.allocate(w, h) {
+                    Some(spot) => spot,
+                    None => {
+                        println!(
+                            "Glyph '{}' at size {} doesn't fit in a {}x{} atlas page!",
+                            ch, self.font_size, self.atlas_width, self.atlas_height
+                        );
+                        return None;
+                    }
+                };
+
+                let target = &mut self.pages[page as usize];
+                let stride = self.atlas_width as usize;
+                for row in 0..h as usize {
+                    let dst = (rect.y as usize + row) * stride + rect.x as usize;
+                    target.bitmap[dst..dst + w as usize]
+                        .copy_from_slice(&bitmap_data[row * w as usize..(row + 1) * w as usize]);
+                }
+
+                target.dirty.push(rect);
+                (page, rect)
+            }
+        };
+
+        Some(Glyph {
+            bounds,
+            page,
+            advance: raw.advance / (self.atlas_width as f32),
+            bearing_x: (raw.xmin - pad as i32) as f32 / self.atlas_width as f32,
+            bearing_y: (raw.ymin - pad as i32) as f32 / self.atlas_height as f32,
+        })
+    }
+}
+
+const INF: f64 = 1e20;
+
+// Felzenszwalb & Huttenlocher squared distance transform of `f` into `d`.
+fn edt_1d(f: &[f64], d: &mut [f64], v: &mut [usize], z: &mut [f64]) {
+    let n = f.len();
+    let parabola = |q: usize, r: usize| {
+        ((f[q] + (q * q) as f64) - (f[r] + (r * r) as f64)) / (2 * q - 2 * r) as f64
+    };
+
+    let mut k = 0;
+    v[0] = 0;
+    z[0] = -INF;
+    z[1] = INF;
+    for q in 1..n {
+        let mut s = parabola(q, v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = parabola(q, v[k]);
+        }
+
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = INF;
+    }
+
+    k = 0;
+    for (q, out) in d.iter_mut().enumerate().take(n) {
+        while z[k + 1] < q as f64 {
+            k += 1;
+        }
+
+        let dx = q as f64 - v[k] as f64;
+        *out = dx * dx + f[v[k]];
+    }
+}
+
+fn edt(grid: &mut [f64], w: usize, h: usize) {
+    let n = w.max(h);
+    let mut f = vec![0.0; n];
+    let mut d = vec![0.0; n];
+    let mut v = vec![0usize; n];
+    let mut z = vec![0.0; n + 1];
+
+    for x in 0..w {
+        for y in 0..h {
+            f[y] = grid[y * w + x];
+        }
+
+        edt_1d(&f[..h], &mut d[..h], &mut v, &mut z);
+        for y in 0..h {
+            grid[y * w + x] = d[y];
+        }
+    }
+
+    for y in 0..h {
+        f[..w].copy_from_slice(&grid[y * w..(y + 1) * w]);
+        edt_1d(&f[..w], &mut d[..w], &mut v, &mut z);
+        grid[y * w..(y + 1) * w].copy_from_slice(&d[..w]);
+    }
+}
+
+/// Turns a coverage bitmap into a distance field padded by `spread` on every side. The
+/// outline sits at 128, values above it are inside the glyph.
+pub fn coverage_to_sdf(coverage: &[u8], w: u32, h: u32, spread: u32) -> Vec<u8> {
+    let (w, h, spread) = (w as usize, h as usize, spread as usize);
+    let (sw, sh) = (w + 2 * spread, h + 2 * spread);
+
+    // Partially covered pixels seed a sub-pixel distance to the edge on one side or the other.
+    let mut outer = vec![INF; sw * sh];
+    let mut inner = vec![0.0; sw * sh];
+    for y in 0..h {
+        for x in 0..w {
+            let a = coverage[y * w + x] as f64 / 255.0;
+            let i = (y + spread) * sw + x + spread;
+            if a >= 1.0 {
+                outer[i] = 0.0;
+                inner[i] = INF;
+            } else if a > 0.0 {
+                let d = 0.5 - a;
+                outer[i] = if d > 0.0 { d * d } else { 0.0 };
+                inner[i] = if d < 0.0 { d * d } else { 0.0 };
+            }
+        }
+    }
+
+    edt(&mut outer, sw, sh);
+    edt(&mut inner, sw, sh);
+
+    outer
+        .iter()
+        .zip(inner.iter())
+        .map(|(o, i)| {
+            let d = o.sqrt() - i.sqrt();
+            let v = 0.5 - d / (2.0 * spread.max(1) as f64);
+            (v.clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+        .collect()
+}
+
+#[test]
+fn test_sdf() {
+    // A solid 6x6 block.
+    let sdf = coverage_to_sdf(&[255; 36], 6, 6, 4);
+    let sw = 6 + 8;
+    assert_eq!(sdf.len(), sw * sw);
+
+    let at = |x: usize, y: usize| sdf[y * sw + x];
+    assert!(at(7, 7) > 200);
+    assert_eq!(at(0, 0), 0);
+    // Just inside and just outside the left edge.
+    assert!(at(4, 7) > 128);
+    assert!(at(3, 7) < 128);
+    // Monotonic falloff away from the outline.
+    assert!(at(2, 7) < at(3, 7) && at(1, 7) < at(2, 7));
+}
+
+#[test]
+fn test_fallbacks() {
+    use super::bmfont::BMFontChar;
+
+    // Two 4x4 bitmap fonts, each with a single solid glyph.
+    let font = |id: char| {
+        let desc = BMFontDescriptor {
+            size: 16,
+            line_height: 16,
+            base: 12,
+            scale_w: 4,
+            scale_h: 4,
+            pages: vec!["page.png".to_string()],
+            chars: vec![BMFontChar {
+                id: id as u32,
+                width: 4,
+                height: 4,
+                yoffset: 8,
+                xadvance: 5,
+                ..Default::default()
+            }],
+            kernings: Vec::new(),
+        };
+        let page = ImageLoadInfo {
+            size: [4, 4],
+            format: dashi::Format::RGBA8,
+            bytes: vec![255; 64],
+            mips: Vec::new(),
+        };
+        TTFont::from_bitmap(&desc, &[page], 64, 64)
+    };
+
+    let mut primary = font('a');
+    primary.add_fallback(font('b'));
+    assert!(!primary.has_glyph('b'));
+    assert_eq!(primary.advance('b'), 5.0);
+
+    let b = primary.glyph('b').unwrap().bounds;
+    assert_eq!((b.w, b.h), (4, 4));
+    assert_eq!(primary.pages[0].bitmap[(b.y * 64 + b.x) as usize], 255);
+
+    // Nobody has 'z', so it's drawn as a box with an empty middle.
+    let tofu_advance = primary.advance('z');
+    let z = primary.glyph('z').unwrap();
+    assert_eq!(z.advance * 64.0, tofu_advance);
+    let z = z.bounds;
+    assert!(z.w > 2 && z.h > 2);
+    let (x, y) = (z.x + z.w / 2, z.y + z.h / 2);
+    assert_eq!(primary.pages[0].bitmap[(y * 64 + x) as usize], 0);
+    assert_eq!(primary.pages[0].bitmap[(z.y * 64 + z.x) as usize], 255);
+
+    // Missing whitespace just takes up no room.
+    assert_eq!(primary.advance('\t'), 0.0);
+    assert!(primary.glyph('\t').is_none());
 }
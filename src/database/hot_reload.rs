@@ -0,0 +1,351 @@
+use super::loader::*;
+use super::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+/// Polls file modification times. Cheap enough to run once a frame during development.
+#[derive(Default)]
+pub(crate) struct FileWatcher {
+    files: HashMap<String, Option<SystemTime>>,
+}
+
+impl FileWatcher {
+    pub fn watch(&mut self, fs: &dyn FileSystem, path: &str) {
+        self.files.insert(path.to_string(), fs.modified(path));
+    }
+
+    pub fn clear(&mut self) {
+        self.files.clear();
+    }
+
+    /// Returns every watched path whose mtime moved since the last call.
+    pub fn changed(&mut self, fs: &dyn FileSystem) -> Vec<String> {
+        let mut changed = Vec::new();
+        for (path, time) in self.files.iter_mut() {
+            let now = fs.modified(path);
+            if now != *time {
+                *time = now;
+                changed.push(path.clone());
+            }
+        }
+
+        changed
+    }
+}
+
+// Entries only get rebuilt when their config really changed, not when a neighbour did.
+fn same_cfg<T: Serialize>(old: &T, new: Option<&T>) -> bool {
+    new.is_none_or(|new| serde_json::to_value(old).ok() == serde_json::to_value(new).ok())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReloadedEntry {
+    pub kind: AssetKind,
+    pub name: String,
+}
+
+impl Database {
+    /// Starts watching shoyu.json, the entry configs and every referenced asset file.
+    /// Call `poll_hot_reload` (or `ResourceManager::hot_reload`) once a frame afterwards.
+    /// Embedded files never change, so nothing reloads for them.
+    pub fn enable_hot_reload(&mut self) {
+        self.watcher = Some(Default::default());
+        self.rewatch();
+    }
+
+    pub fn disable_hot_reload(&mut self) {
+        self.watcher = None;
+    }
+
+    fn entry_path(&self, kind: AssetKind, name: &str) -> Option<String> {
+        let path = match kind {
+            AssetKind::Sprite => &self.sprites.get(name)?.cfg.image_path,
+            AssetKind::SpriteSheet => &self.sprite_sheets.get(name)?.cfg.image_path,
+            AssetKind::TTF => &self.ttfs.get(name)?.cfg.path,
+            AssetKind::Sound => &self.sounds.get(name)?.cfg.path,
+        };
+
+        Some(path.clone())
+    }
+
+    fn entry_names(&self) -> Vec<(AssetKind, String)> {
+        let sprites = self.sprites.keys().map(|n| (AssetKind::Sprite, n.clone()));
+        let sheets = self
+            .sprite_sheets
+            .keys()
+            .map(|n| (AssetKind::SpriteSheet, n.clone()));
+        let ttfs = self.ttfs.keys().map(|n| (AssetKind::TTF, n.clone()));
+        let sounds = self.sounds.keys().map(|n| (AssetKind::Sound, n.clone()));
+
+        sprites.chain(sheets).chain(ttfs).chain(sounds).collect()
+    }
+
+    fn is_loaded(&self, kind: AssetKind, name: &str) -> bool {
+        match kind {
+            AssetKind::Sprite => self.is_sprite_loaded(name),
+            AssetKind::SpriteSheet => self.is_sprite_sheet_loaded(name),
+            AssetKind::TTF => self.is_ttf_loaded(name),
+            AssetKind::Sound => self.is_sound_loaded(name),
+        }
+    }
+
+    fn rewatch(&mut self) {
+        let mut paths = self.config_files.clone();
+        for (kind, name) in self.entry_names() {
+            paths.extend(self.entry_path(kind, &name));
+        }
+
+        if let Some(watcher) = self.watcher.as_mut() {
+            watcher.clear();
+            for path in &paths {
+                watcher.watch(self.fs.as_ref(), path);
+            }
+        }
+    }
+
+    // Decodes the entry again. The old data is kept if the file is mid-save or broken.
+    fn reload_entry(&mut self, kind: AssetKind, name: &str) -> bool {
+        let path = match self.entry_path(kind, name) {
+            Some(p) => p,
+            None => return false,
+        };
+
+        let job = match kind {
+            AssetKind::TTF => self.font_job(name),
+            AssetKind::Sound => Ok(LoadJob::Sound { path }),
+            AssetKind::Sprite => Ok(LoadJob::Image {
+                path,
+                texture: self.sprites[name].texture(),
+            }),
+            AssetKind::SpriteSheet => Ok(LoadJob::Image {
+                path,
+                texture: self.sprite_sheets[name].texture(),
+            }),
+        };
+
+        let output = match job.and_then(|j| run_job(self.fs.as_ref(), j)) {
+            Ok(o) => o,
+            Err(e) => {
+                println!("Hot reload of {} failed: {}", name, e);
+                return false;
+            }
+        };
+
+        match (kind, output) {
+            (AssetKind::Sprite, LoadOutput::Image(img)) => {
+                self.sprites.get_mut(name).unwrap().loaded = Some(img)
+            }
+            (AssetKind::SpriteSheet, LoadOutput::Image(img)) => {
+                self.sprite_sheets.get_mut(name).unwrap().loaded = Some(img)
+            }
+            (AssetKind::TTF, LoadOutput::Font(font)) => {
+                self.ttfs.get_mut(name).unwrap().loaded = Some(Arc::new(Mutex::new(*font)))
+            }
+            (AssetKind::Sound, LoadOutput::Sound(sound)) => {
+                self.sounds.get_mut(name).unwrap().loaded = Some(Arc::new(sound))
+            }
+            _ => return false,
+        }
+
+        self.touch(kind, name);
+        true
+    }
+
+    // Swaps in freshly parsed configs while keeping everything that was resident resident.
+    fn reload_configs(
+        &mut self,
+        changed: &[String],
+        reloaded: &mut Vec<ReloadedEntry>,
+    ) -> Result<(), Error> {
+        let parsed = Database::load_mounts(&self.mounts)?;
+        let resident: Vec<(AssetKind, String, Option<String>)> = self
+            .entry_names()
+            .into_iter()
+            .filter(|(kind, name)| self.is_loaded(*kind, name))
+            .map(|(kind, name)| {
+                let path = self.entry_path(kind, &name);
+                (kind, name, path)
+            })
+            .collect();
+
+        let mut old_sprites = std::mem::replace(&mut self.sprites, parsed.sprites);
+        let mut old_sheets = std::mem::replace(&mut self.sprite_sheets, parsed.sprite_sheets);
+        let mut old_ttfs = std::mem::replace(&mut self.ttfs, parsed.ttfs);
+        let mut old_sounds = std::mem::replace(&mut self.sounds, parsed.sounds);
+        self.maps = parsed.maps;
+        let language = self.localization.language().to_string();
+        let characters = self.localization.characters();
+        self.localization = parsed.localization;
+        // Keep the language the player picked if it's still there. Resident fonts are
+        // reloaded below and pick up its characters then.
+        let _ = self.localization.set_language(&language);
+        self.infos = parsed.infos;
+        self.origins = parsed.origins;
+        self.config_files = parsed.config_files;
+        let characters_changed = self.localization.characters() != characters;
+
+        for (kind, name, old_path) in resident {
+            // Live handles may still point at an entry that was removed from the config,
+            // so it stays around until the game restarts.
+            let same = match kind {
+                AssetKind::Sprite => {
+                    let old = old_sprites.remove(&name).unwrap();
+                    let same = same_cfg(&old.cfg, self.sprites.get(&name).map(|e| &e.cfg));
+                    let entry = self.sprites.entry(name.clone()).or_insert(SpriteEntry {
+                        cfg: old.cfg,
+                        loaded: None,
+                    });
+                    entry.loaded = old.loaded;
+                    same
+                }
+                AssetKind::SpriteSheet => {
+                    let old = old_sheets.remove(&name).unwrap();
+                    let new = self.sprite_sheets.get(&name).map(|e| &e.cfg);
+                    let same = same_cfg(&old.cfg, new);
+                    let entry = self
+                        .sprite_sheets
+                        .entry(name.clone())
+                        .or_insert(SpriteSheetEntry {
+                            cfg: old.cfg,
+                            loaded: None,
+                        });
+                    entry.loaded = old.loaded;
+                    same
+                }
+                AssetKind::TTF => {
+                    let old = old_ttfs.remove(&name).unwrap();
+                    let same = same_cfg(&old.cfg, self.ttfs.get(&name).map(|e| &e.cfg));
+                    let entry = self.ttfs.entry(name.clone()).or_insert(TTFEntry {
+                        cfg: old.cfg,
+                        kind: old.kind,
+                        loaded: None,
+                    });
+                    entry.loaded = old.loaded;
+                    same
+                }
+                AssetKind::Sound => {
+                    let old = old_sounds.remove(&name).unwrap();
+                    let same = same_cfg(&old.cfg, self.sounds.get(&name).map(|e| &e.cfg));
+                    let entry = self.sounds.entry(name.clone()).or_insert(SoundEntry {
+                        cfg: old.cfg,
+                        loaded: None,
+                    });
+                    entry.loaded = old.loaded;
+                    same
+                }
+            };
+
+            // Texture options, sheet bounds or font settings may change without the file.
+            // Fonts also pick up the characters of a new language.
+            let new_path = self.entry_path(kind, &name);
+            let stale = !same
+                || new_path != old_path
+                || new_path.as_ref().is_some_and(|p| changed.contains(p))
+                || (kind == AssetKind::TTF && characters_changed);
+            if stale {
+                self.reload_entry(kind, &name);
+                reloaded.push(ReloadedEntry { kind, name });
+            }
+        }
+
+        self.rewatch();
+        Ok(())
+    }
+
+    /// Checks watched files and re-parses/re-decodes anything that changed. Returns the
+    /// resident entries whose data was replaced so GPU copies can be refreshed.
+    pub fn poll_hot_reload(&mut self) -> Result<Vec<ReloadedEntry>, Error> {
+        let changed = match self.watcher.as_mut() {
+            Some(w) => w.changed(self.fs.as_ref()),
+            None => return Ok(Vec::new()),
+        };
+
+        let mut reloaded = Vec::new();
+        if changed.is_empty() {
+            return Ok(reloaded);
+        }
+
+        let config_paths = self.config_files.clone();
+        if changed.iter().any(|p| config_paths.contains(p)) {
+            self.reload_configs(&changed, &mut reloaded)?;
+        }
+
+        for (kind, name) in self.entry_names() {
+            let path = match self.entry_path(kind, &name) {
+                Some(p) => p,
+                None => continue,
+            };
+
+            let already = reloaded.iter().any(|r| r.kind == kind && r.name == name);
+            if !already
+                && changed.contains(&path)
+                && self.is_loaded(kind, &name)
+                && self.reload_entry(kind, &name)
+            {
+                reloaded.push(ReloadedEntry { kind, name });
+            }
+        }
+
+        Ok(reloaded)
+    }
+}
+
+#[test]
+fn test_hot_reload() {
+    // Replacing a memory file moves its mtime, like saving it on disk would.
+    let fs = Arc::new(
+        MemoryFileSystem::new()
+            .with("a.png", test_png(4, 4))
+            .with("pixel_0.png", test_png(64, 64))
+            .with(
+                "shoyu.json",
+                r#"{"sprite_cfg": "sprites.json", "bmfont_cfg": "fonts.json"}"#,
+            )
+            .with("fonts.json", r#"{"fonts": [{"name": "pixel", "path": "pixel.fnt"}]}"#)
+            .with(
+                "pixel.fnt",
+                "common lineHeight=14 base=11 scaleW=64 scaleH=64 pages=1\n\
+                 page id=0 file=\"pixel_0.png\"\n\
+                 char id=65 x=1 y=2 width=5 height=7 xoffset=0 yoffset=4 xadvance=6 page=0\n",
+            )
+            .with(
+                "sprites.json",
+                r#"{"sprites": [{"name": "a", "image_path": "a.png"}]}"#,
+            ),
+    );
+
+    let mut db = Database::with_filesystem(fs.clone()).unwrap();
+    db.fetch_sprite("a").unwrap();
+    // What a font handle holds on to.
+    let font = db.fetch_ttf("pixel").unwrap().loaded.clone().unwrap();
+    db.enable_hot_reload();
+    assert!(db.poll_hot_reload().unwrap().is_empty());
+
+    fs.insert("a.png", test_png(8, 8));
+    let reloaded = db.poll_hot_reload().unwrap();
+    assert_eq!(reloaded.len(), 1);
+    assert_eq!(reloaded[0].kind, AssetKind::Sprite);
+    assert_eq!(db.fetch_sprite("a").unwrap().loaded.as_ref().unwrap().size, [8, 8]);
+
+    fs.insert(
+        "sprites.json",
+        r#"{"sprites": [{"name": "a", "image_path": "a.png"}, {"name": "b", "image_path": "a.png"}]}"#,
+    );
+    // Adding an entry leaves the resident one alone.
+    assert!(db.poll_hot_reload().unwrap().is_empty());
+    assert!(db.is_sprite_loaded("a"));
+    assert!(db.fetch_sprite("b").is_ok());
+    // The untouched font is still the one the handle draws with.
+    let entry = db.fetch_ttf("pixel").unwrap().loaded.as_ref().unwrap();
+    assert!(Arc::ptr_eq(entry, &font));
+    assert!(font.lock().unwrap().glyph('A').is_some());
+
+    fs.insert(
+        "sprites.json",
+        r#"{"sprites": [{"name": "a", "image_path": "a.png", "texture": {"mipmaps": true}}]}"#,
+    );
+    let reloaded = db.poll_hot_reload().unwrap();
+    assert_eq!(reloaded.len(), 1);
+    assert_eq!(reloaded[0].name, "a");
+}
@@ -1,7 +1,12 @@
+use super::error::*;
 use super::json::*;
 use super::load_funcs::*;
-use super::TTFont;
+use super::loader::{FallbackFont, LoadJob};
+use super::vfs::FileSystem;
+use super::{GlyphMode, TTFont, DEFAULT_SDF_SPREAD};
+use dashi::Rect2D;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 pub struct SpriteEntry {
     pub cfg: SpriteJSONEntry,
@@ -9,15 +14,28 @@ pub struct SpriteEntry {
 }
 
 impl SpriteEntry {
-    pub fn load(&mut self, base_path: &str) {
-        self.loaded = Some(
-            load_image_rgba8(&format!("{}/{}", base_path, self.cfg.image_path.as_str())).unwrap(),
-        );
+    pub fn load(&mut self, fs: &dyn FileSystem) {
+        self.try_load(fs).unwrap();
+    }
+
+    pub fn try_load(&mut self, fs: &dyn FileSystem) -> Result<(), Error> {
+        let img = load_texture(fs, &self.cfg.image_path, &self.texture());
+        self.loaded = Some(img.with_entry(&self.cfg.name)?);
+        Ok(())
+    }
+
+    pub fn texture(&self) -> TextureJSON {
+        self.cfg.texture.unwrap_or_default()
     }
 
     pub fn unload(&mut self) {
         self.loaded = None;
     }
+
+    /// Bytes of decoded image data currently held.
+    pub fn memory_size(&self) -> usize {
+        self.loaded.as_ref().map_or(0, |img| img.bytes.len())
+    }
 }
 
 pub struct SpriteSheetEntry {
@@ -25,33 +43,195 @@ pub struct SpriteSheetEntry {
     pub loaded: Option<ImageLoadInfo<u8>>,
 }
 
+impl SpriteSheetJSONAutoGen {
+    /// Generates the frame table for an image of the given size.
+    pub fn generate(&self, image_size: [u32; 2]) -> Vec<SpriteSheetJSONSprite> {
+        let padding = self.padding.unwrap_or(0);
+        let step_x = self.bounds.w + padding;
+        let step_y = self.bounds.h + padding;
+        if self.bounds.w == 0 || self.bounds.h == 0 {
+            return Vec::new();
+        }
+
+        // How many cells fit between the margin and the edge of the image.
+        let fit = |avail: u32, origin: u32, size: u32, step: u32| {
+            if avail < origin + size {
+                0
+            } else {
+                (avail - origin - size) / step + 1
+            }
+        };
+
+        let cols = match self.stride {
+            0 => fit(image_size[0], self.bounds.x, self.bounds.w, step_x),
+            s => s,
+        };
+        let rows = self
+            .rows
+            .unwrap_or_else(|| fit(image_size[1], self.bounds.y, self.bounds.h, step_y));
+        let count = self.count.unwrap_or(cols * rows).min(cols * rows);
+        let first_id = self.first_id.unwrap_or(0);
+
+        (0..count)
+            .map(|i| SpriteSheetJSONSprite {
+                name: format!("{}_{}", self.name, i),
+                id: first_id + i,
+                bounds: Rect2D {
+                    x: self.bounds.x + (i % cols) * step_x,
+                    y: self.bounds.y + (i / cols) * step_y,
+                    w: self.bounds.w,
+                    h: self.bounds.h,
+                },
+                trim: None,
+                rotated: None,
+            })
+            .collect()
+    }
+}
+
+impl SpriteSheetJSONAnimation {
+    pub fn duration_ms(&self) -> f32 {
+        self.frames.iter().map(|f| f.duration_ms).sum()
+    }
+
+    /// Sprite id to show `elapsed_ms` into the clip, wrapping around when looping.
+    pub fn frame_at(&self, elapsed_ms: f32, looping: bool) -> Option<u32> {
+        let total = self.duration_ms();
+        let mut t = match looping && total > 0.0 {
+            true => elapsed_ms.rem_euclid(total),
+            false => elapsed_ms,
+        };
+
+        for frame in &self.frames {
+            if t < frame.duration_ms {
+                return Some(frame.id);
+            }
+            t -= frame.duration_ms;
+        }
+
+        self.frames.last().map(|f| f.id)
+    }
+}
+
 impl SpriteSheetEntry {
-    pub fn load(&mut self, base_path: &str) {
-        self.loaded = Some(
-            load_image_rgba8(&format!("{}/{}", base_path, self.cfg.image_path.as_str())).unwrap(),
-        );
+    /// Every frame of the sheet: the generated grid first, then the hand-written entries,
+    /// which win on id clashes. Needs the image to be loaded when auto-generating.
+    pub fn sprites(&self) -> Vec<SpriteSheetJSONSprite> {
+        let mut sprites = Vec::new();
+        if let (Some(gen), Some(img)) = (self.cfg.auto_gen.as_ref(), self.loaded.as_ref()) {
+            sprites.extend(gen.generate(img.size));
+        }
+
+        if let Some(spr) = self.cfg.sprites.as_ref() {
+            sprites.extend(spr.iter().cloned());
+        }
+
+        sprites
+    }
+
+    pub fn load(&mut self, fs: &dyn FileSystem) {
+        self.try_load(fs).unwrap();
+    }
+
+    pub fn try_load(&mut self, fs: &dyn FileSystem) -> Result<(), Error> {
+        let img = load_texture(fs, &self.cfg.image_path, &self.texture());
+        self.loaded = Some(img.with_entry(&self.cfg.name)?);
+        Ok(())
+    }
+
+    pub fn texture(&self) -> TextureJSON {
+        self.cfg.texture.unwrap_or_default()
     }
 
     pub fn unload(&mut self) {
         self.loaded = None;
     }
+
+    /// Bytes of decoded image data currently held.
+    pub fn memory_size(&self) -> usize {
+        self.loaded.as_ref().map_or(0, |img| img.bytes.len())
+    }
+}
+
+/// Where a font entry's glyphs come from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FontKind {
+    TrueType,
+    BMFont,
 }
 
 pub struct TTFEntry {
     pub cfg: TTFJSONEntry,
-    pub loaded: Option<TTFont>,
+    pub kind: FontKind,
+    // Shared so font handles keep the glyphs they draw with when the entry is unloaded or
+    // its config reloaded.
+    pub loaded: Option<Arc<Mutex<TTFont>>>,
 }
 
 impl TTFEntry {
-    pub fn load(&mut self, base_path: &str, typeset: &[char]) {
-        self.loaded = Some(
-            TTFont::new(&format!("{}/{}", base_path, self.cfg.path.as_str()), 1280, 1024, self.cfg.size as f32, typeset),
-        );
+    /// Entry for a BMFont descriptor. The size comes from the descriptor once it's loaded.
+    pub fn bitmap(info: BMFontJSONEntry) -> Self {
+        Self {
+            cfg: TTFJSONEntry {
+                name: info.name,
+                path: info.path,
+                size: 0.0,
+                glyphs: None,
+                sdf: None,
+                sdf_spread: None,
+                fallbacks: info.fallbacks,
+            },
+            kind: FontKind::BMFont,
+            loaded: None,
+        }
+    }
+
+    /// `localized` are the active language's characters, rasterized once the fallbacks are in.
+    pub(crate) fn load_job(
+        &self,
+        path: &str,
+        fallbacks: Vec<FallbackFont>,
+        localized: Vec<char>,
+    ) -> LoadJob {
+        let path = path.to_string();
+        match self.kind {
+            FontKind::TrueType => LoadJob::Font {
+                path,
+                size: self.cfg.size as f32,
+                mode: self.glyph_mode(),
+                typeset: self.typeset(),
+                fallbacks,
+                localized,
+            },
+            FontKind::BMFont => LoadJob::BMFont { path, fallbacks },
+        }
+    }
+
+    /// Characters to rasterize. Falls back to the ASCII range when the entry doesn't list any.
+    pub fn typeset(&self) -> Vec<char> {
+        match self.cfg.glyphs.as_ref() {
+            Some(g) => g.chars().collect(),
+            None => (0 as u8 as char..127 as u8 as char).collect(),
+        }
+    }
+
+    pub fn glyph_mode(&self) -> GlyphMode {
+        match self.cfg.sdf {
+            Some(true) => GlyphMode::Sdf {
+                spread: self.cfg.sdf_spread.unwrap_or(DEFAULT_SDF_SPREAD),
+            },
+            _ => GlyphMode::Coverage,
+        }
     }
 
     pub fn unload(&mut self) {
         self.loaded = None;
     }
+
+    /// Bytes held by the rasterized glyph pages.
+    pub fn memory_size(&self) -> usize {
+        self.loaded.as_ref().map_or(0, |font| font.lock().unwrap().memory_size())
+    }
 }
 pub fn parse_sprite_sheets(info: SpriteSheetJSON) -> HashMap<String, SpriteSheetEntry> {
     let tup_vec: Vec<(String, SpriteSheetEntry)> = info
@@ -98,6 +278,7 @@ pub fn parse_ttfs(info: TTFJSON) -> HashMap<String, TTFEntry> {
                 a.name.clone(),
                 TTFEntry {
                     cfg: a.clone(),
+                    kind: FontKind::TrueType,
                     loaded: None,
                 },
             )
@@ -107,3 +288,29 @@ pub fn parse_ttfs(info: TTFJSON) -> HashMap<String, TTFEntry> {
     return tup_vec.into_iter().collect();
 }
 
+
+#[test]
+fn test_auto_gen() {
+    let gen = SpriteSheetJSONAutoGen {
+        name: "walk".to_string(),
+        bounds: Rect2D {
+            x: 2,
+            y: 2,
+            w: 16,
+            h: 16,
+        },
+        stride: 0,
+        rows: None,
+        count: Some(5),
+        padding: Some(1),
+        first_id: Some(10),
+    };
+
+    // (64 - 2 - 16) / 17 + 1 = 3 columns
+    let frames = gen.generate([64, 64]);
+    assert_eq!(frames.len(), 5);
+    assert_eq!(frames[0].name, "walk_0");
+    assert_eq!(frames[0].id, 10);
+    assert_eq!((frames[2].bounds.x, frames[2].bounds.y), (36, 2));
+    assert_eq!((frames[3].bounds.x, frames[3].bounds.y), (2, 19));
+}
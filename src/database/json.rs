@@ -1,9 +1,58 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How texels are encoded. sRGB suits hand-painted art, linear suits data like normal
+/// maps or lookup tables.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureColorSpace {
+    #[default]
+    Srgb,
+    Linear,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureFilter {
+    /// Hard texel edges, for pixel art.
+    #[default]
+    Nearest,
+    /// Smooth scaling, for high resolution art.
+    Linear,
+}
+
+/// What sampling past the edge of the texture reads.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureWrap {
+    #[default]
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    /// Transparent black.
+    ClampToBorder,
+}
+
+/// How an entry's image becomes a texture. Every field is optional, the defaults match
+/// pixel art: sRGB, straight alpha, no mipmaps and nearest filtering.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct TextureJSON {
+    pub color_space: TextureColorSpace,
+    /// Multiply the colors by alpha when loading, so filtering and mipmaps don't bleed the
+    /// color of transparent texels into the edges.
+    pub premultiply_alpha: bool,
+    pub mipmaps: bool,
+    pub min_filter: TextureFilter,
+    pub mag_filter: TextureFilter,
+    pub wrap: TextureWrap,
+}
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct SpriteJSONEntry {
     pub name: String,
     pub image_path: String,
+    pub texture: Option<TextureJSON>,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -11,18 +60,48 @@ pub struct SpriteJSON {
     pub sprites: Vec<SpriteJSONEntry>,
 }
 
+/// Where a trimmed frame sits inside its original, untrimmed image.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct SpriteSheetJSONTrim {
+    pub offset: [u32; 2],
+    pub source_size: [u32; 2],
+}
+
 #[derive(Deserialize, Serialize, Clone)]
 pub struct SpriteSheetJSONSprite {
     pub name: String,
     pub id: u32,
     pub bounds: dashi::Rect2D,
+    pub trim: Option<SpriteSheetJSONTrim>,
+    // Stored 90 degrees clockwise in the image. `bounds` covers the rotated region.
+    pub rotated: Option<bool>,
 }
 
+/// Slices a uniformly gridded sheet into frames.
+/// `bounds` is the first cell: its x/y is the margin into the image and its w/h the cell size.
+/// `stride` is the number of columns, or 0 to fit as many as the image width allows.
+/// Frames are named `{name}_{index}` and numbered from `first_id`, row by row.
 #[derive(Deserialize, Serialize, Clone)]
 pub struct SpriteSheetJSONAutoGen {
     pub name: String,
     pub bounds: dashi::Rect2D,
     pub stride: u32,
+    pub rows: Option<u32>,
+    pub count: Option<u32>,
+    pub padding: Option<u32>,
+    pub first_id: Option<u32>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct SpriteSheetJSONAnimationFrame {
+    pub id: u32,
+    pub duration_ms: f32,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct SpriteSheetJSONAnimation {
+    pub name: String,
+    pub frames: Vec<SpriteSheetJSONAnimationFrame>,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -31,6 +110,8 @@ pub struct SpriteSheetJSONEntry {
     pub image_path: String,
     pub sprites: Option<Vec<SpriteSheetJSONSprite>>,
     pub auto_gen: Option<SpriteSheetJSONAutoGen>,
+    pub animations: Option<Vec<SpriteSheetJSONAnimation>>,
+    pub texture: Option<TextureJSON>,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -44,6 +125,12 @@ pub struct TTFJSONEntry {
     pub path: String,
     pub size: f64,
     pub glyphs: Option<String>,
+    /// Rasterize as a signed distance field so the text stays sharp when scaled.
+    pub sdf: Option<bool>,
+    /// How far the distance field reaches past the outline, in pixels.
+    pub sdf_spread: Option<u32>,
+    /// Names of other font entries to take missing characters from, tried in order.
+    pub fallbacks: Option<Vec<String>>,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -51,10 +138,106 @@ pub struct TTFJSON {
     pub fonts: Vec<TTFJSONEntry>,
 }
 
+#[derive(Deserialize, Serialize, Clone)]
+pub struct BMFontJSONEntry {
+    pub name: String,
+    /// AngelCode BMFont descriptor. Text, XML and binary files all work.
+    pub path: String,
+    pub fallbacks: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct BMFontJSON {
+    pub fonts: Vec<BMFontJSONEntry>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AsepriteJSONEntry {
+    pub name: String,
+    pub path: String,
+    pub texture: Option<TextureJSON>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AsepriteJSON {
+    pub sheets: Vec<AsepriteJSONEntry>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AtlasJSONEntry {
+    pub name: String,
+    pub path: String,
+    pub texture: Option<TextureJSON>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AtlasJSON {
+    pub atlases: Vec<AtlasJSONEntry>,
+}
+
+/// A sound effect or music track. WAV and Ogg Vorbis are supported.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AudioJSONEntry {
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct AudioJSON {
+    pub sounds: Vec<AudioJSONEntry>,
+}
+
+/// A Tiled map, TMX or JSON. Its tilesets are added as sprite sheets.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct TiledJSONEntry {
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct TiledJSON {
+    pub maps: Vec<TiledJSONEntry>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct LocalizationJSONLanguage {
+    /// Language code, like `en` or `pt-BR`.
+    pub name: String,
+    /// String table, a JSON object of keys to strings or plural forms.
+    pub path: String,
+    /// One of `none`, `one`, `zero_one`, `slavic`, `polish`, `czech` or `arabic`. Picked
+    /// from the language code when not set.
+    pub plural_rule: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct LocalizationJSON {
+    pub default_language: String,
+    /// Where strings missing from the active language are taken from.
+    pub fallback_language: Option<String>,
+    pub languages: Vec<LocalizationJSONLanguage>,
+}
+
+/// A string table value. Plurals map CLDR categories (`one`, `few`, `other`, ...) to text.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub enum StringTableJSONValue {
+    Text(String),
+    Plural(HashMap<String, String>),
+}
+
 #[derive(Deserialize, Serialize, Clone)]
 pub struct DatabaseJSON {
     pub sprite_cfg: Option<String>,
     pub sprite_sheet_cfg: Option<String>,
     pub ttf_cfg: Option<String>,
     pub particle_cfg: Option<String>,
+    pub aseprite_cfg: Option<String>,
+    pub atlas_cfg: Option<String>,
+    pub bmfont_cfg: Option<String>,
+    pub audio_cfg: Option<String>,
+    pub tiled_cfg: Option<String>,
+    pub localization_cfg: Option<String>,
+    /// Most decoded asset data to keep in RAM, in megabytes. Unlimited when not set.
+    pub memory_budget_mb: Option<usize>,
 }
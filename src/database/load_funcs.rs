@@ -1,13 +1,31 @@
+use super::compressed::{is_compressed_texture, CompressedImage};
 use super::error::*;
+use super::json::{TextureColorSpace, TextureJSON};
+use super::vfs::FileSystem;
 pub struct ImageLoadInfo<T> {
    pub size: [u32; 2],
    pub format: dashi::Format,
    pub bytes: Vec<T>,
+   /// The mip levels after the one in `bytes`, each half the size of the one before.
+   pub mips: Vec<Vec<T>>,
 }
 
-pub fn load_image_rgba8(path: &str) -> Result<ImageLoadInfo<u8>, Error>{
+impl<T> ImageLoadInfo<T> {
+    pub fn mip_levels(&self) -> u32 {
+        self.mips.len() as u32 + 1
+    }
+}
+
+/// Decodes any image the `image` crate reads, plus BC1, BC3 and BC7 DDS and KTX2 files.
+/// Those are decompressed to RGBA8 as well, see `load_compressed`.
+pub fn load_image_rgba8(fs: &dyn FileSystem, path: &str) -> Result<ImageLoadInfo<u8>, Error>{
     println!("Loading {}", path);
-    let img = image::open(&path)?;
+    let data = fs.read(path)?;
+    if is_compressed_texture(&data) {
+        return CompressedImage::parse(&data).and_then(|c| c.decode()).with_path(path);
+    }
+
+    let img = image::load_from_memory(&data).with_path(path)?;
     
     // Convert the image to RGBA8 format
     let rgba_image = img.to_rgba8();
@@ -22,5 +40,197 @@ pub fn load_image_rgba8(path: &str) -> Result<ImageLoadInfo<u8>, Error>{
         size: [width, height],
         format: dashi::Format::RGBA8,
         bytes,
+        mips: Vec::new(),
     })
 }
+
+impl TextureJSON {
+    pub fn format(&self) -> dashi::Format {
+        match self.color_space {
+            TextureColorSpace::Srgb => dashi::Format::RGBA8,
+            TextureColorSpace::Linear => dashi::Format::RGBA8Unorm,
+        }
+    }
+
+    /// A full mip chain down to 1x1 when mipmaps are on.
+    pub fn mip_levels(&self, size: [u32; 2]) -> u32 {
+        match self.mipmaps {
+            true => u32::BITS - size[0].max(size[1]).max(1).leading_zeros(),
+            false => 1,
+        }
+    }
+}
+
+/// Loads an image the way a sprite or sprite sheet entry's texture options ask for.
+pub fn load_texture(
+    fs: &dyn FileSystem,
+    path: &str,
+    texture: &TextureJSON,
+) -> Result<ImageLoadInfo<u8>, Error> {
+    let mut img = load_image_rgba8(fs, path)?;
+    if texture.premultiply_alpha {
+        for level in std::iter::once(&mut img.bytes).chain(&mut img.mips) {
+            premultiply_alpha(level, texture.color_space);
+        }
+    }
+
+    img.format = texture.format();
+    // DDS and KTX2 files bring their own mip chain.
+    if img.mips.is_empty() {
+        img.mips = generate_mips(&img.bytes, img.size, texture);
+    }
+    Ok(img)
+}
+
+/// The levels after the first of the chain `texture` asks for, each box filtered down
+/// from the one before.
+pub fn generate_mips(bytes: &[u8], size: [u32; 2], texture: &TextureJSON) -> Vec<Vec<u8>> {
+    let mut mips: Vec<Vec<u8>> = Vec::new();
+    for level in 1..texture.mip_levels(size) {
+        let last = mips.last().map_or(bytes, |m| m.as_slice());
+        let last_size = size.map(|s| (s >> (level - 1)).max(1));
+        mips.push(downsample(last, last_size, texture.color_space));
+    }
+    mips
+}
+
+/// Halves RGBA8 pixels, each new one the average of the 2x2 it covers. An odd last row or
+/// column is left out, like the GPU does. sRGB colors are averaged in linear space.
+fn downsample(bytes: &[u8], size: [u32; 2], color_space: TextureColorSpace) -> Vec<u8> {
+    let to_byte = |c: f32| (c * 255.0).round().clamp(0.0, 255.0) as u8;
+    let to_linear: [f32; 256] = std::array::from_fn(|i| match color_space {
+        TextureColorSpace::Srgb => srgb_to_linear(i as f32 / 255.0),
+        TextureColorSpace::Linear => i as f32 / 255.0,
+    });
+
+    let [width, height] = size.map(|s| s as usize);
+    let [half_width, half_height] = size.map(|s| (s / 2).max(1) as usize);
+    let mut out = vec![0u8; half_width * half_height * 4];
+    for y in 0..half_height {
+        for x in 0..half_width {
+            let mut sum = [0.0f32; 4];
+            for sy in [y * 2, y * 2 + 1].map(|sy| sy.min(height - 1)) {
+                for sx in [x * 2, x * 2 + 1].map(|sx| sx.min(width - 1)) {
+                    let pixel = &bytes[(sy * width + sx) * 4..][..4];
+                    for c in 0..3 {
+                        sum[c] += to_linear[pixel[c] as usize];
+                    }
+                    sum[3] += pixel[3] as f32 / 255.0;
+                }
+            }
+
+            let pixel = &mut out[(y * half_width + x) * 4..][..4];
+            for c in 0..3 {
+                pixel[c] = to_byte(match color_space {
+                    TextureColorSpace::Srgb => linear_to_srgb(sum[c] / 4.0),
+                    TextureColorSpace::Linear => sum[c] / 4.0,
+                });
+            }
+            pixel[3] = to_byte(sum[3] / 4.0);
+        }
+    }
+    out
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    match c <= 0.04045 {
+        true => c / 12.92,
+        false => ((c + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    match c <= 0.0031308 {
+        true => c * 12.92,
+        false => 1.055 * c.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+/// Multiplies the colors of RGBA8 pixels by their alpha. sRGB colors are multiplied in
+/// linear space, the way the GPU filters them.
+pub fn premultiply_alpha(bytes: &mut [u8], color_space: TextureColorSpace) {
+    let to_byte = |c: f32| (c * 255.0).round().clamp(0.0, 255.0) as u8;
+    for pixel in bytes.chunks_exact_mut(4) {
+        if pixel[3] == 255 {
+            continue;
+        }
+
+        let alpha = pixel[3] as f32 / 255.0;
+
+        for c in &mut pixel[..3] {
+            let value = *c as f32 / 255.0;
+            *c = match color_space {
+                TextureColorSpace::Srgb => to_byte(linear_to_srgb(srgb_to_linear(value) * alpha)),
+                TextureColorSpace::Linear => to_byte(value * alpha),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn test_png(width: u32, height: u32) -> Vec<u8> {
+    let mut png = Vec::new();
+    image::RgbaImage::new(width, height)
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    png
+}
+
+#[test]
+fn test_texture_options() {
+    let mut png = Vec::new();
+    image::RgbaImage::from_raw(5, 3, [200, 100, 50, 128].repeat(15))
+        .unwrap()
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+
+    let fs = super::vfs::MemoryFileSystem::new()
+        .with("a.png", png)
+        .with("shoyu.json", r#"{"sprite_cfg": "sprites.json"}"#)
+        .with(
+            "sprites.json",
+            r#"{"sprites": [
+                {"name": "pixel", "image_path": "a.png"},
+                {"name": "hd", "image_path": "a.png", "texture": {
+                    "color_space": "linear", "premultiply_alpha": true, "mipmaps": true,
+                    "min_filter": "linear", "mag_filter": "linear", "wrap": "clamp_to_edge"
+                }}
+            ]}"#,
+        );
+    let mut db = super::Database::with_filesystem(std::sync::Arc::new(fs)).unwrap();
+
+    let pixel = db.fetch_sprite("pixel").unwrap();
+    assert_eq!(pixel.texture(), TextureJSON::default());
+    let img = pixel.loaded.as_ref().unwrap();
+    assert_eq!(img.format, dashi::Format::RGBA8);
+    assert_eq!(img.mip_levels(), 1);
+    assert_eq!(&img.bytes[..4], &[200, 100, 50, 128]);
+
+    let hd = db.fetch_sprite("hd").unwrap();
+    assert_eq!(hd.texture().wrap, super::json::TextureWrap::ClampToEdge);
+    let img = hd.loaded.as_ref().unwrap();
+    assert_eq!(img.format, dashi::Format::RGBA8Unorm);
+    // 5x3 goes down to 1x1 in three levels, each one filled in.
+    assert_eq!(img.mip_levels(), 3);
+    assert_eq!(&img.bytes[..4], &[100, 50, 25, 128]);
+    assert_eq!(img.mips[0], [100, 50, 25, 128].repeat(2));
+    assert_eq!(img.mips[1], [100, 50, 25, 128]);
+
+    // Black and white average to mid grey, which in sRGB is brighter than halfway.
+    let stripes = [0, 0, 0, 255, 255, 255, 255, 255].repeat(2);
+    let mut texture = TextureJSON {
+        mipmaps: true,
+        color_space: TextureColorSpace::Linear,
+        ..Default::default()
+    };
+    assert_eq!(generate_mips(&stripes, [2, 2], &texture), [[128, 128, 128, 255]]);
+    texture.color_space = TextureColorSpace::Srgb;
+    assert_eq!(generate_mips(&stripes, [2, 2], &texture), [[188, 188, 188, 255]]);
+
+    // sRGB colors are scaled in linear space, so they come out brighter than a plain
+    // multiply.
+    let mut bytes = vec![200, 100, 50, 128, 9, 9, 9, 255, 7, 7, 7, 0];
+    premultiply_alpha(&mut bytes, TextureColorSpace::Srgb);
+    assert_eq!(&bytes[..4], &[147, 72, 34, 128]);
+    assert_eq!(&bytes[4..], &[9, 9, 9, 255, 0, 0, 0, 0]);
+}
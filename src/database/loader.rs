@@ -0,0 +1,311 @@
+use super::error::*;
+use super::load_funcs::*;
+use super::vfs::FileSystem;
+use super::json::TextureJSON;
+use super::{load_bmfont, load_sound, FontKind, GlyphMode, SoundData, TTFont, GLYPH_PAGE_SIZE};
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+const MAX_LOAD_WORKERS: usize = 4;
+
+/// Handle to a background load request. Query it through the `Database`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LoadTicket(u64);
+
+impl LoadTicket {
+    /// Handed out for entries that are already resident. Real tickets start at 1.
+    pub const READY: LoadTicket = LoadTicket(0);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AssetKind {
+    Sprite,
+    SpriteSheet,
+    TTF,
+    Sound,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadStatus {
+    Pending,
+    Ready,
+    Failed(String),
+}
+
+/// Progress of the current batch of background loads. A new batch starts
+/// whenever a request comes in while nothing else is in flight.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LoadProgress {
+    pub completed: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
+impl LoadProgress {
+    pub fn is_done(&self) -> bool {
+        self.completed + self.failed >= self.total
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+
+        (self.completed + self.failed) as f32 / self.total as f32
+    }
+}
+
+pub(crate) enum LoadJob {
+    Image {
+        path: String,
+        texture: TextureJSON,
+    },
+    Font {
+        path: String,
+        size: f32,
+        mode: GlyphMode,
+        typeset: Vec<char>,
+        fallbacks: Vec<FallbackFont>,
+        localized: Vec<char>,
+    },
+    BMFont {
+        path: String,
+        fallbacks: Vec<FallbackFont>,
+    },
+    Sound {
+        path: String,
+    },
+}
+
+/// A font in another entry's fallback chain.
+pub(crate) struct FallbackFont {
+    pub path: String,
+    pub kind: FontKind,
+}
+
+pub(crate) enum LoadOutput {
+    Image(ImageLoadInfo<u8>),
+    Font(Box<TTFont>),
+    Sound(SoundData),
+}
+
+type LoadResult = (LoadTicket, Result<LoadOutput, Error>);
+
+pub(crate) fn run_job(fs: &dyn FileSystem, job: LoadJob) -> Result<LoadOutput, Error> {
+    match job {
+        LoadJob::Image { path, texture } => {
+            Ok(LoadOutput::Image(load_texture(fs, &path, &texture)?))
+        }
+        LoadJob::Font {
+            path,
+            size,
+            mode,
+            typeset,
+            fallbacks,
+            localized,
+        } => {
+            let mut font = load_truetype(fs, &path, size, mode, &typeset)?;
+            add_fallbacks(fs, &mut font, &fallbacks)?;
+            font.preload(&localized);
+            Ok(LoadOutput::Font(Box::new(font)))
+        }
+        LoadJob::BMFont { path, fallbacks } => {
+            let mut font = load_bmfont(fs, &path)?;
+            add_fallbacks(fs, &mut font, &fallbacks)?;
+            Ok(LoadOutput::Font(Box::new(font)))
+        }
+        LoadJob::Sound { path } => Ok(LoadOutput::Sound(load_sound(fs, &path)?)),
+    }
+}
+
+fn load_truetype(
+    fs: &dyn FileSystem,
+    path: &str,
+    size: f32,
+    mode: GlyphMode,
+    typeset: &[char],
+) -> Result<TTFont, Error> {
+    let [w, h] = GLYPH_PAGE_SIZE;
+    TTFont::try_from_bytes(fs.read(path)?, w, h, size, mode, typeset).with_path(path)
+}
+
+// Fallbacks only hand over coverage, so they're loaded plain at the primary font's size.
+fn add_fallbacks(
+    fs: &dyn FileSystem,
+    font: &mut TTFont,
+    fallbacks: &[FallbackFont],
+) -> Result<(), Error> {
+    for fallback in fallbacks {
+        let loaded = match fallback.kind {
+            FontKind::TrueType => {
+                load_truetype(fs, &fallback.path, font.font_size, GlyphMode::Coverage, &[])?
+            }
+            FontKind::BMFont => load_bmfont(fs, &fallback.path)?,
+        };
+
+        font.add_fallback(loaded);
+    }
+
+    Ok(())
+}
+
+pub(crate) struct AssetLoader {
+    jobs: Option<Sender<(LoadTicket, LoadJob)>>,
+    results: Receiver<LoadResult>,
+    workers: Vec<JoinHandle<()>>,
+    next_ticket: u64,
+    in_flight: HashMap<LoadTicket, (AssetKind, String)>,
+    by_name: HashMap<(AssetKind, String), LoadTicket>,
+    // How the last load of each entry ended, until it's requested again. Kept per entry
+    // so a long session doesn't pile up one status per ticket.
+    finished: HashMap<(AssetKind, String), (LoadTicket, LoadStatus)>,
+    progress: LoadProgress,
+}
+
+impl AssetLoader {
+    pub fn new(fs: Arc<dyn FileSystem>) -> Self {
+        let (job_tx, job_rx) = channel::<(LoadTicket, LoadJob)>();
+        let (res_tx, res_rx) = channel::<LoadResult>();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+
+        let count = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .clamp(1, MAX_LOAD_WORKERS);
+
+        let workers = (0..count)
+            .map(|_| {
+                let job_rx = job_rx.clone();
+                let res_tx = res_tx.clone();
+                let fs = fs.clone();
+                std::thread::spawn(move || loop {
+                    let next = job_rx.lock().unwrap().recv();
+                    match next {
+                        Ok((ticket, job)) => {
+                            if res_tx.send((ticket, run_job(fs.as_ref(), job))).is_err() {
+                                return;
+                            }
+                        }
+                        Err(_) => return,
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            jobs: Some(job_tx),
+            results: res_rx,
+            workers,
+            next_ticket: 0,
+            in_flight: HashMap::new(),
+            by_name: HashMap::new(),
+            finished: HashMap::new(),
+            progress: Default::default(),
+        }
+    }
+
+    fn make_ticket(&mut self) -> LoadTicket {
+        self.next_ticket += 1;
+        LoadTicket(self.next_ticket)
+    }
+
+    pub fn pending(&self, kind: AssetKind, name: &str) -> Option<LoadTicket> {
+        self.by_name.get(&(kind, name.to_string())).copied()
+    }
+
+    pub fn submit(&mut self, kind: AssetKind, name: &str, job: LoadJob) -> LoadTicket {
+        if let Some(ticket) = self.pending(kind, name) {
+            return ticket;
+        }
+
+        if self.in_flight.is_empty() {
+            self.progress = Default::default();
+        }
+
+        let ticket = self.make_ticket();
+        self.finished.remove(&(kind, name.to_string()));
+        self.in_flight.insert(ticket, (kind, name.to_string()));
+        self.by_name.insert((kind, name.to_string()), ticket);
+        self.progress.total += 1;
+
+        self.jobs
+            .as_ref()
+            .unwrap()
+            .send((ticket, job))
+            .expect("Asset loader workers have shut down!");
+
+        ticket
+    }
+
+    pub fn try_recv(&mut self) -> Option<LoadResult> {
+        self.results.try_recv().ok()
+    }
+
+    pub fn recv(&mut self) -> Option<LoadResult> {
+        if self.in_flight.is_empty() {
+            return None;
+        }
+
+        self.results.recv().ok()
+    }
+
+    /// Marks the ticket as done and returns which entry it was loading.
+    pub fn finish(
+        &mut self,
+        ticket: LoadTicket,
+        status: LoadStatus,
+    ) -> Option<(AssetKind, String)> {
+        let (kind, name) = self.in_flight.remove(&ticket)?;
+        self.by_name.remove(&(kind, name.clone()));
+
+        match &status {
+            LoadStatus::Failed(_) => self.progress.failed += 1,
+            _ => self.progress.completed += 1,
+        }
+
+        self.finished.insert((kind, name.clone()), (ticket, status));
+        Some((kind, name))
+    }
+
+    pub fn status(&self, ticket: LoadTicket) -> Option<LoadStatus> {
+        if ticket == LoadTicket::READY {
+            return Some(LoadStatus::Ready);
+        }
+        if self.in_flight.contains_key(&ticket) {
+            return Some(LoadStatus::Pending);
+        }
+
+        self.finished
+            .values()
+            .find(|(t, _)| *t == ticket)
+            .map(|(_, status)| status.clone())
+    }
+
+    pub fn failure(&self, kind: AssetKind, name: &str) -> Option<&str> {
+        match self.finished.get(&(kind, name.to_string())) {
+            Some((_, LoadStatus::Failed(message))) => Some(message),
+            _ => None,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn finished_count(&self) -> usize {
+        self.finished.len()
+    }
+
+    pub fn progress(&self) -> LoadProgress {
+        self.progress
+    }
+}
+
+impl Drop for AssetLoader {
+    fn drop(&mut self) {
+        // Dropping the job sender lets every worker fall out of its loop.
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
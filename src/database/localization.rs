@@ -0,0 +1,457 @@
+use super::error::*;
+use super::json::*;
+use super::vfs::FileSystem;
+use std::collections::{BTreeSet, HashMap};
+
+/// CLDR plural categories. Which ones a language uses depends on its `PluralRule`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+impl PluralCategory {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "zero" => Some(Self::Zero),
+            "one" => Some(Self::One),
+            "two" => Some(Self::Two),
+            "few" => Some(Self::Few),
+            "many" => Some(Self::Many),
+            "other" => Some(Self::Other),
+            _ => None,
+        }
+    }
+}
+
+/// How a count picks a plural form. Covers the integer rules of the common language families.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PluralRule {
+    /// Always `other`. Japanese, Chinese, Korean, Thai, ...
+    None,
+    /// `one` for 1. English, German, Spanish, Italian, Dutch, ...
+    One,
+    /// `one` for 0 and 1. French, Brazilian Portuguese, ...
+    ZeroOne,
+    /// `one`, `few` and `many` by the last digits. Russian, Ukrainian, Serbian, ...
+    Slavic,
+    /// `one` for 1, `few` and `many` by the last digits.
+    Polish,
+    /// `one` for 1, `few` for 2 to 4. Czech and Slovak.
+    Czech,
+    /// Every category.
+    Arabic,
+}
+
+impl PluralRule {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Self::None),
+            "one" => Some(Self::One),
+            "zero_one" => Some(Self::ZeroOne),
+            "slavic" => Some(Self::Slavic),
+            "polish" => Some(Self::Polish),
+            "czech" => Some(Self::Czech),
+            "arabic" => Some(Self::Arabic),
+            _ => None,
+        }
+    }
+
+    /// Rule for a language code like `fr` or `pt-BR`. Unknown languages get `One`.
+    pub fn from_language(language: &str) -> Self {
+        let lower = language.to_lowercase().replace('_', "-");
+        if lower == "pt-br" {
+            return Self::ZeroOne;
+        }
+
+        match lower.split('-').next().unwrap_or_default() {
+            "ja" | "zh" | "ko" | "th" | "vi" | "id" | "ms" | "lo" | "my" => Self::None,
+            "fr" | "hy" | "kab" => Self::ZeroOne,
+            "ru" | "uk" | "be" | "sr" | "hr" | "bs" => Self::Slavic,
+            "pl" => Self::Polish,
+            "cs" | "sk" => Self::Czech,
+            "ar" => Self::Arabic,
+            _ => Self::One,
+        }
+    }
+
+    pub fn category(&self, count: i64) -> PluralCategory {
+        let n = count.unsigned_abs();
+        let (n10, n100) = (n % 10, n % 100);
+        let few = (2..=4).contains(&n10) && !(12..=14).contains(&n100);
+        match self {
+            Self::None => PluralCategory::Other,
+            Self::One if n == 1 => PluralCategory::One,
+            Self::ZeroOne if n <= 1 => PluralCategory::One,
+            Self::Slavic if n10 == 1 && n100 != 11 => PluralCategory::One,
+            Self::Slavic if few => PluralCategory::Few,
+            Self::Slavic => PluralCategory::Many,
+            Self::Polish if n == 1 => PluralCategory::One,
+            Self::Polish if few => PluralCategory::Few,
+            Self::Polish => PluralCategory::Many,
+            Self::Czech if n == 1 => PluralCategory::One,
+            Self::Czech if (2..=4).contains(&n) => PluralCategory::Few,
+            Self::Arabic => match n {
+                0 => PluralCategory::Zero,
+                1 => PluralCategory::One,
+                2 => PluralCategory::Two,
+                _ if (3..=10).contains(&n100) => PluralCategory::Few,
+                _ if n100 >= 11 => PluralCategory::Many,
+                _ => PluralCategory::Other,
+            },
+            _ => PluralCategory::Other,
+        }
+    }
+}
+
+pub enum LocalizedString {
+    Text(String),
+    /// Always has an `Other` form.
+    Plural(HashMap<PluralCategory, String>),
+}
+
+impl LocalizedString {
+    fn form(&self, category: PluralCategory) -> &str {
+        match self {
+            LocalizedString::Text(t) => t,
+            LocalizedString::Plural(forms) => forms
+                .get(&category)
+                .unwrap_or(&forms[&PluralCategory::Other]),
+        }
+    }
+
+    fn texts(&self) -> Vec<&str> {
+        match self {
+            LocalizedString::Text(t) => vec![t],
+            LocalizedString::Plural(forms) => forms.values().map(|f| f.as_str()).collect(),
+        }
+    }
+}
+
+pub struct StringTable {
+    pub plural_rule: PluralRule,
+    pub strings: HashMap<String, LocalizedString>,
+}
+
+/// String tables of every language, and which one is shown. Lookups try the active
+/// language, then the fallback language, and give back the key itself when neither has it
+/// so missing strings are easy to spot in game.
+#[derive(Default)]
+pub struct Localization {
+    tables: HashMap<String, StringTable>,
+    language: String,
+    fallback: Option<String>,
+}
+
+impl Localization {
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    pub fn fallback_language(&self) -> Option<&str> {
+        self.fallback.as_deref()
+    }
+
+    /// Names of every language with a string table, sorted.
+    pub fn languages(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.tables.keys().map(|k| k.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    pub fn table(&self, language: &str) -> Option<&StringTable> {
+        self.tables.get(language)
+    }
+
+    pub(crate) fn set_language(&mut self, language: &str) -> Result<(), Error> {
+        if !self.tables.contains_key(language) {
+            return Err(Error::lookup(language));
+        }
+
+        self.language = language.to_string();
+        Ok(())
+    }
+
+    /// Lays `top`'s strings over these, replacing the ones with the same key. Its default
+    /// and fallback languages take over when it has them.
+    pub(crate) fn merge(&mut self, top: Localization) {
+        for (language, table) in top.tables {
+            match self.tables.get_mut(&language) {
+                Some(t) => {
+                    t.plural_rule = table.plural_rule;
+                    t.strings.extend(table.strings);
+                }
+                None => {
+                    self.tables.insert(language, table);
+                }
+            }
+        }
+
+        if !top.language.is_empty() {
+            self.language = top.language;
+        }
+        if top.fallback.is_some() {
+            self.fallback = top.fallback;
+        }
+    }
+
+    fn lookup(&self, key: &str) -> Option<(&StringTable, &LocalizedString)> {
+        [Some(&self.language), self.fallback.as_ref()]
+            .into_iter()
+            .flatten()
+            .filter_map(|l| self.tables.get(l))
+            .find_map(|t| t.strings.get(key).map(|s| (t, s)))
+    }
+
+    pub fn has(&self, key: &str) -> bool {
+        self.lookup(key).is_some()
+    }
+
+    /// The string for `key` with `{name}` parameters filled in from `args`. Plural strings
+    /// use their `other` form.
+    pub fn text(&self, key: &str, args: &[(&str, &str)]) -> String {
+        match self.lookup(key) {
+            Some((_, s)) => substitute(s.form(PluralCategory::Other), args),
+            None => key.to_string(),
+        }
+    }
+
+    /// The plural form of `key` for `count`, picked by its language's rule. `{count}` is
+    /// filled in along with `args`.
+    pub fn plural(&self, key: &str, count: i64, args: &[(&str, &str)]) -> String {
+        let (table, s) = match self.lookup(key) {
+            Some(found) => found,
+            None => return key.to_string(),
+        };
+
+        let count_str = count.to_string();
+        let mut all = vec![("count", count_str.as_str())];
+        all.extend_from_slice(args);
+        substitute(s.form(table.plural_rule.category(count)), &all)
+    }
+
+    /// Every character the active and fallback languages' strings use, sorted.
+    pub fn characters(&self) -> Vec<char> {
+        let mut chars = BTreeSet::new();
+        for language in [Some(&self.language), self.fallback.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(table) = self.tables.get(language) {
+                for s in table.strings.values() {
+                    chars.extend(s.texts().into_iter().flat_map(|t| t.chars()));
+                }
+            }
+        }
+
+        chars.into_iter().filter(|c| !c.is_control()).collect()
+    }
+}
+
+/// Replaces `{name}` with the matching argument. `{{` and `}}` are literal braces, unknown
+/// parameters are left as they are.
+pub fn substitute(text: &str, args: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            out.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+
+        let close = match rest.starts_with('{').then(|| rest.find('}')).flatten() {
+            Some(c) => c,
+            None => {
+                out.push_str(&rest[..1]);
+                rest = &rest[1..];
+                continue;
+            }
+        };
+
+        let name = &rest[1..close];
+        match args.iter().find(|(n, _)| *n == name) {
+            Some((_, value)) => out.push_str(value),
+            None => out.push_str(&rest[..=close]),
+        }
+        rest = &rest[close + 1..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+fn parse_string_table(
+    json: HashMap<String, StringTableJSONValue>,
+    plural_rule: PluralRule,
+) -> Result<StringTable, Error> {
+    let mut strings = HashMap::new();
+    for (key, value) in json {
+        let s = match value {
+            StringTableJSONValue::Text(t) => LocalizedString::Text(t),
+            StringTableJSONValue::Plural(forms) => {
+                let mut parsed = HashMap::new();
+                for (category, text) in forms {
+                    let c = PluralCategory::from_name(&category).ok_or_else(|| {
+                        Error::loading(format!(
+                            "string '{}' has unknown plural category '{}'",
+                            key, category
+                        ))
+                    })?;
+                    parsed.insert(c, text);
+                }
+
+                if !parsed.contains_key(&PluralCategory::Other) {
+                    return Err(Error::loading(format!(
+                        "plural string '{}' has no 'other' form",
+                        key
+                    )));
+                }
+                LocalizedString::Plural(parsed)
+            }
+        };
+        strings.insert(key, s);
+    }
+
+    Ok(StringTable {
+        plural_rule,
+        strings,
+    })
+}
+
+/// Result of loading a localization config.
+pub struct LocalizationImport {
+    pub localization: Localization,
+    /// String table files, relative to the database, for hot reloading.
+    pub files: Vec<String>,
+}
+
+/// Loads every language's string table. The default language is the active one.
+pub fn load_localization(
+    fs: &dyn FileSystem,
+    info: LocalizationJSON,
+) -> Result<LocalizationImport, Error> {
+    let mut tables = HashMap::new();
+    let mut files = Vec::new();
+    for language in info.languages {
+        let plural_rule = match language.plural_rule.as_deref() {
+            Some(name) => PluralRule::from_name(name).ok_or_else(|| {
+                Error::loading(format!("unknown plural rule '{}'", name)).with_entry(&language.name)
+            })?,
+            None => PluralRule::from_language(&language.name),
+        };
+
+        let path = &language.path;
+        let json_data = fs.read_to_string(path)?;
+        let json: HashMap<String, StringTableJSONValue> =
+            serde_json::from_str(&json_data).with_path(path)?;
+        let table = parse_string_table(json, plural_rule)
+            .with_entry(&language.name)
+            .with_path(path)?;
+
+        files.push(language.path.clone());
+        tables.insert(language.name, table);
+    }
+
+    let mut localization = Localization {
+        tables,
+        language: String::new(),
+        fallback: None,
+    };
+    localization.set_language(&info.default_language)?;
+    if let Some(fallback) = info.fallback_language {
+        if !localization.tables.contains_key(&fallback) {
+            return Err(Error::lookup(&fallback));
+        }
+        localization.fallback = Some(fallback);
+    }
+
+    Ok(LocalizationImport {
+        localization,
+        files,
+    })
+}
+
+#[test]
+fn test_localization() {
+    let fs = super::vfs::MemoryFileSystem::new();
+    fs.insert(
+        "en.json",
+        r#"{
+            "greeting": "Hello {player}!",
+            "coins": {"one": "{count} coin", "other": "{count} coins"},
+            "braces": "{{literal}} {unknown}",
+            "only_english": "Quit"
+        }"#,
+    );
+    fs.insert(
+        "ru.json",
+        r#"{
+            "greeting": "Привет, {player}!",
+            "coins": {"one": "{count} монета", "few": "{count} монеты", "many": "{count} монет", "other": "{count} монеты"}
+        }"#,
+    );
+
+    let info: LocalizationJSON = serde_json::from_str(
+        r#"{
+            "default_language": "en",
+            "fallback_language": "en",
+            "languages": [{"name": "en", "path": "en.json"}, {"name": "ru", "path": "ru.json"}]
+        }"#,
+    )
+    .unwrap();
+    let mut l10n = load_localization(&fs, info)
+        .unwrap()
+        .localization;
+
+    assert_eq!(l10n.language(), "en");
+    assert_eq!(l10n.languages(), vec!["en", "ru"]);
+    assert_eq!(l10n.text("greeting", &[("player", "Sam")]), "Hello Sam!");
+    assert_eq!(l10n.plural("coins", 1, &[]), "1 coin");
+    assert_eq!(l10n.plural("coins", 0, &[]), "0 coins");
+    assert_eq!(l10n.text("braces", &[]), "{literal} {unknown}");
+    assert_eq!(l10n.text("missing", &[]), "missing");
+
+    l10n.set_language("ru").unwrap();
+    assert_eq!(
+        l10n.text("greeting", &[("player", "Саша")]),
+        "Привет, Саша!"
+    );
+    assert_eq!(l10n.plural("coins", 21, &[]), "21 монета");
+    assert_eq!(l10n.plural("coins", 3, &[]), "3 монеты");
+    assert_eq!(l10n.plural("coins", 11, &[]), "11 монет");
+    // Falls back to English.
+    assert_eq!(l10n.text("only_english", &[]), "Quit");
+    assert!(l10n.characters().contains(&'П'));
+    assert!(l10n.set_language("fr").is_err());
+    assert_eq!(l10n.language(), "ru");
+
+    // A mod's strings replace the ones with the same key and add new ones.
+    fs.insert("mod_en.json", r#"{"only_english": "Leave", "modded": "New"}"#);
+    let json: LocalizationJSON = serde_json::from_str(
+        r#"{"default_language": "en", "languages": [{"name": "en", "path": "mod_en.json"}]}"#,
+    )
+    .unwrap();
+    l10n.merge(load_localization(&fs, json).unwrap().localization);
+    assert_eq!(l10n.language(), "en");
+    assert_eq!(l10n.text("only_english", &[]), "Leave");
+    assert_eq!(l10n.text("modded", &[]), "New");
+    assert_eq!(l10n.plural("coins", 2, &[]), "2 coins");
+
+    assert_eq!(
+        PluralRule::from_language("fr-CA").category(0),
+        PluralCategory::One
+    );
+    assert_eq!(
+        PluralRule::from_language("ja").category(1),
+        PluralCategory::Other
+    );
+    assert_eq!(PluralRule::Polish.category(22), PluralCategory::Few);
+    assert_eq!(PluralRule::Arabic.category(102), PluralCategory::Other);
+}
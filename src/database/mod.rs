@@ -1,107 +1,389 @@
 pub mod error;
 pub use error::*;
+pub mod vfs;
+pub use vfs::*;
+pub mod pack;
+pub use pack::{PackBuilder, PackFileSystem};
+pub mod mount;
+pub use mount::*;
 pub mod json;
 pub use json::*;
 use std::collections::HashMap;
-use std::fs;
+use std::sync::{Arc, Mutex};
 pub mod load_funcs;
 pub use load_funcs::*;
+pub mod bcn;
+pub use bcn::BlockFormat;
+pub mod compressed;
+pub use compressed::*;
 mod images;
 use images::*;
 pub mod font;
 pub use font::*;
+pub mod atlas;
+pub use atlas::*;
+pub mod aseprite;
+pub use aseprite::*;
+pub mod bmfont;
+pub use bmfont::*;
+pub mod audio;
+pub use audio::*;
+pub mod tiled;
+pub use tiled::*;
+pub mod localization;
+pub use localization::*;
+pub mod loader;
+pub use loader::{AssetKind, LoadProgress, LoadStatus, LoadTicket};
+use loader::*;
+mod hot_reload;
+pub use hot_reload::ReloadedEntry;
+use hot_reload::FileWatcher;
+pub mod validate;
+pub use validate::*;
+mod budget;
+pub use budget::MemoryUsage;
+use budget::MemoryBudget;
+
+#[derive(Default)]
+struct ParsedConfigs {
+    infos: Vec<DatabaseJSON>,
+    config_files: Vec<String>,
+    sprites: HashMap<String, SpriteEntry>,
+    sprite_sheets: HashMap<String, SpriteSheetEntry>,
+    ttfs: HashMap<String, TTFEntry>,
+    sounds: HashMap<String, SoundEntry>,
+    maps: HashMap<String, TiledMap>,
+    localization: Localization,
+    origins: HashMap<(AssetKind, String), usize>,
+}
+
+impl ParsedConfigs {
+    // Lays the configs of `mount` over what's here, replacing entries with the same name.
+    fn merge(&mut self, top: ParsedConfigs, mount: usize) {
+        let sprites = top.sprites.keys().map(|n| (AssetKind::Sprite, n.clone()));
+        let sheets = top
+            .sprite_sheets
+            .keys()
+            .map(|n| (AssetKind::SpriteSheet, n.clone()));
+        let ttfs = top.ttfs.keys().map(|n| (AssetKind::TTF, n.clone()));
+        let sounds = top.sounds.keys().map(|n| (AssetKind::Sound, n.clone()));
+        let names: Vec<_> = sprites.chain(sheets).chain(ttfs).chain(sounds).collect();
+        self.origins.extend(names.into_iter().map(|k| (k, mount)));
+
+        self.sprites.extend(top.sprites);
+        self.sprite_sheets.extend(top.sprite_sheets);
+        self.ttfs.extend(top.ttfs);
+        self.sounds.extend(top.sounds);
+        self.maps.extend(top.maps);
+        self.localization.merge(top.localization);
+        self.infos.extend(top.infos);
+        for path in top.config_files {
+            if !self.config_files.contains(&path) {
+                self.config_files.push(path);
+            }
+        }
+    }
+}
 
 pub struct Database {
-    base_path: String,
+    fs: Arc<dyn FileSystem>,
+    mounts: Vec<Mount>,
+    infos: Vec<DatabaseJSON>,
+    origins: HashMap<(AssetKind, String), usize>,
+    config_files: Vec<String>,
     sprites: HashMap<String, SpriteEntry>,
     sprite_sheets: HashMap<String, SpriteSheetEntry>,
     ttfs: HashMap<String, TTFEntry>,
-    particle_cfg: String,
+    sounds: HashMap<String, SoundEntry>,
+    maps: HashMap<String, TiledMap>,
+    localization: Localization,
+    loader: Option<AssetLoader>,
+    watcher: Option<FileWatcher>,
+    budget: MemoryBudget,
 }
 
 impl Database {
-    fn get_sprite_json(path: &str) -> Result<SpriteJSON, Error> {
-        let json_data = fs::read_to_string(path)?;
-        let info: SpriteJSON = serde_json::from_str(&json_data)?;
+    fn get_sprite_json(fs: &dyn FileSystem, path: &str) -> Result<SpriteJSON, Error> {
+        let json_data = fs.read_to_string(path)?;
+        let info: SpriteJSON = serde_json::from_str(&json_data).with_path(path)?;
         Ok(info)
     }
 
-    fn get_sprite_sheet_json(path: &str) -> Result<SpriteSheetJSON, Error> {
-        let json_data = fs::read_to_string(path)?;
-        let info: SpriteSheetJSON = serde_json::from_str(&json_data)?;
+    fn get_sprite_sheet_json(fs: &dyn FileSystem, path: &str) -> Result<SpriteSheetJSON, Error> {
+        let json_data = fs.read_to_string(path)?;
+        let info: SpriteSheetJSON = serde_json::from_str(&json_data).with_path(path)?;
         Ok(info)
     }
     
-    fn get_ttf_json(path: &str) -> Result<TTFJSON, Error> {
-        let json_data = fs::read_to_string(path)?;
-        let info: TTFJSON = serde_json::from_str(&json_data)?;
+    fn get_ttf_json(fs: &dyn FileSystem, path: &str) -> Result<TTFJSON, Error> {
+        let json_data = fs.read_to_string(path)?;
+        let info: TTFJSON = serde_json::from_str(&json_data).with_path(path)?;
+        Ok(info)
+    }
+
+    fn get_atlas_json(fs: &dyn FileSystem, path: &str) -> Result<AtlasJSON, Error> {
+        let json_data = fs.read_to_string(path)?;
+        let info: AtlasJSON = serde_json::from_str(&json_data).with_path(path)?;
+        Ok(info)
+    }
+
+    fn get_bmfont_json(fs: &dyn FileSystem, path: &str) -> Result<BMFontJSON, Error> {
+        let json_data = fs.read_to_string(path)?;
+        let info: BMFontJSON = serde_json::from_str(&json_data).with_path(path)?;
+        Ok(info)
+    }
+
+    fn get_audio_json(fs: &dyn FileSystem, path: &str) -> Result<AudioJSON, Error> {
+        let json_data = fs.read_to_string(path)?;
+        let info: AudioJSON = serde_json::from_str(&json_data).with_path(path)?;
+        Ok(info)
+    }
+
+    fn get_tiled_json(fs: &dyn FileSystem, path: &str) -> Result<TiledJSON, Error> {
+        let json_data = fs.read_to_string(path)?;
+        let info: TiledJSON = serde_json::from_str(&json_data).with_path(path)?;
+        Ok(info)
+    }
+
+    fn get_localization_json(fs: &dyn FileSystem, path: &str) -> Result<LocalizationJSON, Error> {
+        let json_data = fs.read_to_string(path)?;
+        let info: LocalizationJSON = serde_json::from_str(&json_data).with_path(path)?;
+        Ok(info)
+    }
+
+    fn get_aseprite_json(fs: &dyn FileSystem, path: &str) -> Result<AsepriteJSON, Error> {
+        let json_data = fs.read_to_string(path)?;
+        let info: AsepriteJSON = serde_json::from_str(&json_data).with_path(path)?;
         Ok(info)
     }
     
-    pub fn base_path(&self) -> &str {
-        &self.base_path
+    /// Where every file of the database is read from.
+    pub fn filesystem(&self) -> &Arc<dyn FileSystem> {
+        &self.fs
     }
 
-    pub fn new(base_path: &str) -> Result<Self, Error> {
-        let json_data = fs::read_to_string(format!("{}/shoyu.json", base_path))?;
+    fn load_configs(fs: &dyn FileSystem) -> Result<ParsedConfigs, Error> {
+        let path = "shoyu.json";
+        let json_data = fs.read_to_string(path)?;
+
+        let info: DatabaseJSON = serde_json::from_str(&json_data).with_path(path)?;
 
-        let info: DatabaseJSON = serde_json::from_str(&json_data)?;
+        let mut config_files = vec![path.to_string()];
+        let cfgs = [
+            &info.sprite_cfg,
+            &info.sprite_sheet_cfg,
+            &info.ttf_cfg,
+            &info.aseprite_cfg,
+            &info.atlas_cfg,
+            &info.bmfont_cfg,
+            &info.audio_cfg,
+            &info.tiled_cfg,
+            &info.localization_cfg,
+        ];
+        for cfg in cfgs.iter().filter_map(|c| c.as_ref()) {
+            config_files.push(cfg.clone());
+        }
 
-        let sprites = if let Some(sprite) = info.sprite_cfg {
-            parse_sprites(Database::get_sprite_json(&format!(
-                "{}/{}",
-                base_path,
-                sprite.as_str()
-            ))?)
+        let sprites = if let Some(sprite) = info.sprite_cfg.as_ref() {
+            parse_sprites(Database::get_sprite_json(fs, sprite)?)
         } else {
             HashMap::new()
         };
 
-        let sprite_sheets = if let Some(sprite) = info.sprite_sheet_cfg {
-            parse_sprite_sheets(Database::get_sprite_sheet_json(&format!(
-                "{}/{}",
-                base_path,
-                sprite.as_str()
-            ))?)
+        let mut sprite_sheets = if let Some(sprite) = info.sprite_sheet_cfg.as_ref() {
+            parse_sprite_sheets(Database::get_sprite_sheet_json(fs, sprite)?)
         } else {
             HashMap::new()
         };
 
-        let ttfs = if let Some(ttf) = info.ttf_cfg {
-            parse_ttfs(Database::get_ttf_json(&format!(
-                "{}/{}",
-                base_path,
-                ttf.as_str()
-            ))?)
+        let mut ttfs = if let Some(ttf) = info.ttf_cfg.as_ref() {
+            parse_ttfs(Database::get_ttf_json(fs, ttf)?)
         } else {
             HashMap::new()
         };
 
-        Ok(Database {
-            base_path: base_path.to_string(),
+        if let Some(aseprite) = info.aseprite_cfg.as_ref() {
+            let sheets = Database::get_aseprite_json(fs, aseprite)?;
+            for sheet in sheets.sheets {
+                let path = sheet.path.clone();
+                let json_data = fs.read_to_string(&path)?;
+                let mut cfg = parse_aseprite(&sheet.name, &sheet.path, &json_data)
+                    .with_entry(&sheet.name)
+                    .with_path(&path)?;
+                cfg.texture = sheet.texture;
+                sprite_sheets.insert(sheet.name, SpriteSheetEntry { cfg, loaded: None });
+                config_files.push(path);
+            }
+        }
+
+        if let Some(atlas) = info.atlas_cfg.as_ref() {
+            let atlases = Database::get_atlas_json(fs, atlas)?;
+            for atlas in atlases.atlases {
+                let path = atlas.path.clone();
+                let json_data = fs.read_to_string(&path)?;
+                let mut cfg = parse_texture_packer(&atlas.name, &atlas.path, &json_data)
+                    .with_entry(&atlas.name)
+                    .with_path(&path)?;
+                cfg.texture = atlas.texture;
+                sprite_sheets.insert(atlas.name, SpriteSheetEntry { cfg, loaded: None });
+                config_files.push(path);
+            }
+        }
+
+        if let Some(bmfont) = info.bmfont_cfg.as_ref() {
+            let fonts = Database::get_bmfont_json(fs, bmfont)?;
+            for font in fonts.fonts {
+                config_files.push(font.path.clone());
+                ttfs.insert(font.name.clone(), TTFEntry::bitmap(font));
+            }
+        }
+
+        let sounds = match info.audio_cfg.as_ref() {
+            Some(audio) => parse_sounds(Database::get_audio_json(fs, audio)?),
+            None => HashMap::new(),
+        };
+
+        let mut maps = HashMap::new();
+        if let Some(tiled) = info.tiled_cfg.as_ref() {
+            let tiled = Database::get_tiled_json(fs, tiled)?;
+            for map in tiled.maps {
+                let import = load_tiled_map(fs, &map.name, &map.path)?;
+                for cfg in import.sheets {
+                    sprite_sheets.insert(cfg.name.clone(), SpriteSheetEntry { cfg, loaded: None });
+                }
+                config_files.extend(import.files);
+                maps.insert(map.name, import.map);
+            }
+        }
+
+        let mut localization = Localization::default();
+        if let Some(cfg) = info.localization_cfg.as_ref() {
+            let json = Database::get_localization_json(fs, cfg)?;
+            let import = load_localization(fs, json)?;
+            config_files.extend(import.files);
+            localization = import.localization;
+        }
+
+        Ok(ParsedConfigs {
+            infos: vec![info],
+            config_files,
             sprites,
             sprite_sheets,
             ttfs,
-            particle_cfg: if info.particle_cfg.is_some() {
-                info.particle_cfg.unwrap().clone()
-            } else {
-                "".to_string()
-            },
+            sounds,
+            maps,
+            localization,
+            origins: HashMap::new(),
         })
     }
 
+    // Each mount's configs are read from that mount alone, then laid over each other.
+    fn load_mounts(mounts: &[Mount]) -> Result<ParsedConfigs, Error> {
+        let mut merged = ParsedConfigs::default();
+        for (index, mount) in mounts.iter().enumerate() {
+            let parsed = match Database::load_configs(mount.fs.as_ref()) {
+                Ok(p) => p,
+                Err(e) if mounts.len() > 1 => return Err(e.in_mount(&mount.name)),
+                Err(e) => return Err(e),
+            };
+            merged.merge(parsed, index);
+        }
+
+        Ok(merged)
+    }
+
+    /// Opens the database in the `base_path` directory, or in the archive at `base_path`
+    /// if it's a file made by `shoyu-pack`.
+    pub fn new(base_path: &str) -> Result<Self, Error> {
+        Self::with_mounts(vec![Mount::open(base_path)?])
+    }
+
+    /// Opens several databases stacked on each other, like the base game followed by DLC
+    /// and mods. See `with_mounts`.
+    pub fn new_layered(paths: &[&str]) -> Result<Self, Error> {
+        let mounts = paths.iter().map(|p| Mount::open(p)).collect::<Result<_, _>>()?;
+        Self::with_mounts(mounts)
+    }
+
+    /// Opens a database whose `shoyu.json` sits at the root of `fs`. Use a
+    /// `MemoryFileSystem` for tests or `embed_files!` to ship the data inside the binary.
+    pub fn with_filesystem(fs: Arc<dyn FileSystem>) -> Result<Self, Error> {
+        Self::with_mounts(vec![Mount::new("base", fs)])
+    }
+
+    /// Opens a stack of database roots. Sprites, sprite sheets, fonts, sounds, maps and
+    /// strings of later mounts replace the ones of earlier mounts with the same name, and
+    /// their particle configs are merged by particle name. Asset files are looked up from
+    /// the last mount down, see `LayeredFileSystem`.
+    pub fn with_mounts(mounts: Vec<Mount>) -> Result<Self, Error> {
+        let fs: Arc<dyn FileSystem> = match mounts.len() {
+            0 => return Err(Error::loading("no database to mount")),
+            1 => mounts[0].fs.clone(),
+            _ => Arc::new(LayeredFileSystem::new(mounts.clone())),
+        };
+
+        let parsed = Database::load_mounts(&mounts)?;
+        let budget = parsed.infos.iter().rev().find_map(|i| i.memory_budget_mb);
+        let budget = budget.map(|mb| mb * 1024 * 1024);
+
+        Ok(Database {
+            fs,
+            mounts,
+            infos: parsed.infos,
+            origins: parsed.origins,
+            sprites: parsed.sprites,
+            sprite_sheets: parsed.sprite_sheets,
+            ttfs: parsed.ttfs,
+            sounds: parsed.sounds,
+            maps: parsed.maps,
+            localization: parsed.localization,
+            config_files: parsed.config_files,
+            loader: None,
+            watcher: None,
+            budget: MemoryBudget::new(budget),
+        })
+    }
+
+    /// The particle config of the last mount that has one. `particle_system_cfgs` lists
+    /// all of them.
     pub fn particle_system_cfg_path(&self) -> Result<String, Error> {
-        return Ok(self.particle_cfg.clone());
+        let cfgs = self.particle_system_cfgs();
+        return Ok(cfgs.last().map(|(_, p)| p.to_string()).unwrap_or_default());
+    }
+
+    /// Every mount's particle config with the mount to read it from, first mount first.
+    pub fn particle_system_cfgs(&self) -> Vec<(&Mount, &str)> {
+        self.mounts
+            .iter()
+            .zip(&self.infos)
+            .filter_map(|(m, info)| info.particle_cfg.as_deref().map(|p| (m, p)))
+            .collect()
+    }
+
+    pub fn mounts(&self) -> &[Mount] {
+        &self.mounts
+    }
+
+    /// The mount whose config an entry came from, for tracking down what a mod replaced.
+    pub fn entry_mount(&self, kind: AssetKind, name: &str) -> Option<&Mount> {
+        let index = self.origins.get(&(kind, name.to_string()))?;
+        self.mounts.get(*index)
+    }
+
+    /// The mount a file is read from.
+    pub fn file_mount(&self, path: &str) -> Option<&Mount> {
+        self.mounts.iter().rev().find(|m| m.fs.exists(path))
     }
 
     pub fn fetch_sprite(&mut self, name: &str) -> Result<&SpriteEntry, Error> {
-        // TODO probably async this.
+        self.wait_for_entry(AssetKind::Sprite, name)?;
         if let Some(entry) = self.sprites.get_mut(name) {
             if entry.loaded.is_none() {
-                entry.load(&self.base_path);
+                entry.try_load(self.fs.as_ref()).with_entry(name)?;
             }
 
-            return Ok(entry);
+            self.touch(AssetKind::Sprite, name);
+            return Ok(&self.sprites[name]);
         }
 
         return Err(Error::LookupError(LookupError {
@@ -110,23 +392,22 @@ impl Database {
     }
 
     pub fn fetch_ttf(&mut self, name: &str) -> Result<&TTFEntry, Error> {
-        let default_typeset: Vec<char> = (0 as u8 as char..127 as u8 as char).collect();
-        // TODO probably async this.
-        if let Some(entry) = self.ttfs.get_mut(name) {
-            if entry.loaded.is_none() {
-                #[allow(unused_assignments)]
-                let mut str = Vec::new();
-                let glyphs: &[char] = match entry.cfg.glyphs.clone() {
-                    Some(g) => {
-                        str = g.chars().collect();
-                        &str
-                    }
-                    None => &default_typeset,
-                };
-                entry.load(&self.base_path, glyphs);
+        self.fetch_ttf_mut(name).map(|e| &*e)
+    }
+
+    /// Mutable variant of `fetch_ttf`. Needed to rasterize glyphs that aren't cached yet.
+    pub fn fetch_ttf_mut(&mut self, name: &str) -> Result<&mut TTFEntry, Error> {
+        self.wait_for_entry(AssetKind::TTF, name)?;
+        if self.ttfs.get(name).is_some_and(|e| e.loaded.is_none()) {
+            let job = self.font_job(name)?;
+            if let LoadOutput::Font(font) = run_job(self.fs.as_ref(), job).with_entry(name)? {
+                self.ttfs.get_mut(name).unwrap().loaded = Some(Arc::new(Mutex::new(*font)));
             }
+        }
 
-            return Ok(entry);
+        if self.ttfs.contains_key(name) {
+            self.touch(AssetKind::TTF, name);
+            return Ok(self.ttfs.get_mut(name).unwrap());
         }
 
         return Err(Error::LookupError(LookupError {
@@ -135,24 +416,321 @@ impl Database {
     }
 
     pub fn fetch_sprite_sheet(&mut self, name: &str) -> Result<&SpriteSheetEntry, Error> {
-        // TODO probably async this.
+        self.wait_for_entry(AssetKind::SpriteSheet, name)?;
         if let Some(entry) = self.sprite_sheets.get_mut(name) {
             if entry.loaded.is_none() {
-                entry.load(&self.base_path);
+                entry.try_load(self.fs.as_ref()).with_entry(name)?;
+            }
+
+            self.touch(AssetKind::SpriteSheet, name);
+            return Ok(&self.sprite_sheets[name]);
+        }
+
+        return Err(Error::LookupError(LookupError {
+            entry: name.to_string(),
+        }));
+    }
+
+    /// Decoded samples of a sound entry, for playing as a whole.
+    pub fn fetch_sound(&mut self, name: &str) -> Result<&SoundEntry, Error> {
+        self.wait_for_entry(AssetKind::Sound, name)?;
+        if let Some(entry) = self.sounds.get_mut(name) {
+            if entry.loaded.is_none() {
+                entry.try_load(self.fs.as_ref()).with_entry(name)?;
             }
 
-            return Ok(entry);
+            self.touch(AssetKind::Sound, name);
+            return Ok(&self.sounds[name]);
         }
 
         return Err(Error::LookupError(LookupError {
             entry: name.to_string(),
         }));
     }
+
+    /// Opens a sound entry for decoding piece by piece, as music is. Nothing is cached.
+    pub fn open_sound_stream(&self, name: &str) -> Result<SoundStream, Error> {
+        let entry = self.sounds.get(name).ok_or(Error::LookupError(LookupError {
+            entry: name.to_string(),
+        }))?;
+
+        SoundStream::open(self.fs.as_ref(), &entry.cfg.path).with_entry(name)
+    }
+
+    /// A Tiled map's layers, objects and tilesets. Maps are parsed along with the configs,
+    /// so this never touches the disk. Draw one with `ResourceManager::make_tilemap`.
+    pub fn fetch_tilemap(&self, name: &str) -> Result<&TiledMap, Error> {
+        self.maps.get(name).ok_or(Error::LookupError(LookupError {
+            entry: name.to_string(),
+        }))
+    }
+
+    /// String tables for every language. Look strings up here rather than hardcoding them.
+    pub fn localization(&self) -> &Localization {
+        &self.localization
+    }
+
+    /// Switches the language strings are looked up in. Loaded TrueType fonts rasterize the
+    /// new language's characters right away, so they're uploaded with the next frame's
+    /// glyphs instead of one at a time as text is drawn.
+    pub fn set_language(&mut self, language: &str) -> Result<(), Error> {
+        self.localization.set_language(language)?;
+
+        let chars = self.localization.characters();
+        for entry in self.ttfs.values_mut() {
+            if let (FontKind::TrueType, Some(font)) = (entry.kind, entry.loaded.as_ref()) {
+                font.lock().unwrap().preload(&chars);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn loader(&mut self) -> &mut AssetLoader {
+        let fs = self.fs.clone();
+        self.loader.get_or_insert_with(|| AssetLoader::new(fs))
+    }
+
+    /// Queues the sprite's image to be decoded on a worker thread.
+    pub fn request_sprite(&mut self, name: &str) -> Result<LoadTicket, Error> {
+        let entry = self.sprites.get(name).ok_or(Error::LookupError(LookupError {
+            entry: name.to_string(),
+        }))?;
+
+        if entry.loaded.is_some() {
+            return Ok(LoadTicket::READY);
+        }
+
+        let path = entry.cfg.image_path.clone();
+        let texture = entry.texture();
+        Ok(self
+            .loader()
+            .submit(AssetKind::Sprite, name, LoadJob::Image { path, texture }))
+    }
+
+    /// Queues the sprite sheet's image to be decoded on a worker thread.
+    pub fn request_sprite_sheet(&mut self, name: &str) -> Result<LoadTicket, Error> {
+        let entry = self
+            .sprite_sheets
+            .get(name)
+            .ok_or(Error::LookupError(LookupError {
+                entry: name.to_string(),
+            }))?;
+
+        if entry.loaded.is_some() {
+            return Ok(LoadTicket::READY);
+        }
+
+        let path = entry.cfg.image_path.clone();
+        let texture = entry.texture();
+        Ok(self
+            .loader()
+            .submit(AssetKind::SpriteSheet, name, LoadJob::Image { path, texture }))
+    }
+
+    /// Queues the font's atlas to be rasterized on a worker thread.
+    pub fn request_ttf(&mut self, name: &str) -> Result<LoadTicket, Error> {
+        let entry = self.ttfs.get(name).ok_or(Error::LookupError(LookupError {
+            entry: name.to_string(),
+        }))?;
+
+        if entry.loaded.is_some() {
+            return Ok(LoadTicket::READY);
+        }
+
+        let job = self.font_job(name)?;
+        Ok(self.loader().submit(AssetKind::TTF, name, job))
+    }
+
+    /// Queues the sound to be decoded on a worker thread.
+    pub fn request_sound(&mut self, name: &str) -> Result<LoadTicket, Error> {
+        let entry = self.sounds.get(name).ok_or(Error::LookupError(LookupError {
+            entry: name.to_string(),
+        }))?;
+
+        if entry.loaded.is_some() {
+            return Ok(LoadTicket::READY);
+        }
+
+        let path = entry.cfg.path.clone();
+        Ok(self
+            .loader()
+            .submit(AssetKind::Sound, name, LoadJob::Sound { path }))
+    }
+
+    // Load job for a font entry with its fallback chain flattened, depth first.
+    fn font_job(&self, name: &str) -> Result<LoadJob, Error> {
+        let lookup = |n: &str| {
+            self.ttfs.get(n).ok_or(Error::LookupError(LookupError {
+                entry: n.to_string(),
+            }))
+        };
+
+        let entry = lookup(name)?;
+        let mut chain: Vec<FallbackFont> = Vec::new();
+        let mut seen = vec![name.to_string()];
+        let mut pending: Vec<&String> = entry.cfg.fallbacks.iter().flatten().rev().collect();
+        while let Some(next) = pending.pop() {
+            if seen.contains(next) {
+                continue;
+            }
+
+            let fallback = lookup(next)?;
+            seen.push(next.clone());
+            pending.extend(fallback.cfg.fallbacks.iter().flatten().rev());
+            chain.push(FallbackFont {
+                path: fallback.cfg.path.clone(),
+                kind: fallback.kind,
+            });
+        }
+
+        Ok(entry.load_job(&entry.cfg.path, chain, self.localization.characters()))
+    }
+
+    fn apply_load(&mut self, ticket: LoadTicket, result: Result<LoadOutput, Error>) {
+        let status = match &result {
+            Ok(_) => LoadStatus::Ready,
+            Err(e) => LoadStatus::Failed(e.to_string()),
+        };
+
+        let (kind, name) = match self.loader().finish(ticket, status) {
+            Some(k) => k,
+            None => return,
+        };
+
+        match (kind, result) {
+            (AssetKind::Sprite, Ok(LoadOutput::Image(img))) => {
+                if let Some(entry) = self.sprites.get_mut(&name) {
+                    entry.loaded = Some(img);
+                }
+            }
+            (AssetKind::SpriteSheet, Ok(LoadOutput::Image(img))) => {
+                if let Some(entry) = self.sprite_sheets.get_mut(&name) {
+                    entry.loaded = Some(img);
+                }
+            }
+            (AssetKind::TTF, Ok(LoadOutput::Font(font))) => {
+                if let Some(entry) = self.ttfs.get_mut(&name) {
+                    entry.loaded = Some(Arc::new(Mutex::new(*font)));
+                }
+            }
+            (AssetKind::Sound, Ok(LoadOutput::Sound(sound))) => {
+                if let Some(entry) = self.sounds.get_mut(&name) {
+                    entry.loaded = Some(Arc::new(sound));
+                }
+            }
+            _ => return,
+        }
+
+        self.touch(kind, &name);
+    }
+
+    /// Moves any finished background loads into their entries. Never blocks.
+    pub fn poll_loads(&mut self) {
+        if self.loader.is_none() {
+            return;
+        }
+
+        while let Some((ticket, result)) = self.loader().try_recv() {
+            self.apply_load(ticket, result);
+        }
+    }
+
+    /// How the ticket's load is going. A finished ticket's status is kept until its entry
+    /// is requested again.
+    pub fn load_status(&mut self, ticket: LoadTicket) -> LoadStatus {
+        if ticket == LoadTicket::READY {
+            return LoadStatus::Ready;
+        }
+
+        self.poll_loads();
+        match self.loader.as_ref().and_then(|l| l.status(ticket)) {
+            Some(status) => status,
+            None => LoadStatus::Failed("Unknown load ticket".to_string()),
+        }
+    }
+
+    /// Why the last background load of an entry failed. Cleared when the entry is
+    /// requested again.
+    pub fn load_failure(&mut self, kind: AssetKind, name: &str) -> Option<Error> {
+        self.poll_loads();
+        let message = self.loader.as_ref()?.failure(kind, name)?;
+        Some(Error::loading(message).with_entry(name))
+    }
+
+    pub fn load_progress(&mut self) -> LoadProgress {
+        self.poll_loads();
+        match self.loader.as_ref() {
+            Some(l) => l.progress(),
+            None => Default::default(),
+        }
+    }
+
+    /// Blocks until the ticket's load has finished.
+    pub fn wait_for(&mut self, ticket: LoadTicket) -> Result<(), Error> {
+        loop {
+            match self.load_status(ticket) {
+                LoadStatus::Ready => return Ok(()),
+                LoadStatus::Failed(message) => return Err(Error::loading(message)),
+                LoadStatus::Pending => match self.loader().recv() {
+                    Some((t, result)) => self.apply_load(t, result),
+                    None => return Ok(()),
+                },
+            }
+        }
+    }
+
+    /// Blocks until every queued background load has finished.
+    pub fn wait_all(&mut self) {
+        if self.loader.is_none() {
+            return;
+        }
+
+        while let Some((ticket, result)) = self.loader().recv() {
+            self.apply_load(ticket, result);
+        }
+    }
+
+    // Synchronous fetches must not race a worker that is already loading the same entry.
+    fn wait_for_entry(&mut self, kind: AssetKind, name: &str) -> Result<(), Error> {
+        let ticket = self.loader.as_ref().and_then(|l| l.pending(kind, name));
+        match ticket {
+            Some(t) => self.wait_for(t),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_sprite_loaded(&self, name: &str) -> bool {
+        self.sprites.get(name).is_some_and(|e| e.loaded.is_some())
+    }
+
+    pub fn is_sprite_sheet_loaded(&self, name: &str) -> bool {
+        self.sprite_sheets
+            .get(name)
+            .is_some_and(|e| e.loaded.is_some())
+    }
+
+    pub fn is_ttf_loaded(&self, name: &str) -> bool {
+        self.ttfs.get(name).is_some_and(|e| e.loaded.is_some())
+    }
+
+    pub fn is_sound_loaded(&self, name: &str) -> bool {
+        self.sounds.get(name).is_some_and(|e| e.loaded.is_some())
+    }
 }
 
 #[test]
 fn test_database() {
-    let res = Database::new("/wksp/database");
+    let fs = MemoryFileSystem::new()
+        .with("shoyu.json", r#"{"sprite_cfg": "sprites.json", "sprite_sheet_cfg": "sheets.json"}"#)
+        .with("sprites.json", r#"{"sprites": [{"name": "name", "image_path": "img/a.png"}]}"#)
+        .with(
+            "sheets.json",
+            r#"{"sprite_sheets": [{"name": "name", "image_path": "img/a.png"}]}"#,
+        )
+        .with("img/a.png", test_png(4, 4));
+
+    let res = Database::with_filesystem(Arc::new(fs));
     assert!(res.is_ok());
 
     let mut db = res.unwrap();
@@ -161,4 +739,54 @@ fn test_database() {
 
     let sprite = db.fetch_sprite_sheet("name");
     assert!(sprite.is_ok());
+    assert!(db.fetch_sprite("other").is_err());
+
+    // A database that was compiled in reads the same way.
+    let png: &'static [u8] = Box::leak(test_png(2, 2).into_boxed_slice());
+    let embedded = EmbeddedFileSystem::new(&[
+        ("shoyu.json", br#"{"sprite_cfg": "sprites.json"}"#),
+        ("sprites.json", br#"{"sprites": [{"name": "a", "image_path": "a.png"}]}"#),
+        ("a.png", png),
+    ]);
+    let mut db = Database::with_filesystem(Arc::new(embedded)).unwrap();
+    assert_eq!(db.fetch_sprite("a").unwrap().loaded.as_ref().unwrap().size, [2, 2]);
+}
+
+#[test]
+fn test_background_load() {
+    let fs = MemoryFileSystem::new()
+        .with("a.png", test_png(4, 4))
+        .with("shoyu.json", r#"{"sprite_cfg": "sprites.json"}"#)
+        .with(
+            "sprites.json",
+            r#"{"sprites": [{"name": "a", "image_path": "a.png"}, {"name": "missing", "image_path": "nope.png"}]}"#,
+        );
+
+    let mut db = Database::with_filesystem(Arc::new(fs)).unwrap();
+    let ok = db.request_sprite("a").unwrap();
+    let bad = db.request_sprite("missing").unwrap();
+    assert_eq!(db.request_sprite("a").unwrap(), ok);
+    assert!(db.request_sprite("not_an_entry").is_err());
+
+    assert!(db.wait_for(ok).is_ok());
+    assert!(db.is_sprite_loaded("a"));
+    assert!(db.wait_for(bad).is_err());
+    assert!(db.load_failure(AssetKind::Sprite, "missing").is_some());
+    assert!(db.load_failure(AssetKind::Sprite, "a").is_none());
+    // Resident entries all share one ticket instead of piling up statuses.
+    assert_eq!(db.request_sprite("a").unwrap(), LoadTicket::READY);
+    assert_eq!(db.load_status(LoadTicket::READY), LoadStatus::Ready);
+
+    let progress = db.load_progress();
+    assert!(progress.is_done());
+    assert_eq!(progress.total, 2);
+    assert_eq!(progress.failed, 1);
+
+    // Retrying an entry replaces its old status instead of adding one per ticket.
+    for _ in 0..8 {
+        let retry = db.request_sprite("missing").unwrap();
+        assert!(db.wait_for(retry).is_err());
+    }
+    assert_eq!(db.load_status(bad), LoadStatus::Failed("Unknown load ticket".to_string()));
+    assert_eq!(db.loader.as_ref().unwrap().finished_count(), 2);
 }
@@ -0,0 +1,160 @@
+use super::error::*;
+use super::pack::PackFileSystem;
+use super::vfs::{DirectoryFileSystem, FileReader, FileSystem};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// One database root in a stack of them, like the base game, a DLC or a player mod. Each
+/// has its own `shoyu.json`.
+#[derive(Clone)]
+pub struct Mount {
+    pub name: String,
+    pub fs: Arc<dyn FileSystem>,
+}
+
+impl Mount {
+    pub fn new(name: &str, fs: Arc<dyn FileSystem>) -> Self {
+        Self {
+            name: name.to_string(),
+            fs,
+        }
+    }
+
+    /// A database directory, or an archive made by `shoyu-pack` if `path` is a file. The
+    /// mount is named after the path.
+    pub fn open(path: &str) -> Result<Self, Error> {
+        let fs: Arc<dyn FileSystem> = match std::path::Path::new(path).is_file() {
+            true => Arc::new(PackFileSystem::open(path)?),
+            false => Arc::new(DirectoryFileSystem::new(path)),
+        };
+
+        Ok(Self::new(path, fs))
+    }
+}
+
+/// Stacks the files of several mounts. A file in a later mount hides the one with the same
+/// path in an earlier mount, so a mod can swap a texture without touching any config.
+pub struct LayeredFileSystem {
+    mounts: Vec<Mount>,
+}
+
+impl LayeredFileSystem {
+    pub fn new(mounts: Vec<Mount>) -> Self {
+        Self { mounts }
+    }
+
+    /// The mount a file is read from.
+    pub fn mount_of(&self, path: &str) -> Option<&Mount> {
+        self.mounts.iter().rev().find(|m| m.fs.exists(path))
+    }
+
+    fn top(&self, path: &str) -> Result<&Mount, Error> {
+        match self.mount_of(path) {
+            Some(m) => Ok(m),
+            None => {
+                let e = std::io::Error::new(std::io::ErrorKind::NotFound, "in no mount");
+                Err(Error::from(e).with_path(path))
+            }
+        }
+    }
+}
+
+impl FileSystem for LayeredFileSystem {
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        self.top(path)?.fs.read(path)
+    }
+
+    fn open(&self, path: &str) -> Result<Box<dyn FileReader>, Error> {
+        self.top(path)?.fs.open(path)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.mount_of(path).is_some()
+    }
+
+    /// The latest time of every mount that has the file, so a change in a mount that's
+    /// hidden still shows up. Configs are read per mount and have to be reloaded then.
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        self.mounts.iter().filter_map(|m| m.fs.modified(path)).max()
+    }
+}
+
+#[test]
+fn test_mounts() {
+    use super::load_funcs::test_png;
+    use super::vfs::MemoryFileSystem;
+    use super::{AssetKind, Database};
+
+    let base = MemoryFileSystem::new()
+        .with(
+            "shoyu.json",
+            r#"{"sprite_cfg": "sprites.json", "particle_cfg": "fx.json"}"#,
+        )
+        .with(
+            "sprites.json",
+            r#"{"sprites": [
+                {"name": "hero", "image_path": "img/hero.png"},
+                {"name": "tree", "image_path": "img/tree.png"}
+            ]}"#,
+        )
+        .with("img/hero.png", test_png(4, 4))
+        .with("img/tree.png", test_png(4, 4));
+    let dlc = MemoryFileSystem::new()
+        .with(
+            "shoyu.json",
+            r#"{"sprite_cfg": "sprites.json", "particle_cfg": "fx.json"}"#,
+        )
+        .with(
+            "sprites.json",
+            r#"{"sprites": [
+                {"name": "hero", "image_path": "dlc/hero.png"},
+                {"name": "sword", "image_path": "img/tree.png"}
+            ]}"#,
+        )
+        .with("dlc/hero.png", test_png(8, 8));
+    // A mod that only swaps a texture.
+    let texture_mod = MemoryFileSystem::new()
+        .with("shoyu.json", "{}")
+        .with("img/tree.png", test_png(2, 2));
+
+    let mounts = vec![
+        Mount::new("base", Arc::new(base)),
+        Mount::new("dlc", Arc::new(dlc)),
+        Mount::new("mod", Arc::new(texture_mod)),
+    ];
+    let mut db = Database::with_mounts(mounts).unwrap();
+
+    let size = |db: &mut Database, name: &str| {
+        db.fetch_sprite(name).unwrap().loaded.as_ref().unwrap().size
+    };
+    assert_eq!(size(&mut db, "hero"), [8, 8]);
+    assert_eq!(size(&mut db, "tree"), [2, 2]);
+    assert_eq!(size(&mut db, "sword"), [2, 2]);
+
+    let mount = |db: &Database, name: &str| {
+        db.entry_mount(AssetKind::Sprite, name)
+            .map(|m| m.name.clone())
+    };
+    assert_eq!(mount(&db, "hero").as_deref(), Some("dlc"));
+    assert_eq!(mount(&db, "tree").as_deref(), Some("base"));
+    assert_eq!(mount(&db, "missing"), None);
+    assert_eq!(db.file_mount("img/tree.png").unwrap().name, "mod");
+    assert_eq!(db.file_mount("img/hero.png").unwrap().name, "base");
+
+    let cfgs: Vec<&str> = db
+        .particle_system_cfgs()
+        .iter()
+        .map(|(m, _)| m.name.as_str())
+        .collect();
+    assert_eq!(cfgs, vec!["base", "dlc"]);
+
+    // Errors in a mount name it.
+    let broken = MemoryFileSystem::new().with("shoyu.json", r#"{"sprite_cfg": "nope.json"}"#);
+    let mounts = vec![
+        Mount::new("base", db.mounts()[0].fs.clone()),
+        Mount::new("broken", Arc::new(broken)),
+    ];
+    let e = Database::with_mounts(mounts).err().unwrap();
+    assert_eq!(e.path(), Some("broken:nope.json"));
+    assert!(Database::with_mounts(Vec::new()).is_err());
+}
@@ -0,0 +1,415 @@
+use super::error::*;
+use super::vfs::{normalize_path, FileReader, FileSystem};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Crc;
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+// Layout, all little endian:
+//   header: magic, version u32, entry count u32, index offset u64
+//   entry data, one after the other
+//   index: per entry a u16 path length, the path, data offset u64, stored size u64,
+//          original size u64, compression u8 and the CRC32 of the original bytes
+const PACK_MAGIC: &[u8; 8] = b"SHOYUPAK";
+const PACK_VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 24;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PackCompression {
+    None,
+    Zlib,
+}
+
+/// Where a file sits in an archive.
+#[derive(Clone, Debug)]
+pub struct PackEntry {
+    pub offset: u64,
+    pub stored_size: u64,
+    pub size: u64,
+    pub compression: PackCompression,
+    pub crc32: u32,
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+fn pack_error(message: impl Into<String>) -> Error {
+    Error::loading(format!("bad pack archive: {}", message.into()))
+}
+
+/// Reads files out of a single archive made by `PackBuilder` or `shoyu-pack`. Only the
+/// index is read up front, each file is read and checked when it's asked for.
+pub struct PackFileSystem {
+    reader: Mutex<Box<dyn FileReader>>,
+    entries: HashMap<String, PackEntry>,
+}
+
+impl PackFileSystem {
+    pub fn open(path: &str) -> Result<Self, Error> {
+        let file = std::fs::File::open(path).with_path(path)?;
+        Self::new(Box::new(std::io::BufReader::new(file))).with_path(path)
+    }
+
+    /// An archive that's already in memory, like one compiled in with `include_bytes!`.
+    pub fn from_bytes(bytes: impl AsRef<[u8]> + Send + 'static) -> Result<Self, Error> {
+        Self::new(Box::new(Cursor::new(bytes)))
+    }
+
+    pub fn new(mut reader: Box<dyn FileReader>) -> Result<Self, Error> {
+        let mut header = [0u8; HEADER_SIZE as usize];
+        reader.read_exact(&mut header)?;
+        if &header[0..8] != PACK_MAGIC {
+            return Err(pack_error("not a shoyu pack archive"));
+        }
+
+        let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if version != PACK_VERSION {
+            return Err(pack_error(format!("unsupported version {}", version)));
+        }
+
+        let count = u32::from_le_bytes(header[12..16].try_into().unwrap());
+        let index_offset = u64::from_le_bytes(header[16..24].try_into().unwrap());
+        // Offsets and sizes come from the file, so they're checked against its real length
+        // before anything is allocated or read with them.
+        let len = reader.seek(SeekFrom::End(0))?;
+        if index_offset < HEADER_SIZE || index_offset > len {
+            return Err(pack_error("index is outside the archive"));
+        }
+        reader.seek(SeekFrom::Start(index_offset))?;
+
+        let mut index = Vec::new();
+        reader.read_to_end(&mut index)?;
+        let mut cursor = Cursor::new(index);
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            let (path, entry) = read_index_entry(&mut cursor)?;
+            let end = entry.offset.checked_add(entry.stored_size);
+            if entry.offset < HEADER_SIZE || end.is_none_or(|end| end > index_offset) {
+                return Err(pack_error(format!("entry {} reaches past its data", path)));
+            }
+            entries.insert(path, entry);
+        }
+
+        Ok(Self {
+            reader: Mutex::new(reader),
+            entries,
+        })
+    }
+
+    /// Every file in the archive, sorted.
+    pub fn paths(&self) -> Vec<&str> {
+        let mut paths: Vec<&str> = self.entries.keys().map(|p| p.as_str()).collect();
+        paths.sort();
+        paths
+    }
+
+    pub fn entry(&self, path: &str) -> Option<&PackEntry> {
+        self.entries.get(&normalize_path(path))
+    }
+}
+
+fn read_index_entry(cursor: &mut Cursor<Vec<u8>>) -> Result<(String, PackEntry), Error> {
+    let mut u16_buf = [0u8; 2];
+    let mut u64_buf = [0u8; 8];
+    let mut read_u64 = |cursor: &mut Cursor<Vec<u8>>| -> Result<u64, Error> {
+        cursor.read_exact(&mut u64_buf)?;
+        Ok(u64::from_le_bytes(u64_buf))
+    };
+
+    cursor.read_exact(&mut u16_buf)?;
+    let mut path = vec![0u8; u16::from_le_bytes(u16_buf) as usize];
+    cursor.read_exact(&mut path)?;
+    let path = String::from_utf8(path)?;
+
+    let offset = read_u64(cursor)?;
+    let stored_size = read_u64(cursor)?;
+    let size = read_u64(cursor)?;
+
+    let mut tail = [0u8; 5];
+    cursor.read_exact(&mut tail)?;
+    let compression = match tail[0] {
+        0 => PackCompression::None,
+        1 => PackCompression::Zlib,
+        c => {
+            return Err(pack_error(format!(
+                "entry {} has unknown compression {}",
+                path, c
+            )))
+        }
+    };
+
+    Ok((
+        path,
+        PackEntry {
+            offset,
+            stored_size,
+            size,
+            compression,
+            crc32: u32::from_le_bytes(tail[1..5].try_into().unwrap()),
+        },
+    ))
+}
+
+impl FileSystem for PackFileSystem {
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        let entry = match self.entries.get(&normalize_path(path)) {
+            Some(e) => e,
+            None => {
+                let e = std::io::Error::new(std::io::ErrorKind::NotFound, "not in the archive");
+                return Err(Error::from(e).with_path(path));
+            }
+        };
+
+        let mut stored = vec![0u8; entry.stored_size as usize];
+        {
+            let mut reader = self.reader.lock().unwrap();
+            reader.seek(SeekFrom::Start(entry.offset)).with_path(path)?;
+            reader.read_exact(&mut stored).with_path(path)?;
+        }
+
+        let data = match entry.compression {
+            PackCompression::None => stored,
+            // Never inflates more than one byte past the size the index claims, which is
+            // enough to tell the entry is damaged.
+            PackCompression::Zlib => {
+                let mut data = Vec::new();
+                ZlibDecoder::new(stored.as_slice())
+                    .take(entry.size.saturating_add(1))
+                    .read_to_end(&mut data)
+                    .with_path(path)?;
+                data
+            }
+        };
+
+        if data.len() as u64 != entry.size || crc32(&data) != entry.crc32 {
+            return Err(
+                pack_error("checksum doesn't match, the archive is damaged").with_path(path)
+            );
+        }
+
+        Ok(data)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.entries.contains_key(&normalize_path(path))
+    }
+}
+
+/// How big an archive came out.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PackStats {
+    pub files: usize,
+    pub size: u64,
+    pub packed_size: u64,
+}
+
+/// Collects files and writes them out as one archive.
+pub struct PackBuilder {
+    files: Vec<(String, Vec<u8>)>,
+    compress: bool,
+}
+
+impl PackBuilder {
+    /// With `compress` set, files are stored zlib compressed whenever that makes them
+    /// smaller. Already compressed formats like PNG and Ogg usually stay as they are.
+    pub fn new(compress: bool) -> Self {
+        Self {
+            files: Vec::new(),
+            compress,
+        }
+    }
+
+    pub fn add(&mut self, path: &str, data: Vec<u8>) {
+        let path = normalize_path(path);
+        self.files.retain(|(p, _)| *p != path);
+        self.files.push((path, data));
+    }
+
+    /// Adds every file under `dir`, named by their path relative to it.
+    pub fn add_directory(&mut self, dir: &Path) -> Result<(), Error> {
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(next) = pending.pop() {
+            let listing = std::fs::read_dir(&next).with_path(&next.to_string_lossy())?;
+            for item in listing {
+                let path = item.with_path(&next.to_string_lossy())?.path();
+                if path.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+
+                let name = path.strip_prefix(dir).unwrap_or(&path).to_string_lossy();
+                let data = std::fs::read(&path).with_path(&path.to_string_lossy())?;
+                self.add(&name, data);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn write(mut self, out: &mut impl Write) -> Result<PackStats, Error> {
+        // Sorted so packing the same files twice gives the same archive.
+        self.files.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut stats = PackStats {
+            files: self.files.len(),
+            ..Default::default()
+        };
+        let mut blobs = Vec::with_capacity(self.files.len());
+        let mut offset = HEADER_SIZE;
+        for (path, data) in &self.files {
+            if path.len() > u16::MAX as usize {
+                return Err(pack_error("path is too long").with_path(path));
+            }
+
+            let (compression, stored) = match self.compress {
+                true => {
+                    let mut zlib = ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+                    zlib.write_all(data)?;
+                    match zlib.finish()? {
+                        z if z.len() < data.len() => (PackCompression::Zlib, Some(z)),
+                        _ => (PackCompression::None, None),
+                    }
+                }
+                false => (PackCompression::None, None),
+            };
+
+            let stored_size = stored.as_ref().map_or(data.len(), |s| s.len()) as u64;
+            let entry = PackEntry {
+                offset,
+                stored_size,
+                size: data.len() as u64,
+                compression,
+                crc32: crc32(data),
+            };
+            offset += stored_size;
+            stats.size += entry.size;
+            blobs.push((entry, stored));
+        }
+
+        out.write_all(PACK_MAGIC)?;
+        out.write_all(&PACK_VERSION.to_le_bytes())?;
+        out.write_all(&(self.files.len() as u32).to_le_bytes())?;
+        out.write_all(&offset.to_le_bytes())?;
+        for ((_, data), (_, stored)) in self.files.iter().zip(&blobs) {
+            out.write_all(stored.as_deref().unwrap_or(data))?;
+        }
+
+        for ((path, _), (entry, _)) in self.files.iter().zip(&blobs) {
+            out.write_all(&(path.len() as u16).to_le_bytes())?;
+            out.write_all(path.as_bytes())?;
+            out.write_all(&entry.offset.to_le_bytes())?;
+            out.write_all(&entry.stored_size.to_le_bytes())?;
+            out.write_all(&entry.size.to_le_bytes())?;
+            out.write_all(&[(entry.compression == PackCompression::Zlib) as u8])?;
+            out.write_all(&entry.crc32.to_le_bytes())?;
+            offset += 2 + path.len() as u64 + 8 * 3 + 1 + 4;
+        }
+
+        stats.packed_size = offset;
+        Ok(stats)
+    }
+}
+
+/// Packs a database directory into one archive file that `Database::new` can open. The
+/// output file is left out if it's inside the directory.
+pub fn pack_directory(dir: &str, out_path: &str, compress: bool) -> Result<PackStats, Error> {
+    if !Path::new(dir).join("shoyu.json").is_file() {
+        return Err(Error::loading("no shoyu.json, not a database directory").with_path(dir));
+    }
+
+    let mut builder = PackBuilder::new(compress);
+    builder.add_directory(Path::new(dir))?;
+    // Skip the archive itself when it's written inside the directory it packs.
+    if let Ok(out) = std::fs::canonicalize(out_path) {
+        let dir = Path::new(dir);
+        builder
+            .files
+            .retain(|(p, _)| std::fs::canonicalize(dir.join(p)).ok().as_ref() != Some(&out));
+    }
+
+    let mut file = std::io::BufWriter::new(std::fs::File::create(out_path).with_path(out_path)?);
+    let stats = builder.write(&mut file).with_path(out_path)?;
+    file.flush().with_path(out_path)?;
+    Ok(stats)
+}
+
+#[test]
+fn test_pack() {
+    let text = "hello ".repeat(100);
+    let mut builder = PackBuilder::new(true);
+    builder.add("shoyu.json", b"{}".to_vec());
+    builder.add("./maps/../text.txt", text.as_bytes().to_vec());
+    builder.add("raw.bin", vec![1, 2, 3]);
+
+    let mut archive = Vec::new();
+    let stats = builder.write(&mut archive).unwrap();
+    assert_eq!(stats.files, 3);
+    assert!(stats.packed_size < stats.size);
+
+    let pack = PackFileSystem::from_bytes(archive.clone()).unwrap();
+    assert_eq!(pack.paths(), vec!["raw.bin", "shoyu.json", "text.txt"]);
+    assert_eq!(pack.read_to_string("text.txt").unwrap(), text);
+    assert_eq!(pack.read("raw.bin").unwrap(), vec![1, 2, 3]);
+    assert_eq!(
+        pack.entry("text.txt").unwrap().compression,
+        PackCompression::Zlib
+    );
+    assert_eq!(
+        pack.entry("raw.bin").unwrap().compression,
+        PackCompression::None
+    );
+    assert!(pack.read("missing").is_err());
+
+    // Flip a byte of the uncompressed entry's data.
+    let raw = pack.entry("raw.bin").unwrap().offset as usize;
+    let mut damaged = archive.clone();
+    damaged[raw] ^= 0xFF;
+    let pack = PackFileSystem::from_bytes(damaged).unwrap();
+    assert!(pack
+        .read("raw.bin")
+        .unwrap_err()
+        .to_string()
+        .contains("checksum"));
+    assert!(pack.read("shoyu.json").is_ok());
+
+    assert!(PackFileSystem::from_bytes(b"not an archive at all!!!".to_vec()).is_err());
+
+    // Offsets out of range are errors, not overflows or huge allocations.
+    let mut far_index = archive.clone();
+    far_index[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(PackFileSystem::from_bytes(far_index).is_err());
+    let name = archive.windows(7).rposition(|w| w == b"raw.bin").unwrap() + 7;
+    for (field, value) in [(0, u64::MAX - 1), (8, u64::MAX), (8, 1 << 40)] {
+        let mut bad_entry = archive.clone();
+        bad_entry[name + field..name + field + 8].copy_from_slice(&value.to_le_bytes());
+        assert!(PackFileSystem::from_bytes(bad_entry).is_err());
+    }
+
+    // A whole database folder, opened through `Database::new`.
+    let dir = std::env::temp_dir().join("shoyu_test_pack");
+    std::fs::create_dir_all(dir.join("img")).unwrap();
+    std::fs::write(dir.join("img/a.png"), super::load_funcs::test_png(4, 4)).unwrap();
+    std::fs::write(dir.join("shoyu.json"), r#"{"sprite_cfg": "sprites.json"}"#).unwrap();
+    std::fs::write(
+        dir.join("sprites.json"),
+        r#"{"sprites": [{"name": "a", "image_path": "img/a.png"}]}"#,
+    )
+    .unwrap();
+
+    let out = dir.join("game.pak");
+    let out = out.to_str().unwrap();
+    let _ = std::fs::remove_file(out);
+    pack_directory(dir.to_str().unwrap(), out, true).unwrap();
+    // Packing again must not pick up the archive from the first run.
+    let stats = pack_directory(dir.to_str().unwrap(), out, true).unwrap();
+    assert_eq!(stats.files, 3);
+
+    let mut db = super::Database::new(out).unwrap();
+    assert!(db.fetch_sprite("a").is_ok());
+}
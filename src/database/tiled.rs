@@ -0,0 +1,1291 @@
+use super::atlas::relative_image_path;
+use super::bmfont::parse_attributes;
+use super::error::*;
+use super::json::*;
+use super::vfs::FileSystem;
+use dashi::Rect2D;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::str::FromStr;
+
+/// Tile ids in layers and tile objects carry these flags in their top bits.
+pub const TILE_FLIP_HORIZONTAL: u32 = 0x8000_0000;
+pub const TILE_FLIP_VERTICAL: u32 = 0x4000_0000;
+pub const TILE_FLIP_DIAGONAL: u32 = 0x2000_0000;
+// Includes the bit hexagonal maps use for 120 degree rotation.
+const TILE_FLAGS: u32 = 0xF000_0000;
+
+/// A global tile id with its flip flags cleared.
+pub fn tile_gid(tile: u32) -> u32 {
+    tile & !TILE_FLAGS
+}
+
+/// A custom property. Colors and file paths come through as strings, object references as
+/// the object's id.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TiledProperty {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+pub type TiledProperties = HashMap<String, TiledProperty>;
+
+#[derive(Clone, Debug)]
+pub struct TiledTileset {
+    pub name: String,
+    pub first_gid: u32,
+    /// Sprite sheet entry made from the tileset's image. Its sprite ids are the tile ids
+    /// within the tileset.
+    pub sheet: String,
+    pub tile_size: [u32; 2],
+    pub tile_count: u32,
+    pub columns: u32,
+    pub properties: TiledProperties,
+    pub tile_properties: HashMap<u32, TiledProperties>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TiledTileLayer {
+    pub name: String,
+    /// In tiles.
+    pub size: [u32; 2],
+    /// In pixels, including the offsets of any groups the layer was in.
+    pub offset: [f32; 2],
+    pub opacity: f32,
+    pub visible: bool,
+    /// Global tile ids row by row, flip flags included. 0 is an empty cell.
+    pub tiles: Vec<u32>,
+    pub properties: TiledProperties,
+}
+
+impl TiledTileLayer {
+    pub fn tile(&self, x: u32, y: u32) -> u32 {
+        if x >= self.size[0] || y >= self.size[1] {
+            return 0;
+        }
+
+        self.tiles[(y * self.size[0] + x) as usize]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TiledShape {
+    Rectangle,
+    Ellipse,
+    Point,
+    /// Points relative to the object's position.
+    Polygon(Vec<[f32; 2]>),
+    Polyline(Vec<[f32; 2]>),
+    /// A tile drawn as an object. Its position is the tile's bottom left corner.
+    Tile(u32),
+    Text(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct TiledObject {
+    pub id: u32,
+    pub name: String,
+    /// The object's class, called its type before Tiled 1.9.
+    pub kind: String,
+    /// In pixels, without the layer offset.
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    /// Degrees clockwise.
+    pub rotation: f32,
+    pub visible: bool,
+    pub shape: TiledShape,
+    pub properties: TiledProperties,
+}
+
+#[derive(Clone, Debug)]
+pub struct TiledObjectLayer {
+    pub name: String,
+    pub offset: [f32; 2],
+    pub visible: bool,
+    pub objects: Vec<TiledObject>,
+    pub properties: TiledProperties,
+}
+
+#[derive(Clone, Debug)]
+pub enum TiledLayer {
+    Tiles(TiledTileLayer),
+    Objects(TiledObjectLayer),
+}
+
+impl TiledLayer {
+    pub fn name(&self) -> &str {
+        match self {
+            TiledLayer::Tiles(l) => &l.name,
+            TiledLayer::Objects(l) => &l.name,
+        }
+    }
+
+    pub fn visible(&self) -> bool {
+        match self {
+            TiledLayer::Tiles(l) => l.visible,
+            TiledLayer::Objects(l) => l.visible,
+        }
+    }
+}
+
+/// An orthogonal Tiled map.
+#[derive(Clone, Debug)]
+pub struct TiledMap {
+    /// In tiles.
+    pub size: [u32; 2],
+    pub tile_size: [u32; 2],
+    /// Tile and object layers in drawing order. Groups are flattened into their layers and
+    /// image layers are left out.
+    pub layers: Vec<TiledLayer>,
+    /// Sorted by `first_gid`.
+    pub tilesets: Vec<TiledTileset>,
+    pub properties: TiledProperties,
+}
+
+impl TiledMap {
+    pub fn tile_layers(&self) -> impl Iterator<Item = &TiledTileLayer> {
+        self.layers.iter().filter_map(|l| match l {
+            TiledLayer::Tiles(t) => Some(t),
+            _ => None,
+        })
+    }
+
+    pub fn object_layers(&self) -> impl Iterator<Item = &TiledObjectLayer> {
+        self.layers.iter().filter_map(|l| match l {
+            TiledLayer::Objects(o) => Some(o),
+            _ => None,
+        })
+    }
+
+    pub fn layer(&self, name: &str) -> Option<&TiledLayer> {
+        self.layers.iter().find(|l| l.name() == name)
+    }
+
+    /// Every object on every object layer.
+    pub fn objects(&self) -> impl Iterator<Item = &TiledObject> {
+        self.object_layers().flat_map(|l| l.objects.iter())
+    }
+
+    pub fn object(&self, name: &str) -> Option<&TiledObject> {
+        self.objects().find(|o| o.name == name)
+    }
+
+    pub fn objects_of_kind<'a>(&'a self, kind: &'a str) -> impl Iterator<Item = &'a TiledObject> {
+        self.objects().filter(move |o| o.kind == kind)
+    }
+
+    /// Index of the tileset a tile comes from, and the tile's id within it.
+    pub fn tileset_of(&self, tile: u32) -> Option<(usize, u32)> {
+        let gid = tile_gid(tile);
+        let (i, set) = self
+            .tilesets
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, t)| t.first_gid <= gid)?;
+
+        let local = gid - set.first_gid;
+        (gid != 0 && local < set.tile_count).then_some((i, local))
+    }
+
+    pub fn tile_properties(&self, tile: u32) -> Option<&TiledProperties> {
+        let (set, local) = self.tileset_of(tile)?;
+        self.tilesets[set].tile_properties.get(&local)
+    }
+}
+
+/// Everything a Tiled map adds to the database.
+pub struct TiledImport {
+    pub map: TiledMap,
+    /// One per tileset. Embedded tilesets are named `{map}/{tileset}`, external ones by their
+    /// path so maps sharing a tileset share the sheet.
+    pub sheets: Vec<SpriteSheetJSONEntry>,
+    /// Every file that was read, relative to the database root.
+    pub files: Vec<String>,
+}
+
+// The parts of a tileset both file formats agree on.
+#[derive(Default)]
+struct TilesetDef {
+    name: String,
+    image: Option<String>,
+    image_size: [u32; 2],
+    tile_size: [u32; 2],
+    margin: u32,
+    spacing: u32,
+    tile_count: Option<u32>,
+    columns: Option<u32>,
+    properties: TiledProperties,
+    tile_properties: HashMap<u32, TiledProperties>,
+}
+
+impl TilesetDef {
+    // `file` is what the image path is relative to.
+    fn finish(
+        self,
+        first_gid: u32,
+        sheet: String,
+        file: &str,
+    ) -> Result<(TiledTileset, SpriteSheetJSONEntry), Error> {
+        let image = match self.image {
+            Some(i) => i,
+            None => {
+                let msg = format!(
+                    "tileset {} has an image per tile, which isn't supported",
+                    self.name
+                );
+                return Err(Error::loading(msg));
+            }
+        };
+
+        let [w, h] = self.tile_size;
+        // Tiled always writes both, but older files may not.
+        let fit = |image: u32, tile: u32| {
+            (image + self.spacing).saturating_sub(2 * self.margin) / (tile + self.spacing).max(1)
+        };
+        let columns = self.columns.unwrap_or(fit(self.image_size[0], w));
+        let tile_count = self
+            .tile_count
+            .unwrap_or(columns * fit(self.image_size[1], h));
+
+        let entry = SpriteSheetJSONEntry {
+            name: sheet.clone(),
+            image_path: relative_image_path(file, &image),
+            sprites: None,
+            auto_gen: Some(SpriteSheetJSONAutoGen {
+                name: sheet.clone(),
+                bounds: Rect2D {
+                    x: self.margin,
+                    y: self.margin,
+                    w,
+                    h,
+                },
+                stride: columns,
+                rows: None,
+                count: Some(tile_count),
+                padding: Some(self.spacing),
+                first_id: Some(0),
+            }),
+            animations: None,
+            texture: None,
+        };
+
+        let tileset = TiledTileset {
+            name: self.name,
+            first_gid,
+            sheet,
+            tile_size: self.tile_size,
+            tile_count,
+            columns,
+            properties: self.properties,
+            tile_properties: self.tile_properties,
+        };
+
+        Ok((tileset, entry))
+    }
+}
+
+// Collects layers, tilesets and files as either format is walked.
+struct Builder<'a> {
+    name: &'a str,
+    fs: &'a dyn FileSystem,
+    map_path: &'a str,
+    layers: Vec<TiledLayer>,
+    tilesets: Vec<TiledTileset>,
+    sheets: Vec<SpriteSheetJSONEntry>,
+    files: Vec<String>,
+}
+
+impl<'a> Builder<'a> {
+    fn read(&mut self, path: &str) -> Result<String, Error> {
+        let text = self.fs.read_to_string(path)?;
+        self.files.push(path.to_string());
+        Ok(text)
+    }
+
+    fn add_tileset(
+        &mut self,
+        def: TilesetDef,
+        first_gid: u32,
+        file: Option<&str>,
+    ) -> Result<(), Error> {
+        let sheet = match file {
+            Some(f) => f.to_string(),
+            None => format!("{}/{}", self.name, def.name),
+        };
+
+        let (tileset, entry) = def.finish(first_gid, sheet, file.unwrap_or(self.map_path))?;
+        self.tilesets.push(tileset);
+        self.sheets.push(entry);
+        Ok(())
+    }
+
+    // External tilesets can be TSX or JSON no matter which the map is.
+    fn external_tileset(&mut self, source: &str, first_gid: u32) -> Result<(), Error> {
+        let path = relative_image_path(self.map_path, source);
+        let text = self.read(&path)?;
+        let def = match is_xml(&text) {
+            true => parse_xml(&text).and_then(|xml| tileset_from_xml(&xml)),
+            false => {
+                let json: Result<JsonTileset, _> = serde_json::from_str(&text);
+                json.map(tileset_from_json).map_err(Error::from)
+            }
+        }
+        .with_path(&path)?;
+
+        self.add_tileset(def, first_gid, Some(&path))
+    }
+
+    fn finish(
+        mut self,
+        size: [u32; 2],
+        tile_size: [u32; 2],
+        properties: TiledProperties,
+    ) -> TiledImport {
+        self.tilesets.sort_by_key(|t| t.first_gid);
+        TiledImport {
+            map: TiledMap {
+                size,
+                tile_size,
+                layers: self.layers,
+                tilesets: self.tilesets,
+                properties,
+            },
+            sheets: self.sheets,
+            files: self.files,
+        }
+    }
+}
+
+fn is_xml(text: &str) -> bool {
+    text.trim_start().starts_with('<')
+}
+
+fn check_map(orientation: &str, infinite: bool) -> Result<(), Error> {
+    if orientation != "orthogonal" {
+        let msg = format!(
+            "{} maps aren't supported, only orthogonal ones",
+            orientation
+        );
+        return Err(Error::loading(msg));
+    }
+
+    if infinite {
+        return Err(Error::loading("infinite maps aren't supported"));
+    }
+
+    Ok(())
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ => return Err(Error::loading("layer data isn't valid base64")),
+        };
+
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+
+    Ok(out)
+}
+
+// Tile data as written by Tiled: CSV text, or base64 that may also be compressed.
+fn decode_tiles(data: &str, encoding: &str, compression: &str) -> Result<Vec<u32>, Error> {
+    if encoding != "base64" {
+        return data
+            .split(',')
+            .map(|t| t.trim())
+            .filter(|t| !t.is_empty())
+            .map(|t| {
+                t.parse()
+                    .map_err(|_| Error::loading(format!("bad tile id '{}'", t)))
+            })
+            .collect();
+    }
+
+    let raw = decode_base64(data)?;
+    let mut bytes = Vec::new();
+    match compression {
+        "" => bytes = raw,
+        "zlib" => {
+            ZlibDecoder::new(&raw[..]).read_to_end(&mut bytes)?;
+        }
+        "gzip" => {
+            GzDecoder::new(&raw[..]).read_to_end(&mut bytes)?;
+        }
+        other => {
+            let msg = format!("{} compressed layers aren't supported", other);
+            return Err(Error::loading(msg));
+        }
+    }
+
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+fn check_tiles(name: &str, size: [u32; 2], tiles: &[u32]) -> Result<(), Error> {
+    let expected = size[0] as usize * size[1] as usize;
+    if tiles.len() != expected {
+        let msg = format!(
+            "layer {} has {} tiles, expected {}",
+            name,
+            tiles.len(),
+            expected
+        );
+        return Err(Error::loading(msg));
+    }
+
+    Ok(())
+}
+
+fn property(kind: &str, value: &str) -> TiledProperty {
+    let parsed = match kind {
+        "bool" => value.parse().ok().map(TiledProperty::Bool),
+        "int" | "object" => value.parse().ok().map(TiledProperty::Int),
+        "float" => value.parse().ok().map(TiledProperty::Float),
+        _ => None,
+    };
+
+    parsed.unwrap_or_else(|| TiledProperty::String(value.to_string()))
+}
+
+// Just enough XML for Tiled's files: elements, attributes and text.
+#[derive(Default)]
+struct XmlElement {
+    tag: String,
+    attrs: HashMap<String, String>,
+    children: Vec<XmlElement>,
+    text: String,
+}
+
+impl XmlElement {
+    fn attr(&self, key: &str) -> Option<&str> {
+        self.attrs.get(key).map(|a| a.as_str())
+    }
+
+    fn get<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.attr(key)?.trim().parse().ok()
+    }
+
+    fn child(&self, tag: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|c| c.tag == tag)
+    }
+
+    fn children<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a XmlElement> {
+        self.children.iter().filter(move |c| c.tag == tag)
+    }
+
+    fn properties(&self) -> TiledProperties {
+        let props = self.child("properties").into_iter();
+        props
+            .flat_map(|p| p.children("property"))
+            .filter_map(|p| {
+                // Multi-line strings are kept in the element's text instead.
+                let value = p.attr("value").unwrap_or(&p.text);
+                let kind = p.attr("type").unwrap_or("string");
+                Some((p.attr("name")?.to_string(), property(kind, value)))
+            })
+            .collect()
+    }
+}
+
+fn unescape(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let end = match rest.find(';') {
+            Some(e) => e,
+            None => break,
+        };
+
+        let entity = &rest[1..end];
+        let ch = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix("#x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity
+                    .strip_prefix('#')
+                    .and_then(|d| d.parse().ok())
+                    .and_then(char::from_u32),
+            },
+        };
+
+        match ch {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+fn parse_xml(text: &str) -> Result<XmlElement, Error> {
+    let mut stack = vec![XmlElement::default()];
+    let mut rest = text;
+    while let Some(open) = rest.find('<') {
+        stack
+            .last_mut()
+            .unwrap()
+            .text
+            .push_str(&unescape(&rest[..open]));
+        rest = &rest[open + 1..];
+
+        let skip = [("!--", "-->"), ("![CDATA[", "]]>")];
+        if let Some((start, end)) = skip.iter().find(|(s, _)| rest.starts_with(s)) {
+            let close = rest
+                .find(end)
+                .ok_or(Error::loading("unterminated XML section"))?;
+            if *start != "!--" {
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .text
+                    .push_str(&rest[start.len()..close]);
+            }
+            rest = &rest[close + end.len()..];
+            continue;
+        }
+
+        let close = rest
+            .find('>')
+            .ok_or(Error::loading("unterminated XML tag"))?;
+        let tag = &rest[..close];
+        rest = &rest[close + 1..];
+        if tag.starts_with(['?', '!']) {
+            continue;
+        }
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let done = stack.pop().unwrap();
+            if stack.is_empty() || done.tag != name.trim() {
+                return Err(Error::loading(format!("unexpected </{}>", name.trim())));
+            }
+            stack.last_mut().unwrap().children.push(done);
+            continue;
+        }
+
+        let (body, closed) = match tag.strip_suffix('/') {
+            Some(b) => (b, true),
+            None => (tag, false),
+        };
+        let (name, attrs) = body
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap_or((body.trim(), ""));
+        let element = XmlElement {
+            tag: name.to_string(),
+            attrs: parse_attributes(attrs)
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), unescape(v)))
+                .collect(),
+            ..Default::default()
+        };
+
+        match closed {
+            true => stack.last_mut().unwrap().children.push(element),
+            false => stack.push(element),
+        }
+    }
+
+    if stack.len() != 1 {
+        return Err(Error::loading(format!(
+            "<{}> is never closed",
+            stack.last().unwrap().tag
+        )));
+    }
+
+    let root = stack.pop().unwrap().children.into_iter().next();
+    root.ok_or(Error::loading("no XML elements"))
+}
+
+fn tileset_from_xml(xml: &XmlElement) -> Result<TilesetDef, Error> {
+    let image = xml.child("image");
+    Ok(TilesetDef {
+        name: xml.attr("name").unwrap_or_default().to_string(),
+        image: image.and_then(|i| i.attr("source")).map(|s| s.to_string()),
+        image_size: [
+            image.and_then(|i| i.get("width")).unwrap_or(0),
+            image.and_then(|i| i.get("height")).unwrap_or(0),
+        ],
+        tile_size: [
+            xml.get("tilewidth")
+                .ok_or(Error::loading("tileset has no tilewidth"))?,
+            xml.get("tileheight")
+                .ok_or(Error::loading("tileset has no tileheight"))?,
+        ],
+        margin: xml.get("margin").unwrap_or(0),
+        spacing: xml.get("spacing").unwrap_or(0),
+        tile_count: xml.get("tilecount"),
+        columns: xml.get("columns"),
+        properties: xml.properties(),
+        tile_properties: xml
+            .children("tile")
+            .filter_map(|t| Some((t.get("id")?, t.properties())))
+            .filter(|(_, p): &(u32, TiledProperties)| !p.is_empty())
+            .collect(),
+    })
+}
+
+fn xml_points(points: &str) -> Vec<[f32; 2]> {
+    points
+        .split_whitespace()
+        .filter_map(|p| {
+            let (x, y) = p.split_once(',')?;
+            Some([x.parse().ok()?, y.parse().ok()?])
+        })
+        .collect()
+}
+
+fn object_from_xml(xml: &XmlElement) -> TiledObject {
+    let shape = if let Some(gid) = xml.get("gid") {
+        TiledShape::Tile(gid)
+    } else if xml.child("point").is_some() {
+        TiledShape::Point
+    } else if xml.child("ellipse").is_some() {
+        TiledShape::Ellipse
+    } else if let Some(p) = xml.child("polygon") {
+        TiledShape::Polygon(xml_points(p.attr("points").unwrap_or_default()))
+    } else if let Some(p) = xml.child("polyline") {
+        TiledShape::Polyline(xml_points(p.attr("points").unwrap_or_default()))
+    } else if let Some(t) = xml.child("text") {
+        TiledShape::Text(t.text.clone())
+    } else {
+        TiledShape::Rectangle
+    };
+
+    TiledObject {
+        id: xml.get("id").unwrap_or(0),
+        name: xml.attr("name").unwrap_or_default().to_string(),
+        kind: xml
+            .attr("class")
+            .or(xml.attr("type"))
+            .unwrap_or_default()
+            .to_string(),
+        position: [xml.get("x").unwrap_or(0.0), xml.get("y").unwrap_or(0.0)],
+        size: [
+            xml.get("width").unwrap_or(0.0),
+            xml.get("height").unwrap_or(0.0),
+        ],
+        rotation: xml.get("rotation").unwrap_or(0.0),
+        visible: xml.get::<u32>("visible") != Some(0),
+        shape,
+        properties: xml.properties(),
+    }
+}
+
+// Groups pass their offset, opacity and visibility down to the layers inside them.
+#[derive(Clone, Copy)]
+struct Inherited {
+    offset: [f32; 2],
+    opacity: f32,
+    visible: bool,
+}
+
+impl Inherited {
+    fn apply(&self, offset: [f32; 2], opacity: f32, visible: bool) -> Self {
+        Self {
+            offset: [self.offset[0] + offset[0], self.offset[1] + offset[1]],
+            opacity: self.opacity * opacity,
+            visible: self.visible && visible,
+        }
+    }
+}
+
+const ROOT: Inherited = Inherited {
+    offset: [0.0, 0.0],
+    opacity: 1.0,
+    visible: true,
+};
+
+fn layers_from_xml(
+    builder: &mut Builder,
+    xml: &XmlElement,
+    parent: Inherited,
+) -> Result<(), Error> {
+    for child in &xml.children {
+        let own = parent.apply(
+            [
+                child.get("offsetx").unwrap_or(0.0),
+                child.get("offsety").unwrap_or(0.0),
+            ],
+            child.get("opacity").unwrap_or(1.0),
+            child.get::<u32>("visible") != Some(0),
+        );
+        let name = child.attr("name").unwrap_or_default().to_string();
+
+        match child.tag.as_str() {
+            "layer" => {
+                let size = [
+                    child.get("width").unwrap_or(0),
+                    child.get("height").unwrap_or(0),
+                ];
+                let data = child
+                    .child("data")
+                    .ok_or(Error::loading(format!("layer {} has no data", name)))?;
+                let tiles = match data.attr("encoding") {
+                    Some(encoding) => decode_tiles(
+                        &data.text,
+                        encoding,
+                        data.attr("compression").unwrap_or_default(),
+                    )?,
+                    // The oldest format, one element per tile.
+                    None => data
+                        .children("tile")
+                        .map(|t| t.get("gid").unwrap_or(0))
+                        .collect(),
+                };
+                check_tiles(&name, size, &tiles)?;
+
+                builder.layers.push(TiledLayer::Tiles(TiledTileLayer {
+                    name,
+                    size,
+                    offset: own.offset,
+                    opacity: own.opacity,
+                    visible: own.visible,
+                    tiles,
+                    properties: child.properties(),
+                }));
+            }
+            "objectgroup" => builder.layers.push(TiledLayer::Objects(TiledObjectLayer {
+                name,
+                offset: own.offset,
+                visible: own.visible,
+                objects: child.children("object").map(object_from_xml).collect(),
+                properties: child.properties(),
+            })),
+            "group" => layers_from_xml(builder, child, own)?,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn map_from_xml(mut builder: Builder, xml: &XmlElement) -> Result<TiledImport, Error> {
+    check_map(
+        xml.attr("orientation").unwrap_or("orthogonal"),
+        xml.get::<u32>("infinite") == Some(1),
+    )?;
+
+    for tileset in xml.children("tileset") {
+        let first_gid = tileset.get("firstgid").unwrap_or(1);
+        match tileset.attr("source") {
+            Some(source) => builder.external_tileset(source, first_gid)?,
+            None => builder.add_tileset(tileset_from_xml(tileset)?, first_gid, None)?,
+        }
+    }
+
+    layers_from_xml(&mut builder, xml, ROOT)?;
+    let size = [
+        xml.get("width").unwrap_or(0),
+        xml.get("height").unwrap_or(0),
+    ];
+    let tile_size = [
+        xml.get("tilewidth").unwrap_or(0),
+        xml.get("tileheight").unwrap_or(0),
+    ];
+    Ok(builder.finish(size, tile_size, xml.properties()))
+}
+
+fn yes() -> bool {
+    true
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize)]
+struct JsonProperty {
+    name: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    value: serde_json::Value,
+}
+
+fn json_properties(props: Vec<JsonProperty>) -> TiledProperties {
+    props
+        .into_iter()
+        .map(|p| {
+            let value = match p.value {
+                serde_json::Value::String(s) => property(&p.kind, &s),
+                other => property(&p.kind, &other.to_string()),
+            };
+            (p.name, value)
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct JsonTile {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+#[derive(Deserialize)]
+struct JsonTileset {
+    firstgid: Option<u32>,
+    source: Option<String>,
+    #[serde(default)]
+    name: String,
+    image: Option<String>,
+    #[serde(default)]
+    imagewidth: u32,
+    #[serde(default)]
+    imageheight: u32,
+    #[serde(default)]
+    tilewidth: u32,
+    #[serde(default)]
+    tileheight: u32,
+    #[serde(default)]
+    margin: u32,
+    #[serde(default)]
+    spacing: u32,
+    tilecount: Option<u32>,
+    columns: Option<u32>,
+    #[serde(default)]
+    tiles: Vec<JsonTile>,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+fn tileset_from_json(json: JsonTileset) -> TilesetDef {
+    TilesetDef {
+        name: json.name,
+        image: json.image,
+        image_size: [json.imagewidth, json.imageheight],
+        tile_size: [json.tilewidth, json.tileheight],
+        margin: json.margin,
+        spacing: json.spacing,
+        tile_count: json.tilecount,
+        columns: json.columns,
+        properties: json_properties(json.properties),
+        tile_properties: json
+            .tiles
+            .into_iter()
+            .filter(|t| !t.properties.is_empty())
+            .map(|t| (t.id, json_properties(t.properties)))
+            .collect(),
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonPoint {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Deserialize)]
+struct JsonText {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct JsonObject {
+    #[serde(default)]
+    id: u32,
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    class: String,
+    #[serde(default)]
+    x: f32,
+    #[serde(default)]
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    rotation: f32,
+    #[serde(default = "yes")]
+    visible: bool,
+    gid: Option<u32>,
+    #[serde(default)]
+    point: bool,
+    #[serde(default)]
+    ellipse: bool,
+    polygon: Option<Vec<JsonPoint>>,
+    polyline: Option<Vec<JsonPoint>>,
+    text: Option<JsonText>,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+fn object_from_json(json: JsonObject) -> TiledObject {
+    let points = |p: Vec<JsonPoint>| p.into_iter().map(|p| [p.x, p.y]).collect();
+    let shape = if let Some(gid) = json.gid {
+        TiledShape::Tile(gid)
+    } else if json.point {
+        TiledShape::Point
+    } else if json.ellipse {
+        TiledShape::Ellipse
+    } else if let Some(p) = json.polygon {
+        TiledShape::Polygon(points(p))
+    } else if let Some(p) = json.polyline {
+        TiledShape::Polyline(points(p))
+    } else if let Some(t) = json.text {
+        TiledShape::Text(t.text)
+    } else {
+        TiledShape::Rectangle
+    };
+
+    TiledObject {
+        id: json.id,
+        name: json.name,
+        kind: match json.class.is_empty() {
+            true => json.kind,
+            false => json.class,
+        },
+        position: [json.x, json.y],
+        size: [json.width, json.height],
+        rotation: json.rotation,
+        visible: json.visible,
+        shape,
+        properties: json_properties(json.properties),
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    data: Option<serde_json::Value>,
+    #[serde(default)]
+    encoding: String,
+    #[serde(default)]
+    compression: String,
+    #[serde(default)]
+    offsetx: f32,
+    #[serde(default)]
+    offsety: f32,
+    #[serde(default = "one")]
+    opacity: f32,
+    #[serde(default = "yes")]
+    visible: bool,
+    #[serde(default)]
+    objects: Vec<JsonObject>,
+    #[serde(default)]
+    layers: Vec<JsonLayer>,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+fn layers_from_json(
+    builder: &mut Builder,
+    layers: Vec<JsonLayer>,
+    parent: Inherited,
+) -> Result<(), Error> {
+    for layer in layers {
+        let own = parent.apply([layer.offsetx, layer.offsety], layer.opacity, layer.visible);
+        match layer.kind.as_str() {
+            "tilelayer" => {
+                let size = [layer.width, layer.height];
+                let tiles = match layer.data {
+                    Some(serde_json::Value::String(s)) => {
+                        decode_tiles(&s, &layer.encoding, &layer.compression)?
+                    }
+                    Some(serde_json::Value::Array(a)) => {
+                        a.iter().map(|t| t.as_u64().unwrap_or(0) as u32).collect()
+                    }
+                    _ => return Err(Error::loading(format!("layer {} has no data", layer.name))),
+                };
+                check_tiles(&layer.name, size, &tiles)?;
+
+                builder.layers.push(TiledLayer::Tiles(TiledTileLayer {
+                    name: layer.name,
+                    size,
+                    offset: own.offset,
+                    opacity: own.opacity,
+                    visible: own.visible,
+                    tiles,
+                    properties: json_properties(layer.properties),
+                }));
+            }
+            "objectgroup" => builder.layers.push(TiledLayer::Objects(TiledObjectLayer {
+                name: layer.name,
+                offset: own.offset,
+                visible: own.visible,
+                objects: layer.objects.into_iter().map(object_from_json).collect(),
+                properties: json_properties(layer.properties),
+            })),
+            "group" => layers_from_json(builder, layer.layers, own)?,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct JsonMap {
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    orientation: String,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    layers: Vec<JsonLayer>,
+    #[serde(default)]
+    tilesets: Vec<JsonTileset>,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+fn map_from_json(mut builder: Builder, json: JsonMap) -> Result<TiledImport, Error> {
+    check_map(&json.orientation, json.infinite)?;
+
+    for tileset in json.tilesets {
+        let first_gid = tileset.firstgid.unwrap_or(1);
+        match tileset.source.clone() {
+            Some(source) => builder.external_tileset(&source, first_gid)?,
+            None => builder.add_tileset(tileset_from_json(tileset), first_gid, None)?,
+        }
+    }
+
+    layers_from_json(&mut builder, json.layers, ROOT)?;
+    Ok(builder.finish(
+        [json.width, json.height],
+        [json.tilewidth, json.tileheight],
+        json_properties(json.properties),
+    ))
+}
+
+/// Loads a Tiled map saved as TMX or JSON, along with any external tilesets it uses.
+pub fn load_tiled_map(
+    fs: &dyn FileSystem,
+    name: &str,
+    map_path: &str,
+) -> Result<TiledImport, Error> {
+    let mut builder = Builder {
+        name,
+        fs,
+        map_path,
+        layers: Vec::new(),
+        tilesets: Vec::new(),
+        sheets: Vec::new(),
+        files: Vec::new(),
+    };
+
+    let text = builder.read(map_path)?;
+    match is_xml(&text) {
+        true => {
+            let xml = parse_xml(&text).with_path(map_path)?;
+            map_from_xml(builder, &xml)
+        }
+        false => {
+            let json: JsonMap = serde_json::from_str(&text).with_path(map_path)?;
+            map_from_json(builder, json)
+        }
+    }
+    .with_entry(name)
+}
+
+#[test]
+fn test_tiled_map() {
+    use flate2::write::ZlibEncoder;
+    use super::vfs::MemoryFileSystem;
+    use std::io::Write;
+
+    let fs = MemoryFileSystem::new();
+
+    // Two rows of tiles, zlib compressed and base64 encoded like Tiled writes them.
+    let ids: [u32; 6] = [1, 2, 0, 5 | TILE_FLIP_HORIZONTAL, 4, 3];
+    let mut zlib = ZlibEncoder::new(Vec::new(), Default::default());
+    zlib.write_all(
+        &ids.iter()
+            .flat_map(|i| i.to_le_bytes())
+            .collect::<Vec<u8>>(),
+    )
+    .unwrap();
+    let packed = zlib.finish().unwrap();
+    let table = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in packed.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(table[(n >> (18 - 6 * i) & 63) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+
+    fs.insert(
+        "maps/ground.tsx",
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" name="ground" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+ <image source="../ground.png" width="32" height="32"/>
+ <tile id="1"><properties><property name="solid" type="bool" value="true"/></properties></tile>
+</tileset>"#,
+    );
+
+    let tmx = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="16" infinite="0">
+ <properties>
+  <property name="music" value="cave &amp; wind"/>
+  <property name="gravity" type="float" value="9.5"/>
+ </properties>
+ <tileset firstgid="1" source="ground.tsx"/>
+ <tileset firstgid="5" name="props" tilewidth="16" tileheight="16" spacing="1" margin="1" tilecount="2" columns="2">
+  <image source="props.png" width="35" height="18"/>
+ </tileset>
+ <layer id="1" name="floor" width="3" height="2">
+  <data encoding="csv">
+1,2,0,
+0,4,3
+</data>
+ </layer>
+ <group name="upper" offsetx="4" visible="0">
+  <layer id="2" name="decor" width="3" height="2" offsetx="1">
+   <data encoding="base64" compression="zlib">{}</data>
+  </layer>
+ </group>
+ <objectgroup name="things">
+  <object id="1" name="spawn" type="player" x="8" y="24"><point/></object>
+  <object id="2" name="door" class="warp" x="32" y="0" width="16" height="16">
+   <properties><property name="to" value="level2"/></properties>
+  </object>
+  <object id="3" x="0" y="0"><polygon points="0,0 16,0 16,16"/></object>
+ </objectgroup>
+</map>"#,
+        encoded
+    );
+    fs.insert("maps/cave.tmx", tmx);
+
+    let import = load_tiled_map(&fs, "cave", "maps/cave.tmx").unwrap();
+    let map = &import.map;
+    assert_eq!((map.size, map.tile_size), ([3, 2], [16, 16]));
+    assert_eq!(
+        map.properties["music"],
+        TiledProperty::String("cave & wind".to_string())
+    );
+    assert_eq!(map.properties["gravity"], TiledProperty::Float(9.5));
+    assert_eq!(import.files, vec!["maps/cave.tmx", "maps/ground.tsx"]);
+
+    // Tilesets become auto-generated sheets, with ids local to the tileset.
+    assert_eq!(import.sheets.len(), 2);
+    assert_eq!(import.sheets[0].name, "maps/ground.tsx");
+    assert_eq!(import.sheets[0].image_path, "maps/../ground.png");
+    assert_eq!(import.sheets[1].name, "cave/props");
+    let props = import.sheets[1]
+        .auto_gen
+        .as_ref()
+        .unwrap()
+        .generate([35, 18]);
+    assert_eq!((props[1].bounds.x, props[1].bounds.y), (18, 1));
+    assert_eq!(map.tileset_of(5 | TILE_FLIP_HORIZONTAL), Some((1, 0)));
+    assert_eq!(map.tileset_of(0), None);
+    assert_eq!(map.tileset_of(7), None);
+    assert_eq!(
+        map.tile_properties(2).unwrap()["solid"],
+        TiledProperty::Bool(true)
+    );
+
+    let floor: Vec<_> = map.tile_layers().collect();
+    assert_eq!(floor[0].tiles, vec![1, 2, 0, 0, 4, 3]);
+    assert_eq!(floor[0].tile(2, 1), 3);
+    assert_eq!(floor[1].tiles, ids);
+    assert_eq!(floor[1].offset, [5.0, 0.0]);
+    assert!(!floor[1].visible);
+
+    assert_eq!(map.object("spawn").unwrap().shape, TiledShape::Point);
+    let door = map.objects_of_kind("warp").next().unwrap();
+    assert_eq!(
+        door.properties["to"],
+        TiledProperty::String("level2".to_string())
+    );
+    assert_eq!(door.size, [16.0, 16.0]);
+    assert_eq!(
+        map.objects().last().unwrap().shape,
+        TiledShape::Polygon(vec![[0.0, 0.0], [16.0, 0.0], [16.0, 16.0]])
+    );
+
+    // The same map saved as JSON.
+    fs.insert(
+        "maps/cave.tmj",
+        r#"{"width": 3, "height": 2, "tilewidth": 16, "tileheight": 16,
+            "orientation": "orthogonal", "infinite": false,
+            "tilesets": [{"firstgid": 1, "source": "ground.tsx"}],
+            "layers": [
+                {"type": "tilelayer", "name": "floor", "width": 3, "height": 2,
+                 "data": [1, 2, 0, 0, 4, 3]},
+                {"type": "objectgroup", "name": "things", "objects": [
+                    {"id": 1, "name": "spawn", "type": "player", "x": 8, "y": 24, "point": true,
+                     "properties": [{"name": "hp", "type": "int", "value": 3}]}
+                ]}
+            ]}"#,
+    );
+
+    let import = load_tiled_map(&fs, "cave", "maps/cave.tmj").unwrap();
+    let map = &import.map;
+    assert_eq!(
+        map.tile_layers().next().unwrap().tiles,
+        vec![1, 2, 0, 0, 4, 3]
+    );
+    let spawn = map.object("spawn").unwrap();
+    assert_eq!(spawn.kind, "player");
+    assert_eq!(spawn.properties["hp"], TiledProperty::Int(3));
+    assert_eq!(import.sheets[0].name, "maps/ground.tsx");
+
+    fs.insert(
+        "maps/iso.tmj",
+        r#"{"width": 1, "height": 1, "tilewidth": 16, "tileheight": 8, "orientation": "isometric"}"#,
+    );
+    let err = load_tiled_map(&fs, "iso", "maps/iso.tmj").err().unwrap();
+    assert!(err.to_string().contains("only orthogonal"));
+}
@@ -0,0 +1,397 @@
+use super::loader::run_job;
+use super::*;
+use dashi::Rect2D;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::Hash;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found while validating a database.
+#[derive(Serialize, Clone, Debug)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    /// Kind of thing the problem is with, like `sprite_sheet` or `canvas`.
+    pub category: String,
+    pub entry: String,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    pub fn error(category: &str, entry: &str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            category: category.to_string(),
+            entry: entry.to_string(),
+            message: message.into(),
+        }
+    }
+
+    pub fn warning(category: &str, entry: &str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(category, entry, message)
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+
+        write!(
+            f,
+            "{}: {} '{}': {}",
+            severity, self.category, self.entry, self.message
+        )
+    }
+}
+
+/// Values that show up more than once, each reported once, in order of first repeat.
+pub fn find_duplicates<T: Eq + Hash + Clone>(items: impl IntoIterator<Item = T>) -> Vec<T> {
+    let mut seen = HashSet::new();
+    let mut reported = HashSet::new();
+    items
+        .into_iter()
+        .filter(|i| !seen.insert(i.clone()) && reported.insert(i.clone()))
+        .collect()
+}
+
+/// Whether `rect` lies entirely inside an image of the given size.
+pub fn rect_in_image(rect: &Rect2D, size: [u32; 2]) -> bool {
+    rect.x as u64 + rect.w as u64 <= size[0] as u64
+        && rect.y as u64 + rect.h as u64 <= size[1] as u64
+}
+
+fn sorted<V>(map: &HashMap<String, V>) -> Vec<(&String, &V)> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+impl Database {
+    /// Decodes every entry and checks for the mistakes that otherwise only show up as panics
+    /// once a renderer touches them. Nothing is kept loaded.
+    pub fn validate(&mut self) -> Vec<ValidationIssue> {
+        let mut issues = self.duplicate_names();
+
+        for (name, entry) in sorted(&self.sprites) {
+            let path = &entry.cfg.image_path;
+            if let Err(e) = load_image_rgba8(self.fs.as_ref(), path) {
+                let msg = format!("image {} doesn't load: {}", path, e);
+                issues.push(ValidationIssue::error("sprite", name, msg));
+            }
+        }
+
+        for (name, entry) in sorted(&self.sprite_sheets) {
+            issues.extend(self.validate_sprite_sheet(name, entry));
+        }
+
+        let fonts: Vec<String> = sorted(&self.ttfs)
+            .into_iter()
+            .map(|(n, _)| n.clone())
+            .collect();
+        for name in fonts {
+            if let Err(e) = self
+                .font_job(&name)
+                .and_then(|j| run_job(self.fs.as_ref(), j))
+            {
+                let msg = format!("font doesn't load: {}", e);
+                issues.push(ValidationIssue::error("font", &name, msg));
+            }
+        }
+
+        for (name, entry) in sorted(&self.sounds) {
+            let path = &entry.cfg.path;
+            if let Err(e) = load_sound(self.fs.as_ref(), path) {
+                let msg = format!("sound {} doesn't load: {}", path, e);
+                issues.push(ValidationIssue::error("sound", name, msg));
+            }
+        }
+
+        for (name, map) in sorted(&self.maps) {
+            issues.extend(validate_tilemap(name, map));
+        }
+
+        issues.extend(validate_localization(&self.localization));
+
+        issues
+    }
+
+    // The maps can only hold one entry per name, so look at the config files themselves.
+    // Later mounts replacing entries of earlier ones is on purpose and isn't reported.
+    fn duplicate_names(&self) -> Vec<ValidationIssue> {
+        self.mounts
+            .iter()
+            .zip(&self.infos)
+            .flat_map(|(mount, info)| Self::duplicate_names_in(mount.fs.as_ref(), info))
+            .collect()
+    }
+
+    fn duplicate_names_in(fs: &dyn FileSystem, info: &DatabaseJSON) -> Vec<ValidationIssue> {
+
+        let mut sprites = Vec::new();
+        if let Some(Ok(json)) = info
+            .sprite_cfg
+            .as_ref()
+            .map(|p| Self::get_sprite_json(fs, p))
+        {
+            sprites.extend(json.sprites.into_iter().map(|s| s.name));
+        }
+
+        let mut sheets = Vec::new();
+        if let Some(Ok(json)) = info
+            .sprite_sheet_cfg
+            .as_ref()
+            .map(|p| Self::get_sprite_sheet_json(fs, p))
+        {
+            sheets.extend(json.sprite_sheets.into_iter().map(|s| s.name));
+        }
+        if let Some(Ok(json)) = info
+            .aseprite_cfg
+            .as_ref()
+            .map(|p| Self::get_aseprite_json(fs, p))
+        {
+            sheets.extend(json.sheets.into_iter().map(|s| s.name));
+        }
+        if let Some(Ok(json)) = info.atlas_cfg.as_ref().map(|p| Self::get_atlas_json(fs, p)) {
+            sheets.extend(json.atlases.into_iter().map(|s| s.name));
+        }
+
+        let mut fonts = Vec::new();
+        if let Some(Ok(json)) = info.ttf_cfg.as_ref().map(|p| Self::get_ttf_json(fs, p)) {
+            fonts.extend(json.fonts.into_iter().map(|s| s.name));
+        }
+        if let Some(Ok(json)) = info
+            .bmfont_cfg
+            .as_ref()
+            .map(|p| Self::get_bmfont_json(fs, p))
+        {
+            fonts.extend(json.fonts.into_iter().map(|s| s.name));
+        }
+
+        let mut sounds = Vec::new();
+        if let Some(Ok(json)) = info.audio_cfg.as_ref().map(|p| Self::get_audio_json(fs, p)) {
+            sounds.extend(json.sounds.into_iter().map(|s| s.name));
+        }
+
+        let mut maps = Vec::new();
+        if let Some(Ok(json)) = info.tiled_cfg.as_ref().map(|p| Self::get_tiled_json(fs, p)) {
+            maps.extend(json.maps.into_iter().map(|s| s.name));
+        }
+
+        [
+            ("sprite", sprites),
+            ("sprite_sheet", sheets),
+            ("font", fonts),
+            ("sound", sounds),
+            ("tilemap", maps),
+        ]
+        .into_iter()
+        .flat_map(|(category, names)| {
+            find_duplicates(names).into_iter().map(move |name| {
+                let msg = "name is used more than once, only the last one is kept";
+                ValidationIssue::error(category, &name, msg)
+            })
+        })
+        .collect()
+    }
+
+    fn validate_sprite_sheet(&self, name: &str, entry: &SpriteSheetEntry) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let mut error =
+            |msg: String| issues.push(ValidationIssue::error("sprite_sheet", name, msg));
+
+        let path = &entry.cfg.image_path;
+        let img = match load_image_rgba8(self.fs.as_ref(), path) {
+            Ok(img) => img,
+            Err(e) => {
+                error(format!("image {} doesn't load: {}", path, e));
+                return issues;
+            }
+        };
+
+        let size = img.size;
+        let sheet = SpriteSheetEntry {
+            cfg: entry.cfg.clone(),
+            loaded: Some(img),
+        };
+
+        let sprites = sheet.sprites();
+        for sprite in &sprites {
+            if !rect_in_image(&sprite.bounds, size) {
+                let b = sprite.bounds;
+                error(format!(
+                    "sprite '{}' at {},{} {}x{} reaches outside the {}x{} image",
+                    sprite.name, b.x, b.y, b.w, b.h, size[0], size[1]
+                ));
+            }
+        }
+
+        // Generated frames are meant to be overridden by hand-written ones, so only the
+        // hand-written list has to be unique.
+        let written = entry.cfg.sprites.iter().flatten();
+        for id in find_duplicates(written.clone().map(|s| s.id)) {
+            error(format!("sprite id {} is used more than once", id));
+        }
+        for dup in find_duplicates(written.map(|s| s.name.clone())) {
+            error(format!("sprite name '{}' is used more than once", dup));
+        }
+
+        let ids: HashSet<u32> = sprites.iter().map(|s| s.id).collect();
+        let animations = entry.cfg.animations.iter().flatten();
+        for dup in find_duplicates(animations.clone().map(|a| a.name.clone())) {
+            error(format!("animation name '{}' is used more than once", dup));
+        }
+        for anim in animations {
+            for frame in anim.frames.iter().filter(|f| !ids.contains(&f.id)) {
+                error(format!(
+                    "animation '{}' shows sprite id {}, which the sheet doesn't have",
+                    anim.name, frame.id
+                ));
+            }
+        }
+
+        issues
+    }
+}
+
+fn validate_tilemap(name: &str, map: &TiledMap) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    for layer in map.tile_layers() {
+        let mut missing: Vec<u32> = layer
+            .tiles
+            .iter()
+            .map(|t| tile_gid(*t))
+            .filter(|gid| *gid != 0 && map.tileset_of(*gid).is_none())
+            .collect();
+        missing.sort();
+        missing.dedup();
+
+        for gid in missing {
+            let msg = format!(
+                "layer '{}' uses tile {}, which no tileset has",
+                layer.name, gid
+            );
+            issues.push(ValidationIssue::error("tilemap", name, msg));
+        }
+    }
+
+    issues
+}
+
+fn validate_localization(l10n: &Localization) -> Vec<ValidationIssue> {
+    let mut keys: Vec<&String> = l10n
+        .languages()
+        .into_iter()
+        .filter_map(|l| l10n.table(l))
+        .flat_map(|t| t.strings.keys())
+        .collect();
+    keys.sort();
+    keys.dedup();
+
+    let fallback = l10n
+        .fallback_language()
+        .and_then(|f| l10n.table(f).map(|t| (f, t)));
+    let mut issues = Vec::new();
+    for language in l10n.languages() {
+        let table = l10n.table(language).unwrap();
+        for key in keys.iter().filter(|k| !table.strings.contains_key(**k)) {
+            match fallback {
+                Some((f, t)) if t.strings.contains_key(*key) => {
+                    let msg = format!("string '{}' is missing, the '{}' one is shown", key, f);
+                    issues.push(ValidationIssue::warning("localization", language, msg));
+                }
+                _ => {
+                    let msg = format!("string '{}' is missing and shows as its key", key);
+                    issues.push(ValidationIssue::error("localization", language, msg));
+                }
+            }
+        }
+    }
+
+    issues
+}
+
+#[test]
+fn test_validate() {
+    use std::fs;
+
+    let dir = std::env::temp_dir().join("shoyu_test_validate");
+    fs::create_dir_all(&dir).unwrap();
+    image::RgbaImage::new(16, 16)
+        .save(dir.join("a.png"))
+        .unwrap();
+    fs::write(dir.join("broken.png"), b"not a png").unwrap();
+    fs::write(
+        dir.join("shoyu.json"),
+        r#"{"sprite_cfg": "sprites.json", "sprite_sheet_cfg": "sheets.json", "ttf_cfg": "fonts.json"}"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.join("sprites.json"),
+        r#"{"sprites": [
+            {"name": "a", "image_path": "a.png"},
+            {"name": "a", "image_path": "a.png"},
+            {"name": "b", "image_path": "broken.png"},
+            {"name": "c", "image_path": "missing.png"}
+        ]}"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.join("sheets.json"),
+        r#"{"sprite_sheets": [{
+            "name": "hero",
+            "image_path": "a.png",
+            "sprites": [
+                {"name": "idle", "id": 0, "bounds": {"x": 0, "y": 0, "w": 8, "h": 8}},
+                {"name": "walk", "id": 0, "bounds": {"x": 12, "y": 0, "w": 8, "h": 8}}
+            ],
+            "animations": [{"name": "run", "frames": [{"id": 3, "duration_ms": 100.0}]}]
+        }]}"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.join("fonts.json"),
+        r#"{"fonts": [{"name": "f", "path": "missing.ttf", "size": 12.0, "fallbacks": ["nope"]}]}"#,
+    )
+    .unwrap();
+
+    let mut db = Database::new(dir.to_str().unwrap()).unwrap();
+    let issues = db.validate();
+    let found = |category: &str, entry: &str, text: &str| {
+        issues
+            .iter()
+            .any(|i| i.category == category && i.entry == entry && i.message.contains(text))
+    };
+
+    assert!(found("sprite", "a", "more than once"));
+    assert!(found("sprite", "b", "broken.png"));
+    assert!(found("sprite", "c", "missing.png"));
+    assert!(found(
+        "sprite_sheet",
+        "hero",
+        "'walk' at 12,0 8x8 reaches outside"
+    ));
+    assert!(found(
+        "sprite_sheet",
+        "hero",
+        "sprite id 0 is used more than once"
+    ));
+    assert!(found("sprite_sheet", "hero", "sprite id 3"));
+    assert!(found("font", "f", "nope"));
+    assert_eq!(issues.len(), 7);
+    assert!(issues.iter().all(|i| i.severity == Severity::Error));
+    assert!(!db.is_sprite_loaded("a"));
+
+    assert_eq!(find_duplicates([1, 2, 1, 3, 1, 2]), vec![1, 2]);
+}
@@ -0,0 +1,270 @@
+use super::error::*;
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+/// A file opened for reading piece by piece, like music being streamed.
+pub trait FileReader: Read + Seek + Send {}
+impl<T: Read + Seek + Send> FileReader for T {}
+
+/// Where the database and its loaders read files from. Paths are relative to the file
+/// system's root and use `/` as the separator.
+pub trait FileSystem: Send + Sync {
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error>;
+
+    fn read_to_string(&self, path: &str) -> Result<String, Error> {
+        String::from_utf8(self.read(path)?).with_path(path)
+    }
+
+    /// Opens a file for streaming. Reads the whole file into memory unless overridden.
+    fn open(&self, path: &str) -> Result<Box<dyn FileReader>, Error> {
+        Ok(Box::new(Cursor::new(self.read(path)?)))
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.read(path).is_ok()
+    }
+
+    /// When the file last changed, for hot reloading. `None` if it doesn't exist or the
+    /// file system can't change.
+    fn modified(&self, _path: &str) -> Option<SystemTime> {
+        None
+    }
+}
+
+fn not_found(path: &str) -> Error {
+    let e = std::io::Error::new(std::io::ErrorKind::NotFound, "file not found");
+    Error::from(e).with_path(path)
+}
+
+/// Resolves `.` and `..` and strips leading slashes, so `maps/../a.png` and `./a.png` both
+/// name `a.png`.
+pub fn normalize_path(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            p => parts.push(p),
+        }
+    }
+
+    parts.join("/")
+}
+
+/// Files in a directory on disk.
+pub struct DirectoryFileSystem {
+    root: PathBuf,
+}
+
+impl DirectoryFileSystem {
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: PathBuf::from(root),
+        }
+    }
+
+    pub fn root(&self) -> &str {
+        self.root.to_str().unwrap_or_default()
+    }
+
+    fn full_path(&self, path: &str) -> PathBuf {
+        self.root.join(path)
+    }
+}
+
+impl FileSystem for DirectoryFileSystem {
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        let full = self.full_path(path);
+        std::fs::read(&full).with_path(&full.to_string_lossy())
+    }
+
+    fn open(&self, path: &str) -> Result<Box<dyn FileReader>, Error> {
+        let full = self.full_path(path);
+        let file = std::fs::File::open(&full).with_path(&full.to_string_lossy())?;
+        Ok(Box::new(std::io::BufReader::new(file)))
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.full_path(path).is_file()
+    }
+
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        std::fs::metadata(self.full_path(path))
+            .and_then(|m| m.modified())
+            .ok()
+    }
+}
+
+/// Files held in memory. Mostly for tests, which can build a whole database without
+/// touching the disk. Files can be swapped while in use and count as modified for hot
+/// reloading.
+#[derive(Default)]
+pub struct MemoryFileSystem {
+    files: RwLock<HashMap<String, MemoryFile>>,
+}
+
+type MemoryFile = (Arc<Vec<u8>>, SystemTime);
+
+impl MemoryFileSystem {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds or replaces a file.
+    pub fn insert(&self, path: &str, data: impl Into<Vec<u8>>) {
+        let mut files = self.files.write().unwrap();
+        let path = normalize_path(path);
+        // Make sure a replaced file always looks changed, even within the clock's resolution.
+        let mut time = SystemTime::now();
+        if let Some((_, old)) = files.get(&path) {
+            time = time.max(*old + std::time::Duration::from_nanos(1));
+        }
+
+        files.insert(path, (Arc::new(data.into()), time));
+    }
+
+    pub fn remove(&self, path: &str) {
+        self.files.write().unwrap().remove(&normalize_path(path));
+    }
+
+    /// Builder style `insert`.
+    pub fn with(self, path: &str, data: impl Into<Vec<u8>>) -> Self {
+        self.insert(path, data);
+        self
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        match self.files.read().unwrap().get(&normalize_path(path)) {
+            Some((data, _)) => Ok(data.as_ref().clone()),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn open(&self, path: &str) -> Result<Box<dyn FileReader>, Error> {
+        match self.files.read().unwrap().get(&normalize_path(path)) {
+            Some((data, _)) => Ok(Box::new(Cursor::new(ArcBytes(data.clone())))),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.files.read().unwrap().contains_key(&normalize_path(path))
+    }
+
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        self.files
+            .read()
+            .unwrap()
+            .get(&normalize_path(path))
+            .map(|(_, time)| *time)
+    }
+}
+
+// Lets a stream read a memory file without copying it.
+struct ArcBytes(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for ArcBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Files compiled into the binary, usually with `embed_files!`. Lets a game ship as a
+/// single executable.
+pub struct EmbeddedFileSystem {
+    files: HashMap<String, &'static [u8]>,
+}
+
+impl EmbeddedFileSystem {
+    pub fn new(files: &[(&str, &'static [u8])]) -> Self {
+        Self {
+            files: files
+                .iter()
+                .map(|(path, data)| (normalize_path(path), *data))
+                .collect(),
+        }
+    }
+}
+
+impl FileSystem for EmbeddedFileSystem {
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        match self.files.get(&normalize_path(path)) {
+            Some(data) => Ok(data.to_vec()),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn open(&self, path: &str) -> Result<Box<dyn FileReader>, Error> {
+        match self.files.get(&normalize_path(path)) {
+            Some(data) => Ok(Box::new(Cursor::new(*data))),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.files.contains_key(&normalize_path(path))
+    }
+}
+
+/// Builds an `EmbeddedFileSystem` out of files under `dir`, which is relative to the file
+/// the macro is used in, like `include_bytes!`.
+///
+/// ```ignore
+/// let fs = shoyu::embed_files!("../assets", "shoyu.json", "sprites.json", "hero.png");
+/// let db = Database::with_filesystem(Arc::new(fs))?;
+/// ```
+#[macro_export]
+macro_rules! embed_files {
+    ($dir:literal, $($path:literal),* $(,)?) => {
+        $crate::database::EmbeddedFileSystem::new(&[
+            $(($path, include_bytes!(concat!($dir, "/", $path)) as &'static [u8]),)*
+        ])
+    };
+}
+
+#[test]
+fn test_filesystem() {
+    assert_eq!(normalize_path("./maps/../tiles//a.png"), "tiles/a.png");
+    assert_eq!(normalize_path("/a/b"), "a/b");
+
+    let memory = MemoryFileSystem::new().with("maps/level.json", "{}");
+    assert_eq!(memory.read_to_string("./maps/level.json").unwrap(), "{}");
+    let missing = memory.read("maps/other.json").unwrap_err();
+    assert_eq!(missing.path(), Some("maps/other.json"));
+
+    let before = memory.modified("maps/level.json").unwrap();
+    memory.insert("maps/level.json", "[]");
+    assert!(memory.modified("maps/level.json").unwrap() > before);
+    let mut streamed = String::new();
+    memory
+        .open("maps/level.json")
+        .unwrap()
+        .read_to_string(&mut streamed)
+        .unwrap();
+    assert_eq!(streamed, "[]");
+
+    let embedded = EmbeddedFileSystem::new(&[("a/b.txt", b"hi")]);
+    assert_eq!(embedded.read("a/c/../b.txt").unwrap(), b"hi");
+    assert!(embedded.read("b.txt").is_err());
+    assert_eq!(embedded.modified("a/b.txt"), None);
+
+    let dir = std::env::temp_dir().join("shoyu_test_filesystem");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("c.txt"), "disk").unwrap();
+    let disk = DirectoryFileSystem::new(dir.to_str().unwrap());
+    assert_eq!(disk.read_to_string("c.txt").unwrap(), "disk");
+    assert!(disk.modified("c.txt").is_some());
+    assert!(disk
+        .read("nope.txt")
+        .unwrap_err()
+        .path()
+        .unwrap()
+        .ends_with("nope.txt"));
+}
@@ -1,5 +1,7 @@
 pub mod utils;
 pub mod renderer2d;
 pub mod database;
+pub mod audio;
 pub mod io;
+pub mod validate;
 
@@ -0,0 +1,185 @@
+use glam::{vec4, Vec4};
+
+/// Bobs glyphs up and down along a sine wave. Sizes are in font pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextWave {
+    pub amplitude: f32,
+    /// Radians per second.
+    pub speed: f32,
+    /// Phase offset between neighbouring glyphs, in radians.
+    pub frequency: f32,
+}
+
+impl Default for TextWave {
+    fn default() -> Self {
+        Self {
+            amplitude: 4.0,
+            speed: 6.0,
+            frequency: 0.6,
+        }
+    }
+}
+
+/// A piece of text drawn with a single style.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StyledRun {
+    pub text: String,
+    pub color: Vec4,
+    pub scale: f32,
+    pub wave: Option<TextWave>,
+}
+
+enum Style {
+    Color(Vec4),
+    Scale(f32),
+    Wave(TextWave),
+}
+
+fn parse_color(hex: &str) -> Option<Vec4> {
+    let hex = hex.strip_prefix('#')?;
+    let digits: Vec<u32> = hex.chars().map(|c| c.to_digit(16)).collect::<Option<_>>()?;
+
+    // Short forms repeat each digit, so #f80 is #ff8800.
+    let channels: Vec<f32> = match digits.len() {
+        3 | 4 => digits.iter().map(|d| (d * 17) as f32 / 255.0).collect(),
+        6 | 8 => digits
+            .chunks(2)
+            .map(|c| (c[0] * 16 + c[1]) as f32 / 255.0)
+            .collect(),
+        _ => return None,
+    };
+
+    let alpha = channels.get(3).copied().unwrap_or(1.0);
+    Some(vec4(channels[0], channels[1], channels[2], alpha))
+}
+
+// Returns the tag name and, for opening tags, the style it applies.
+fn parse_tag(tag: &str) -> Option<(&str, Option<Style>)> {
+    if let Some(name) = tag.strip_prefix('/') {
+        return match name {
+            "color" | "scale" | "wave" => Some((name, None)),
+            _ => None,
+        };
+    }
+
+    let (name, value) = match tag.split_once('=') {
+        Some((n, v)) => (n, Some(v)),
+        None => (tag, None),
+    };
+
+    let style = match (name, value) {
+        ("color", Some(v)) => Style::Color(parse_color(v)?),
+        ("scale", Some(v)) => Style::Scale(v.parse().ok()?),
+        ("wave", None) => Style::Wave(Default::default()),
+        ("wave", Some(v)) => Style::Wave(TextWave {
+            amplitude: v.parse().ok()?,
+            ..Default::default()
+        }),
+        _ => return None,
+    };
+
+    Some((name, Some(style)))
+}
+
+fn current_run(stack: &[(&str, Style)], base_color: Vec4) -> StyledRun {
+    let mut run = StyledRun {
+        text: String::new(),
+        color: base_color,
+        scale: 1.0,
+        wave: None,
+    };
+
+    for (_, style) in stack {
+        match style {
+            Style::Color(c) => run.color = *c,
+            Style::Scale(s) => run.scale *= s,
+            Style::Wave(w) => run.wave = Some(*w),
+        }
+    }
+
+    run
+}
+
+/// Splits marked up text into styled runs. Supports `[color=#rgb]`, `[color=#rrggbbaa]`,
+/// `[scale=1.5]`, `[wave]` and `[wave=amplitude]`, each closed by the matching `[/tag]`.
+/// `[[` is a literal bracket. Anything that isn't a known tag is kept as text.
+pub fn parse_markup(text: &str, base_color: Vec4) -> Vec<StyledRun> {
+    let mut runs: Vec<StyledRun> = Vec::new();
+    let mut stack: Vec<(&str, Style)> = Vec::new();
+    let mut pending = String::new();
+
+    let flush = |pending: &mut String, stack: &[(&str, Style)], runs: &mut Vec<StyledRun>| {
+        if !pending.is_empty() {
+            let mut run = current_run(stack, base_color);
+            run.text = std::mem::take(pending);
+            runs.push(run);
+        }
+    };
+
+    let mut rest = text;
+    while let Some(open) = rest.find('[') {
+        pending.push_str(&rest[..open]);
+        rest = &rest[open..];
+
+        if let Some(after) = rest.strip_prefix("[[") {
+            pending.push('[');
+            rest = after;
+            continue;
+        }
+
+        let tag = rest[1..].find(']').and_then(|close| {
+            let (name, style) = parse_tag(&rest[1..close + 1])?;
+            Some((name, style, close + 2))
+        });
+
+        match tag {
+            Some((name, style, len)) => {
+                flush(&mut pending, &stack, &mut runs);
+                match style {
+                    Some(style) => stack.push((name, style)),
+                    None => {
+                        if let Some(pos) = stack.iter().rposition(|(n, _)| *n == name) {
+                            stack.remove(pos);
+                        }
+                    }
+                }
+                rest = &rest[len..];
+            }
+            None => {
+                pending.push('[');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    pending.push_str(rest);
+    flush(&mut pending, &stack, &mut runs);
+    runs
+}
+
+#[test]
+fn test_parse_markup() {
+    let white = vec4(1.0, 1.0, 1.0, 1.0);
+    let runs = parse_markup(
+        "You found [color=#ff0]gold [scale=2]coins[/scale][/color]! [wave]yay[/wave] [[x] [b]",
+        white,
+    );
+
+    let texts: Vec<&str> = runs.iter().map(|r| r.text.as_str()).collect();
+    assert_eq!(
+        texts,
+        vec!["You found ", "gold ", "coins", "! ", "yay", " [x] [b]"]
+    );
+
+    assert_eq!(runs[0].color, white);
+    assert_eq!(runs[1].color, vec4(1.0, 1.0, 0.0, 1.0));
+    assert_eq!(runs[2].color, vec4(1.0, 1.0, 0.0, 1.0));
+    assert_eq!(runs[2].scale, 2.0);
+    assert_eq!(runs[3].scale, 1.0);
+    assert!(runs[4].wave.is_some());
+    assert!(runs[5].wave.is_none());
+
+    let runs = parse_markup("[color=#00ff0080]a[scale=nope]", white);
+    assert_eq!(runs[0].color, vec4(0.0, 1.0, 0.0, 128.0 / 255.0));
+    assert_eq!(runs[0].text, "a[scale=nope]");
+}
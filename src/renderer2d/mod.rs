@@ -3,6 +3,8 @@ use glam::{vec2, vec4, Vec2};
 pub use types::*;
 pub mod resource_manager;
 pub use resource_manager::*;
+pub mod shared;
+pub use shared::{Shared, SharedResource};
 
 pub use dashi::utils::*;
 pub use dashi::*;
@@ -10,7 +12,16 @@ pub use dashi::*;
 pub mod particle;
 pub use particle::*;
 
-use crate::database::Database;
+pub mod text;
+pub use text::*;
+
+pub mod markup;
+pub use markup::*;
+
+pub mod tilemap;
+pub use tilemap::*;
+
+use crate::database::{Database, GlyphMode};
 use crate::utils::Canvas;
 mod pipeline;
 
@@ -42,10 +53,33 @@ pub struct SpriteSheetDrawCommand {
 
 pub struct TextDrawCommand<'a> {
     pub font: Handle<Font>,
+    // Top left of the layout box.
     pub position: glam::Vec2,
     pub scale: f32,
     pub text: &'a str,
     pub color: glam::Vec4,
+    // Box the text is wrapped, aligned and anchored in. `position` is its top left.
+    pub layout: TextLayoutInfo,
+}
+
+/// Draws text made of differently styled runs, usually from `parse_markup`.
+pub struct StyledTextDrawCommand<'a> {
+    pub font: Handle<Font>,
+    pub position: glam::Vec2,
+    pub scale: f32,
+    pub runs: &'a [StyledRun],
+    pub layout: TextLayoutInfo,
+    // Seconds, drives animated styles like waves.
+    pub time: f32,
+}
+
+pub struct TileMapDrawCommand<'a> {
+    pub map: Handle<TileMap>,
+    // Where the map's top left corner goes.
+    pub position: glam::Vec2,
+    pub scale: f32,
+    // Only this layer, even if it's hidden. Every visible tile layer in order otherwise.
+    pub layer: Option<&'a str>,
 }
 
 impl<'a> Default for TextDrawCommand<'a> {
@@ -56,6 +90,7 @@ impl<'a> Default for TextDrawCommand<'a> {
             scale: Default::default(),
             text: Default::default(),
             color: vec4(1.0, 1.0, 1.0, 1.0),
+            layout: Default::default(),
         }
     }
 }
@@ -64,6 +99,14 @@ fn normalized_to_vulkan_f32(c: f32) -> f32 {
     2.0 * c - 1.0
 }
 
+// The sprite pipeline's second uniform block.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SpriteUniform {
+    camera: Vec2,
+    premultiplied: f32,
+}
+
 fn normalized_to_vulkan(c: Vec2) -> Vec2 {
     let x = 2.0 * c.x() - 1.0;
     let y = 2.0 * c.y() - 1.0;
@@ -97,17 +140,16 @@ impl Renderer2D {
             })
             .unwrap();
 
-        let particle_path = database.particle_system_cfg_path().unwrap();
-        let base_path = database.base_path().to_string();
+        let particle_system = ParticleSystem::from_database(ctx, &canvas, &database);
         let manager = ResourceManager::new(ctx, canvas, database);
         Self {
-            cmd: FramedCommandList::new(ctx, "Renderer2D", 3),
+            cmd: FramedCommandList::new(ctx, "Renderer2D", FRAMES_IN_FLIGHT),
             display,
             sems: ctx.make_semaphores(1).unwrap(),
             ctx,
             display_img: Default::default(),
             display_sem: Default::default(),
-            particle_system: ParticleSystem::new(ctx, manager.canvas(), &base_path, &particle_path),
+            particle_system,
             manager,
         }
     }
@@ -117,6 +159,8 @@ impl Renderer2D {
     }
 
     pub fn begin_drawing(&mut self) {
+        self.manager.collect_garbage();
+        self.manager.hot_reload();
         self.manager.allocator().reset();
         let (img, sem, _idx, _good) =
             unsafe { (*self.ctx).acquire_new_image(&mut self.display).unwrap() };
@@ -126,6 +170,8 @@ impl Renderer2D {
 
         //        self.cmd = unsafe { (*self.ctx).begin_command_list(&Default::default()).unwrap() };
         self.particle_system.update(&mut self.cmd);
+        self.manager.upload_glyphs(&mut self.cmd);
+        self.manager.upload_images(&mut self.cmd);
 
         self.cmd.append(|cmd| {
             cmd.begin_drawing(&DrawBegin {
@@ -169,6 +215,24 @@ impl Renderer2D {
     }
 
     pub fn draw_text(&mut self, cmd: &TextDrawCommand) {
+        let runs = [StyledRun {
+            text: cmd.text.to_string(),
+            color: cmd.color,
+            scale: 1.0,
+            wave: None,
+        }];
+
+        self.draw_styled_text(&StyledTextDrawCommand {
+            font: cmd.font,
+            position: cmd.position,
+            scale: cmd.scale,
+            runs: &runs,
+            layout: cmd.layout,
+            time: 0.0,
+        });
+    }
+
+    pub fn draw_styled_text(&mut self, cmd: &StyledTextDrawCommand) {
         #[repr(C)]
         #[derive(Clone, Copy)]
         struct TextVertex {
@@ -177,74 +241,93 @@ impl Renderer2D {
         }
 
         let font_handle = self.manager.fetch_font(cmd.font).unwrap();
-        let font_bg = font_handle.bg;
-        let font = font_handle.font;
+        let ttf = font_handle.font.clone();
         let dim = font_handle.dim;
+        let page_bgs: Vec<Handle<BindGroup>> = font_handle.pages.iter().map(|p| p.bg).collect();
+        let mut font = ttf.lock().unwrap();
+        let pipeline = match font.mode {
+            GlyphMode::Coverage => self.manager.gfx().text_pipeline,
+            GlyphMode::Sdf { .. } => self.manager.gfx().sdf_text_pipeline,
+        };
+        let layout = layout_runs(&font, cmd.runs, &cmd.layout);
+        let run_of: Vec<&StyledRun> = cmd
+            .runs
+            .iter()
+            .flat_map(|r| std::iter::repeat_n(r, r.text.chars().count()))
+            .collect();
 
         self.cmd.append(|list| {
             list.begin_drawing(&DrawBegin {
                 viewport: self.manager.canvas().viewport(),
-                pipeline: self.manager.gfx().text_pipeline,
+                pipeline,
             })
             .unwrap();
             let res = self.manager.canvas().viewport().area.clone();
             let pos = screen_to_normalized(cmd.position, res.w, res.h);
-            let mut xpos = pos.x();
-            let ypos = pos.y();
-            for ch in cmd.text.chars() {
-                unsafe {
-                    if let Some(g) = (*font).glyphs.get(&ch) {
-                        let mut vert_alloc = self.manager.allocator().bump().unwrap();
-                        let mut info = self.manager.allocator().bump().unwrap();
-                        let vertices = vert_alloc.slice::<TextVertex>().split_at_mut(4).0;
-                        let color = info.slice::<glam::Vec4>();
-
-                        color[0] = cmd.color;
-
-                        let scale = cmd.scale;
-                        let gw = g.bounds.w as f32 / dim[0] as f32;
-                        let gh = g.bounds.h as f32 / dim[1] as f32;
-
-                        let x0 = (scale * (xpos)) - 1.0;
-                        let y0 = (scale * (ypos - gh - g.bearing_y)) - 1.0;
-                        let x1 = (scale * ((xpos) + (g.bounds.w as f32 / dim[0] as f32))) - 1.0;
-                        let y1 = (scale * ((ypos - gh - g.bearing_y) + gh)) - 1.0;
-
-                        let tex_x0 = (g.bounds.x as f32 / dim[0] as f32) as f32;
-                        let tex_y0 = (g.bounds.y as f32 / dim[1] as f32) as f32;
-
-                        let tex_x1 = tex_x0 + gw;
-                        let tex_y1 = tex_y0 + gh;
-
-                        vertices.copy_from_slice(&[
-                            TextVertex {
-                                pos: vec2(x1, y1),
-                                tex: vec2(tex_x1, tex_y1),
-                            },
-                            TextVertex {
-                                pos: vec2(x0, y1),
-                                tex: vec2(tex_x0, tex_y1),
-                            },
-                            TextVertex {
-                                pos: vec2(x0, y0),
-                                tex: vec2(tex_x0, tex_y0),
-                            },
-                            TextVertex {
-                                pos: vec2(x1, y0),
-                                tex: vec2(tex_x1, tex_y0),
-                            },
-                        ]);
-
-                        xpos += g.advance;
-                        list.draw_dynamic_indexed(&DrawIndexedDynamic {
-                            vertices: vert_alloc,
-                            indices: self.manager.indices().to_unmapped_dynamic(0),
-                            dynamic_buffers: [Some(info), None, None, None],
-                            bind_groups: [Some(font_bg), None, None, None],
-                            index_count: 6,
-                            ..Default::default()
-                        });
-                    }
+            for placed in &layout.glyphs {
+                // Layout positions are in font pixels, like the glyph metrics.
+                let run = run_of[placed.index];
+                let wave = run.wave.map_or(0.0, |w| {
+                    w.amplitude * (w.speed * cmd.time + w.frequency * placed.index as f32).sin()
+                });
+                let xpos = pos.x() + placed.x / dim[0] as f32;
+                let ypos = pos.y() + (placed.y + wave) / dim[1] as f32;
+                let s = placed.scale;
+                // Glyphs on a page that hasn't been uploaded yet get skipped for this frame.
+                let glyph = font.glyph(placed.ch).filter(|g| g.bounds.w > 0);
+                if let Some((g, font_bg)) =
+                    glyph.and_then(|g| Some((g, *page_bgs.get(g.page as usize)?)))
+                {
+                    let mut vert_alloc = self.manager.allocator().bump().unwrap();
+                    let mut info = self.manager.allocator().bump().unwrap();
+                    let vertices = vert_alloc.slice::<TextVertex>().split_at_mut(4).0;
+                    let color = info.slice::<glam::Vec4>();
+
+                    color[0] = run.color;
+
+                    let scale = cmd.scale;
+                    let gw = g.bounds.w as f32 / dim[0] as f32;
+                    let gh = g.bounds.h as f32 / dim[1] as f32;
+
+                    // The run's scale grows the glyph out from its pen position on the baseline.
+                    let x0 = (scale * (xpos + s * g.bearing_x)) - 1.0;
+                    let y0 = (scale * (ypos - s * (gh + g.bearing_y))) - 1.0;
+                    let x1 = (scale * (xpos + s * (g.bearing_x + gw))) - 1.0;
+                    let y1 = (scale * (ypos - s * g.bearing_y)) - 1.0;
+
+                    let tex_x0 = (g.bounds.x as f32 / dim[0] as f32) as f32;
+                    let tex_y0 = (g.bounds.y as f32 / dim[1] as f32) as f32;
+
+                    let tex_x1 = tex_x0 + gw;
+                    let tex_y1 = tex_y0 + gh;
+
+                    vertices.copy_from_slice(&[
+                        TextVertex {
+                            pos: vec2(x1, y1),
+                            tex: vec2(tex_x1, tex_y1),
+                        },
+                        TextVertex {
+                            pos: vec2(x0, y1),
+                            tex: vec2(tex_x0, tex_y1),
+                        },
+                        TextVertex {
+                            pos: vec2(x0, y0),
+                            tex: vec2(tex_x0, tex_y0),
+                        },
+                        TextVertex {
+                            pos: vec2(x1, y0),
+                            tex: vec2(tex_x1, tex_y0),
+                        },
+                    ]);
+
+                    list.draw_dynamic_indexed(&DrawIndexedDynamic {
+                        vertices: vert_alloc,
+                        indices: self.manager.indices().to_unmapped_dynamic(0),
+                        dynamic_buffers: [Some(info), None, None, None],
+                        bind_groups: [Some(font_bg), None, None, None],
+                        index_count: 6,
+                        ..Default::default()
+                    });
                 }
             }
         });
@@ -253,7 +336,7 @@ impl Renderer2D {
         let mut b1 = self.manager.allocator().bump().unwrap();
         let mut b2 = self.manager.allocator().bump().unwrap();
         let transform = &mut b1.slice::<glam::Mat4>()[0];
-        let camera = &mut b2.slice::<glam::Vec2>()[0];
+        let uniform = &mut b2.slice::<SpriteUniform>()[0];
 
         let res = self.manager.canvas().viewport().area.clone();
         let size = screen_to_normalized(cmd.size, res.w, res.h);
@@ -274,12 +357,40 @@ impl Renderer2D {
 
         let t = translate_back * rotate * translate_to_origin * scale;
         *transform = t;
-        *camera = glam::Vec2::new(0.0, 0.0);
-        let sprite_bg = self.manager.fetch_sprite(cmd.sprite).unwrap().bg;
+        let (sprite_bg, uv) = {
+            let sprite = self.manager.fetch_sprite(cmd.sprite).unwrap();
+            *uniform = SpriteUniform {
+                camera: glam::Vec2::new(0.0, 0.0),
+                premultiplied: sprite.premultiplied as u32 as f32,
+            };
+            (sprite.bg, sprite.uv)
+        };
+
+        // Atlas sprites only cover part of their page.
+        let mut vert_alloc = self.manager.allocator().bump().unwrap();
+        vert_alloc.slice::<Vertex>()[..4].copy_from_slice(&[
+            Vertex {
+                position: [-1.0, 1.0],
+                tex_coords: [uv.x, uv.h],
+            },
+            Vertex {
+                position: [-1.0, -1.0],
+                tex_coords: [uv.x, uv.y],
+            },
+            Vertex {
+                position: [1.0, -1.0],
+                tex_coords: [uv.w, uv.y],
+            },
+            Vertex {
+                position: [1.0, 1.0],
+                tex_coords: [uv.w, uv.h],
+            },
+        ]);
+
         self.cmd.append(|cmd| {
-            cmd.draw_indexed(&DrawIndexed {
-                vertices: self.manager.vertices(),
-                indices: self.manager.indices(),
+            cmd.draw_dynamic_indexed(&DrawIndexedDynamic {
+                vertices: vert_alloc,
+                indices: self.manager.indices().to_unmapped_dynamic(0),
                 dynamic_buffers: [Some(b1), Some(b2), None, None],
                 bind_groups: [Some(sprite_bg), None, None, None],
                 index_count: 6,
@@ -298,29 +409,57 @@ impl Renderer2D {
             if let Some(bounds) = sheet.sprites.get(&cmd.sprite_id) {
                 let size = screen_to_normalized(cmd.size, res.w, res.h);
                 let transform = &mut b1.slice::<glam::Mat4>()[0];
-                let camera = &mut b2.slice::<glam::Vec2>()[0];
+                let uniform = &mut b2.slice::<SpriteUniform>()[0];
                 let vertices = vert_alloc.slice::<Vertex>().split_at_mut(4).0;
+                let layout = sheet.layouts.get(&cmd.sprite_id).copied();
+
+                // Trimmed frames only cover part of the untrimmed quad.
+                let (x0, y0, x1, y1) = match layout {
+                    Some(l) => (
+                        -1.0 + 2.0 * l.offset[0],
+                        -1.0 + 2.0 * l.offset[1],
+                        -1.0 + 2.0 * (l.offset[0] + l.size[0]),
+                        -1.0 + 2.0 * (l.offset[1] + l.size[1]),
+                    ),
+                    None => (-1.0, -1.0, 1.0, 1.0),
+                };
+
+                // Rotated frames are stored clockwise, so walk the region the other way round.
+                let tex = match layout.is_some_and(|l| l.rotated) {
+                    true => [
+                        [bounds.x, bounds.y],
+                        [bounds.w, bounds.y],
+                        [bounds.w, bounds.h],
+                        [bounds.x, bounds.h],
+                    ],
+                    false => [
+                        [bounds.x, bounds.h],
+                        [bounds.x, bounds.y],
+                        [bounds.w, bounds.y],
+                        [bounds.w, bounds.h],
+                    ],
+                };
 
                 vertices.copy_from_slice(&[
                     // Top-left corner of the screen
                     Vertex {
-                        position: [-1.0, 1.0],
-                        tex_coords: [bounds.x, bounds.h],
+                        position: [x0, y1],
+                        tex_coords: tex[0],
                     },
                     // Bottom-left corner of the screen
                     Vertex {
-                        position: [-1.0, -1.0],
-                        tex_coords: [bounds.x, bounds.y],
+                        position: [x0, y0],
+                        tex_coords: tex[1],
                     },
                     // Bottom-right corner of the screen
                     Vertex {
-                        position: [1.0, -1.0],
-                        tex_coords: [bounds.w, bounds.y],
+                        position: [x1, y0],
+                        tex_coords: tex[2],
                     },
                     // Top-right corner of the screen
                     Vertex {
-                        position: [1.0, 1.0],
-                        tex_coords: [bounds.w, bounds.h],
+                        position: [x1, y1],
+                        tex_coords: tex[3],
                     },
                 ]);
 
@@ -344,7 +483,10 @@ impl Renderer2D {
                 let t = translate_back * rotate * translate_to_origin * scale;
 
                 *transform = t;
-                *camera = glam::Vec2::new(0.0, 0.0);
+                *uniform = SpriteUniform {
+                    camera: glam::Vec2::new(0.0, 0.0),
+                    premultiplied: sheet.premultiplied as u32 as f32,
+                };
                 let sprite_bg = self.manager.fetch_sprite_sheet(cmd.sheet).unwrap().bg;
 
                 self.cmd.append(|cmd| {
@@ -360,4 +502,81 @@ impl Renderer2D {
             }
         }
     }
+
+    /// Draws a tile map chunk by chunk, one draw per tileset in each chunk, skipping chunks
+    /// that are off screen. Layer opacity isn't applied.
+    pub fn draw_tilemap(&mut self, cmd: &TileMapDrawCommand) {
+        let res = self.manager.canvas().viewport().area;
+        let (chunks, sheets, layers) = match self.manager.fetch_tilemap(cmd.map) {
+            Some(map) => (
+                std::mem::take(&mut map.chunks),
+                map.sheets.clone(),
+                map.layers.clone(),
+            ),
+            None => return,
+        };
+
+        let shown: Vec<bool> = layers
+            .iter()
+            .map(|(name, visible)| match cmd.layer {
+                Some(layer) => name == layer,
+                None => *visible,
+            })
+            .collect();
+
+        // Map pixels straight to Vulkan coordinates, shared by every chunk.
+        let mut b1 = self.manager.allocator().bump().unwrap();
+        let mut b2 = self.manager.allocator().bump().unwrap();
+        let origin = screen_to_vulkan(cmd.position, res.w, res.h);
+        b1.slice::<glam::Mat4>()[0] =
+            glam::Mat4::from_translation(glam::Vec3::new(origin.x(), origin.y(), 0.0))
+                * glam::Mat4::from_scale(glam::Vec3::new(
+                    2.0 * cmd.scale / res.w,
+                    2.0 * cmd.scale / res.h,
+                    1.0,
+                ));
+        // Tilesets come from map files, which have no texture options.
+        b2.slice::<SpriteUniform>()[0] = SpriteUniform {
+            camera: glam::Vec2::new(0.0, 0.0),
+            premultiplied: 0.0,
+        };
+
+        let mut draws = Vec::new();
+        for chunk in chunks.iter().filter(|c| shown[c.layer]) {
+            let b = &chunk.bounds;
+            let x0 = cmd.position.x() + b.x * cmd.scale;
+            let y0 = cmd.position.y() + b.y * cmd.scale;
+            let x1 = cmd.position.x() + b.w * cmd.scale;
+            let y1 = cmd.position.y() + b.h * cmd.scale;
+            if x1 < 0.0 || y1 < 0.0 || x0 > res.w || y0 > res.h {
+                continue;
+            }
+
+            let bg = match self.manager.fetch_sprite_sheet(sheets[chunk.sheet]) {
+                Some(sheet) => sheet.bg,
+                None => continue,
+            };
+
+            let mut vert_alloc = self.manager.allocator().bump().unwrap();
+            vert_alloc.slice::<Vertex>()[..chunk.vertices.len()].copy_from_slice(&chunk.vertices);
+            draws.push((vert_alloc, bg, (chunk.vertices.len() / 4 * 6) as u32));
+        }
+
+        if let Some(map) = self.manager.fetch_tilemap(cmd.map) {
+            map.chunks = chunks;
+        }
+
+        self.cmd.append(|list| {
+            for (vertices, bg, index_count) in draws {
+                list.draw_dynamic_indexed(&DrawIndexedDynamic {
+                    vertices,
+                    indices: self.manager.indices().to_unmapped_dynamic(0),
+                    dynamic_buffers: [Some(b1), Some(b2), None, None],
+                    bind_groups: [Some(bg), None, None, None],
+                    index_count,
+                    ..Default::default()
+                });
+            }
+        });
+    }
 }
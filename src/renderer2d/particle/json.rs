@@ -27,3 +27,15 @@ pub struct ParticleSystemJSONEntry {
 pub struct ParticleSystemJSON {
     pub particles: Vec<ParticleSystemJSONEntry>,
 }
+
+impl ParticleSystemJSON {
+    /// Adds `top`'s particles, replacing the ones with the same name.
+    pub fn merge(&mut self, top: ParticleSystemJSON) {
+        for particle in top.particles {
+            match self.particles.iter_mut().find(|p| p.name == particle.name) {
+                Some(p) => *p = particle,
+                None => self.particles.push(particle),
+            }
+        }
+    }
+}
@@ -8,7 +8,10 @@ use dashi::*;
 mod pipelines;
 use pipelines::*;
 
-use crate::database::load_funcs;
+use crate::database::{
+    find_duplicates, load_funcs, rect_in_image, Database, Error, ErrorContext, FileSystem,
+    ValidationIssue,
+};
 use crate::utils::{Canvas, SizedImage, Timer};
 use rand::prelude::*;
 #[repr(C)]
@@ -152,30 +155,194 @@ pub struct ParticleSystem {
 }
 
 impl ParticleSystem {
-    pub fn new(ctx: &mut Context, canvas: &Canvas, base_path: &str, particle_cfg: &str) -> Self {
+    fn read_cfg(fs: &dyn FileSystem, path: &str) -> Result<ParticleSystemJSON, Error> {
+        let json_data = fs.read_to_string(path)?;
+        let info: ParticleSystemJSON = serde_json::from_str(&json_data).with_path(path)?;
+        Ok(info)
+    }
+
+    // A particle replaces the one with the same name from an earlier config.
+    fn read_cfgs(cfgs: &[(&dyn FileSystem, &str)]) -> Result<ParticleSystemJSON, Error> {
+        let mut merged = ParticleSystemJSON {
+            particles: Vec::new(),
+        };
+        for (fs, path) in cfgs {
+            merged.merge(Self::read_cfg(*fs, path)?);
+        }
+
+        Ok(merged)
+    }
+
+    /// Checks a particle config the way `new` would read it, without touching the GPU.
+    pub fn validate_cfg(fs: &dyn FileSystem, particle_cfg: &str) -> Vec<ValidationIssue> {
+        Self::validate_cfgs(fs, &[(fs, particle_cfg)])
+    }
+
+    /// Checks the particle configs of every mount, each read from its own file system and
+    /// merged the way `from_database` merges them. Images are read through `fs`.
+    pub fn validate_cfgs(
+        fs: &dyn FileSystem,
+        cfgs: &[(&dyn FileSystem, &str)],
+    ) -> Vec<ValidationIssue> {
+        let error = |entry: &str, msg: String| ValidationIssue::error("particle", entry, msg);
+        let particle_cfg = cfgs.last().map(|(_, p)| *p).unwrap_or_default();
+
+        let mut issues = Vec::new();
+        let mut info = ParticleSystemJSON {
+            particles: Vec::new(),
+        };
+        for (cfg_fs, path) in cfgs {
+            let cfg = match Self::read_cfg(*cfg_fs, path) {
+                Ok(cfg) => cfg,
+                Err(e) => {
+                    issues.push(error(path, format!("can't read {}: {}", path, e)));
+                    continue;
+                }
+            };
+
+            for name in find_duplicates(cfg.particles.iter().map(|p| p.name.clone())) {
+                issues.push(error(&name, "name is used more than once".to_string()));
+            }
+            info.merge(cfg);
+        }
+
+        for id in find_duplicates(info.particles.iter().map(|p| p.id)) {
+            let msg = format!("id {} is used by more than one particle", id);
+            issues.push(error(particle_cfg, msg));
+        }
+
+        // Every particle draws out of the first particle's image.
+        let size = info.particles.first().and_then(|first| {
+            match load_funcs::load_image_rgba8(fs, &first.image_path) {
+                Ok(img) => Some(img.size),
+                Err(e) => {
+                    let msg = format!("image {} doesn't load: {}", first.image_path, e);
+                    issues.push(error(&first.name, msg));
+                    None
+                }
+            }
+        });
+
+        for particle in &info.particles {
+            let name = particle.name.as_str();
+            if particle.id as usize >= MAX_PARTICLE_ANIMATIONS {
+                let msg = format!(
+                    "id {} is past the limit of {} particles",
+                    particle.id, MAX_PARTICLE_ANIMATIONS
+                );
+                issues.push(error(name, msg));
+            }
+
+            if particle.image_path != info.particles[0].image_path {
+                let msg = format!(
+                    "image {} is ignored, particles all use {}",
+                    particle.image_path, info.particles[0].image_path
+                );
+                issues.push(ValidationIssue::warning("particle", name, msg));
+                continue;
+            }
+
+            if particle.animations.len() > 1 {
+                let msg = "has more than one animation, only the last one is used";
+                issues.push(ValidationIssue::warning("particle", name, msg));
+            }
+
+            for anim in &particle.animations {
+                if anim.sprites.len() > MAX_PARTICLE_ANIMATIONS {
+                    let msg = format!(
+                        "animation '{}' has {} frames, the limit is {}",
+                        anim.name,
+                        anim.sprites.len(),
+                        MAX_PARTICLE_ANIMATIONS
+                    );
+                    issues.push(error(name, msg));
+                }
+
+                let outside = anim
+                    .sprites
+                    .iter()
+                    .filter(|r| size.is_some_and(|s| !rect_in_image(r, s)));
+                for r in outside {
+                    let msg = format!(
+                        "animation '{}' frame at {},{} {}x{} reaches outside the image",
+                        anim.name, r.x, r.y, r.w, r.h
+                    );
+                    issues.push(error(name, msg));
+                }
+            }
+        }
+
+        issues
+    }
+
+    /// Reads the particle config and its image through the database's file system.
+    pub fn new(
+        ctx: &mut Context,
+        canvas: &Canvas,
+        fs: &dyn FileSystem,
+        particle_cfg: &str,
+    ) -> Self {
+        Self::try_new(ctx, canvas, fs, particle_cfg).unwrap()
+    }
+
+    /// Same as `new`, but a missing or malformed config or image comes back as an error.
+    pub fn try_new(
+        ctx: &mut Context,
+        canvas: &Canvas,
+        fs: &dyn FileSystem,
+        particle_cfg: &str,
+    ) -> Result<Self, Error> {
+        Self::try_from_cfgs(ctx, canvas, fs, &[(fs, particle_cfg)])
+    }
+
+    /// Builds the particle system out of the particle configs of every mount of the
+    /// database. Later mounts replace particles with the same name.
+    pub fn from_database(ctx: &mut Context, canvas: &Canvas, database: &Database) -> Self {
+        Self::try_from_database(ctx, canvas, database).unwrap()
+    }
+
+    pub fn try_from_database(
+        ctx: &mut Context,
+        canvas: &Canvas,
+        database: &Database,
+    ) -> Result<Self, Error> {
+        let cfgs: Vec<(&dyn FileSystem, &str)> = database
+            .particle_system_cfgs()
+            .into_iter()
+            .map(|(mount, path)| (mount.fs.as_ref(), path))
+            .collect();
+        if cfgs.is_empty() {
+            return Err(Error::loading("the database has no particle config"));
+        }
+
+        Self::try_from_cfgs(ctx, canvas, database.filesystem().as_ref(), &cfgs)
+    }
+
+    fn try_from_cfgs(
+        ctx: &mut Context,
+        canvas: &Canvas,
+        fs: &dyn FileSystem,
+        cfgs: &[(&dyn FileSystem, &str)],
+    ) -> Result<Self, Error> {
         const _TEST_CHECKER: [u8; 64] = [0; std::mem::size_of::<ShaderParticle>()];
 
         const MAX_PARTICLES: usize = 2048;
-        let json_data = std::fs::read_to_string(format!("{}/{}", base_path, particle_cfg))
-            .expect("Unable to load Particle System JSON!");
-        let info: ParticleSystemJSON =
-            serde_json::from_str(&json_data).expect("Unable to parse Particle System JSON!");
+        let info = Self::read_cfgs(cfgs)?;
+        let particle_cfg = cfgs.last().map(|(_, p)| *p).unwrap_or_default();
 
         // Parse particle info
         let initial_data = vec![ShaderParticle::default(); MAX_PARTICLES];
 
-        let particle_buffer = ctx
-            .make_buffer(&BufferInfo {
-                debug_name: "Particle System Buffers",
-                byte_size: (std::mem::size_of::<ShaderParticle>() * MAX_PARTICLES) as u32,
-                visibility: MemoryVisibility::CpuAndGpu,
-                usage: BufferUsage::STORAGE,
-                initial_data: Some(unsafe { &initial_data.align_to::<u8>().1 }),
-            })
-            .unwrap();
+        let particle_buffer = ctx.make_buffer(&BufferInfo {
+            debug_name: "Particle System Buffers",
+            byte_size: (std::mem::size_of::<ShaderParticle>() * MAX_PARTICLES) as u32,
+            visibility: MemoryVisibility::CpuAndGpu,
+            usage: BufferUsage::STORAGE,
+            initial_data: Some(unsafe { &initial_data.align_to::<u8>().1 }),
+        })?;
 
         drop(initial_data);
-        let alloc = ctx.make_dynamic_allocator(&Default::default()).unwrap();
+        let alloc = ctx.make_dynamic_allocator(&Default::default())?;
         let pipelines = pipelines::make_pipelines(ctx, canvas);
 
         //        let particle_animations =
@@ -185,29 +352,22 @@ impl ParticleSystem {
 
         for particle in &info.particles {
             if image.is_none() {
-                let img = load_funcs::load_image_rgba8(&format!(
-                    "{}/{}",
-                    base_path, &particle.image_path
-                ))
-                .unwrap();
-                let gpu_img = ctx
-                    .make_image(&ImageInfo {
-                        debug_name: &particle.image_path,
-                        dim: [img.size[0], img.size[1], 1],
-                        format: Format::RGBA8,
-                        mip_levels: 1,
-                        initial_data: Some(&img.bytes),
-                    })
-                    .unwrap();
-
-                let view = ctx
-                    .make_image_view(&ImageViewInfo {
-                        debug_name: &particle.image_path,
-                        img: gpu_img,
-                        layer: 1,
-                        mip_level: 0,
-                    })
-                    .unwrap();
+                let img_path = &particle.image_path;
+                let img = load_funcs::load_image_rgba8(fs, img_path).with_path(img_path)?;
+                let gpu_img = ctx.make_image(&ImageInfo {
+                    debug_name: &particle.image_path,
+                    dim: [img.size[0], img.size[1], 1],
+                    format: Format::RGBA8,
+                    mip_levels: 1,
+                    initial_data: Some(&img.bytes),
+                })?;
+
+                let view = ctx.make_image_view(&ImageViewInfo {
+                    debug_name: &particle.image_path,
+                    img: gpu_img,
+                    layer: 1,
+                    mip_level: 0,
+                })?;
 
                 image = Some(SizedImage {
                     handle: gpu_img,
@@ -217,92 +377,87 @@ impl ParticleSystem {
                 });
             }
 
+            if particle.id as usize >= MAX_PARTICLE_ANIMATIONS {
+                let msg = format!("particle id {} is out of range", particle.id);
+                return Err(Error::loading(msg).with_path(particle_cfg));
+            }
+
             for anim in &particle.animations {
                 animations[particle.id as usize] =
                     convert_animation(anim, image.as_ref().unwrap().dim);
             }
         }
 
-        let sampler = ctx.make_sampler(&Default::default()).unwrap();
-
-        let particle_anim_buffer = ctx
-            .make_buffer(&BufferInfo {
-                debug_name: "Particle Animation Info",
-                byte_size: size_of::<ParticleAnimation>() as u32 * MAX_PARTICLE_ANIMATIONS as u32,
-                visibility: MemoryVisibility::Gpu,
-                usage: BufferUsage::STORAGE,
-                initial_data: Some(unsafe { &animations.align_to::<u8>().1 }),
-            })
-            .unwrap();
-
-        let compute_bg = ctx
-            .make_bind_group(&BindGroupInfo {
-                debug_name: "Particle System Compute BG",
-                layout: pipelines.compute_bg_layout,
-                bindings: &[
-                    BindingInfo {
-                        resource: ShaderResource::StorageBuffer(particle_anim_buffer),
-                        binding: 0,
-                    },
-                    BindingInfo {
-                        resource: ShaderResource::StorageBuffer(particle_buffer),
-                        binding: 1,
-                    },
-                    BindingInfo {
-                        resource: ShaderResource::Dynamic(&alloc),
-                        binding: 2,
-                    },
-                    BindingInfo {
-                        resource: ShaderResource::Dynamic(&alloc),
-                        binding: 3,
-                    },
-                    BindingInfo {
-                        resource: ShaderResource::SampledImage(
-                            image.as_ref().unwrap().view,
-                            sampler,
-                        ),
-                        binding: 4,
-                    },
-                ],
-                set: 0,
-            })
-            .unwrap();
-
-        let draw_bg = ctx
-            .make_bind_group(&BindGroupInfo {
-                debug_name: "Particle System Main Buffer",
-                layout: pipelines.bg_layout,
-                bindings: &[
-                    BindingInfo {
-                        resource: ShaderResource::StorageBuffer(particle_anim_buffer),
-                        binding: 0,
-                    },
-                    BindingInfo {
-                        resource: ShaderResource::StorageBuffer(particle_buffer),
-                        binding: 1,
-                    },
-                    BindingInfo {
-                        resource: ShaderResource::Dynamic(&alloc),
-                        binding: 2,
-                    },
-                    BindingInfo {
-                        resource: ShaderResource::Dynamic(&alloc),
-                        binding: 3,
-                    },
-                    BindingInfo {
-                        resource: ShaderResource::SampledImage(
-                            image.as_ref().unwrap().view,
-                            sampler,
-                        ),
-                        binding: 4,
-                    },
-                ],
-                set: 0,
-            })
-            .unwrap();
-        let raw_slice = ctx
-            .map_buffer_mut::<ShaderParticle>(particle_buffer)
-            .unwrap();
+        let atlas = match image {
+            Some(image) => image,
+            None => return Err(Error::loading("config lists no particles").with_path(particle_cfg)),
+        };
+        let sampler = ctx.make_sampler(&Default::default())?;
+
+        let particle_anim_buffer = ctx.make_buffer(&BufferInfo {
+            debug_name: "Particle Animation Info",
+            byte_size: size_of::<ParticleAnimation>() as u32 * MAX_PARTICLE_ANIMATIONS as u32,
+            visibility: MemoryVisibility::Gpu,
+            usage: BufferUsage::STORAGE,
+            initial_data: Some(unsafe { &animations.align_to::<u8>().1 }),
+        })?;
+
+        let compute_bg = ctx.make_bind_group(&BindGroupInfo {
+            debug_name: "Particle System Compute BG",
+            layout: pipelines.compute_bg_layout,
+            bindings: &[
+                BindingInfo {
+                    resource: ShaderResource::StorageBuffer(particle_anim_buffer),
+                    binding: 0,
+                },
+                BindingInfo {
+                    resource: ShaderResource::StorageBuffer(particle_buffer),
+                    binding: 1,
+                },
+                BindingInfo {
+                    resource: ShaderResource::Dynamic(&alloc),
+                    binding: 2,
+                },
+                BindingInfo {
+                    resource: ShaderResource::Dynamic(&alloc),
+                    binding: 3,
+                },
+                BindingInfo {
+                    resource: ShaderResource::SampledImage(atlas.view, sampler),
+                    binding: 4,
+                },
+            ],
+            set: 0,
+        })?;
+
+        let draw_bg = ctx.make_bind_group(&BindGroupInfo {
+            debug_name: "Particle System Main Buffer",
+            layout: pipelines.bg_layout,
+            bindings: &[
+                BindingInfo {
+                    resource: ShaderResource::StorageBuffer(particle_anim_buffer),
+                    binding: 0,
+                },
+                BindingInfo {
+                    resource: ShaderResource::StorageBuffer(particle_buffer),
+                    binding: 1,
+                },
+                BindingInfo {
+                    resource: ShaderResource::Dynamic(&alloc),
+                    binding: 2,
+                },
+                BindingInfo {
+                    resource: ShaderResource::Dynamic(&alloc),
+                    binding: 3,
+                },
+                BindingInfo {
+                    resource: ShaderResource::SampledImage(atlas.view, sampler),
+                    binding: 4,
+                },
+            ],
+            set: 0,
+        })?;
+        let raw_slice = ctx.map_buffer_mut::<ShaderParticle>(particle_buffer)?;
         let ptr = raw_slice.as_mut_ptr();
 
         let s_indices: [u32; 6] = [
@@ -324,27 +479,22 @@ impl ParticleSystem {
         assert!(size_of::<Vec2>() * s_vertices.len() == 32);
         let initial_data = unsafe { s_vertices.align_to::<u8>().1 };
         assert!(initial_data.len() == 32);
-        let vertices = ctx
-            .make_buffer(&BufferInfo {
-                debug_name: "renderer2d-vertices",
-                byte_size: (size_of::<Vec2>() * s_vertices.len()) as u32,
-                visibility: MemoryVisibility::Gpu,
-                usage: BufferUsage::VERTEX,
-                initial_data: unsafe { Some(s_vertices.align_to::<u8>().1) },
-            })
-            .unwrap();
-
-        let indices = ctx
-            .make_buffer(&BufferInfo {
-                debug_name: "renderer2d-indices",
-                byte_size: (size_of::<u32>() * s_indices.len()) as u32,
-                visibility: MemoryVisibility::Gpu,
-                usage: BufferUsage::INDEX,
-                initial_data: unsafe { Some(s_indices.align_to::<u8>().1) },
-            })
-            .unwrap();
-
-        Self {
+        let vertices = ctx.make_buffer(&BufferInfo {
+            debug_name: "renderer2d-vertices",
+            byte_size: (size_of::<Vec2>() * s_vertices.len()) as u32,
+            visibility: MemoryVisibility::Gpu,
+            usage: BufferUsage::VERTEX,
+            initial_data: unsafe { Some(s_vertices.align_to::<u8>().1) },
+        })?;
+
+        let indices = ctx.make_buffer(&BufferInfo {
+            debug_name: "renderer2d-indices",
+            byte_size: (size_of::<u32>() * s_indices.len()) as u32,
+            visibility: MemoryVisibility::Gpu,
+            usage: BufferUsage::INDEX,
+            initial_data: unsafe { Some(s_indices.align_to::<u8>().1) },
+        })?;
+        Ok(Self {
             pipelines,
             dim: [canvas.viewport().area.w, canvas.viewport().area.h],
             timer: Timer::new(),
@@ -354,20 +504,20 @@ impl ParticleSystem {
             curr_particle: 0,
             alloc,
             draw_bg,
-            atlas: image.unwrap(),
+            atlas,
             sampler,
             vertices,
             indices,
             particle_animations: particle_anim_buffer,
             compute_bg,
-        }
+        })
     }
 
     pub fn emit_random(&mut self, info: &ParticleEmitInfo) {
         let mut rng = rand::thread_rng();
 
         let pos = super::screen_to_vulkan(info.position, self.dim[0], self.dim[1]);
-                for id in self.curr_particle..self.curr_particle + info.amount {
+        for id in self.curr_particle..self.curr_particle + info.amount {
             if (id as usize) < self.particle_list.len() {
                 let pos = vec2(
                     pos.x() + random_offset(0.0, 0.2),
@@ -11,6 +11,9 @@ pub struct GraphicsPipelineInfo {
     pub text_bg_layout: Handle<BindGroupLayout>,
     pub text_layout: Handle<GraphicsPipelineLayout>,
     pub text_pipeline: Handle<GraphicsPipeline>,
+
+    pub sdf_text_layout: Handle<GraphicsPipelineLayout>,
+    pub sdf_text_pipeline: Handle<GraphicsPipeline>,
 }
 
 pub fn make_graphics_pipeline(ctx: &mut Context, canvas: &Canvas) -> GraphicsPipelineInfo {
@@ -73,6 +76,7 @@ pub fn make_graphics_pipeline(ctx: &mut Context, canvas: &Canvas) -> GraphicsPip
 layout(location = 0) in vec2 in_position;
 layout(location = 1) in vec2 in_tex;
 layout(location = 0) out vec2 frag_coords;
+layout(location = 1) flat out float frag_premultiplied;
 
 layout(binding = 0) uniform position_offset {
     mat4 transform;
@@ -80,6 +84,7 @@ layout(binding = 0) uniform position_offset {
 
 layout(binding = 1) uniform camera_offset {
     vec2 camera;
+    float premultiplied;
 };
 
 void main() {
@@ -87,6 +92,7 @@ void main() {
     position -= vec4(camera.xy, 0.0, 0.0);
     gl_Position = position;
     frag_coords = in_tex;
+    frag_premultiplied = premultiplied;
 }
 "#,
                         vert
@@ -99,11 +105,16 @@ void main() {
                         r#"
     #version 450 core
     layout(location = 0) in vec2 frag_coords;
+    layout(location = 1) flat in float frag_premultiplied;
     layout(location = 0) out vec4 out_color;
     layout(binding = 2) uniform sampler2D in_image;
 
     void main() { 
         out_color = texture(in_image, frag_coords); 
+        // Blending expects straight alpha. Undoing the premultiply after the lookup still
+        // gets the clean edges of filtering premultiplied texels.
+        if(frag_premultiplied > 0.5 && out_color.a > 0.0)
+            out_color.rgb /= out_color.a;
 //        if(out_color.a < 0.9) 
 //            discard;
     }
@@ -215,12 +226,11 @@ void main() {
     };
 
     void main() { 
-        out_color = texture(in_image, frag_coords); 
-        if(out_color.r < 0.1)
+        float alpha = float(texture(in_image, frag_coords).r) / 255.0;
+        if(alpha <= 0.0)
             discard;
-        else 
-            out_color = vec4(color.xyz, 1.0);
-        
+
+        out_color = vec4(color.xyz, color.w * alpha);
     }
 "#,
                         frag
@@ -241,6 +251,92 @@ void main() {
         })
         .unwrap();
 
+    ///////////////////////////////////////////////////////////////////////////////
+    ///////////////////////////////////////////////////////////////////////////////
+    ///////////////////////////////////////////////////////////////////////////////
+
+    // Same bindings as the text pipeline, but the atlas holds distance fields and is
+    // sampled with linear filtering.
+    let sdf_text_layout = ctx
+        .make_graphics_pipeline_layout(&GraphicsPipelineLayoutInfo {
+            debug_name: "SDF Text GFX Pipeline Layout",
+            vertex_info: VertexDescriptionInfo {
+                entries: &[
+                    VertexEntryInfo {
+                        format: ShaderPrimitiveType::Vec2,
+                        location: 0,
+                        offset: 0,
+                    },
+                    VertexEntryInfo {
+                        format: ShaderPrimitiveType::Vec2,
+                        location: 1,
+                        offset: 8,
+                    },
+                ],
+                stride: 16,
+                rate: VertexRate::Vertex,
+            },
+            bg_layout: text_bg_layout,
+            shaders: &[
+                PipelineShaderInfo {
+                    stage: ShaderType::Vertex,
+                    spirv: inline_spirv::inline_spirv!(
+                        r#"
+#version 450
+layout(location = 0) in vec2 in_position;
+layout(location = 1) in vec2 in_tex;
+
+layout(location = 0) out vec2 frag_coords;
+
+void main() {
+    gl_Position = vec4(in_position.xy, 0.0, 1.0);
+    frag_coords = in_tex;
+}
+"#,
+                        vert
+                    ),
+                    specialization: &[],
+                },
+                PipelineShaderInfo {
+                    stage: ShaderType::Fragment,
+                    spirv: inline_spirv::inline_spirv!(
+                        r#"
+    #version 450 core
+    layout(location = 0) in vec2 frag_coords;
+    layout(location = 0) out vec4 out_color;
+    layout(binding = 2) uniform sampler2D in_image;
+    layout(binding = 1) uniform camera_offset {
+        vec4 color;
+    };
+
+    void main() {
+        // The outline sits at 0.5. Smooth over about a pixel on screen, whatever the scale.
+        float dist = texture(in_image, frag_coords).r;
+        float width = max(fwidth(dist) * 0.75, 1.0 / 255.0);
+        float alpha = smoothstep(0.5 - width, 0.5 + width, dist);
+        if(alpha <= 0.0)
+            discard;
+
+        out_color = vec4(color.xyz, color.w * alpha);
+    }
+"#,
+                        frag
+                    ),
+                    specialization: &[],
+                },
+            ],
+            details: Default::default(),
+        })
+        .expect("Unable to create GFX Pipeline Layout!");
+
+    let sdf_text_pipeline = ctx
+        .make_graphics_pipeline(&dashi::GraphicsPipelineInfo {
+            debug_name: "SDF Text GFX Pipeline",
+            layout: sdf_text_layout,
+            render_pass: canvas.render_pass(),
+        })
+        .unwrap();
+
     GraphicsPipelineInfo {
         bg_layout,
         pipeline_layout,
@@ -248,5 +344,7 @@ void main() {
         text_bg_layout,
         text_layout,
         text_pipeline,
+        sdf_text_layout,
+        sdf_text_pipeline,
     }
 }
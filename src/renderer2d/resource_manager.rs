@@ -1,9 +1,12 @@
 use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
 
 use super::pipeline;
+use super::shared::*;
+use super::tilemap::*;
 use super::types::*;
 use crate::database::*;
-use crate::utils::Canvas;
+use crate::utils::{Canvas, RectPacker};
 use dashi::utils::*;
 use dashi::*;
 pub struct ResourceManager {
@@ -17,14 +20,75 @@ pub struct ResourceManager {
     fonts: Pool<Font>,
     gfx: pipeline::GraphicsPipelineInfo,
     sampler: Handle<Sampler>,
+    linear_sampler: Handle<Sampler>,
+    samplers: HashMap<SamplerKey, Handle<Sampler>>,
     sprite_sheets: Pool<SpriteSheet>,
+    tilemaps: Pool<TileMap>,
+    sprite_keys: HashMap<String, Vec<(Handle<Sprite>, String)>>,
+    sprite_sheet_keys: HashMap<String, Vec<(Handle<SpriteSheet>, String)>>,
+    font_keys: HashMap<String, Vec<(Handle<Font>, String)>>,
+    // Number of sprites drawing from each atlas page.
+    atlas_users: HashMap<Handle<Image>, usize>,
+    // Where each atlas sprite sits on its page and the page's format.
+    atlas_rects: HashMap<Handle<Sprite>, (Rect2D, Format)>,
+    uploads: Vec<PendingUpload>,
+    // Views of single mip levels, made only for the uploads into them.
+    upload_views: Vec<Handle<ImageView>>,
+    frame: usize,
+    garbage: Vec<(usize, Garbage)>,
+    drops: (Sender<Dropped>, Receiver<Dropped>),
 }
 
-#[repr(C)]
-#[derive(Clone, Copy)]
-pub struct Vertex {
-    pub position: [f32; 2],
-    pub tex_coords: [f32; 2],
+/// Frames the renderer keeps in flight. Released resources live at least this long.
+pub const FRAMES_IN_FLIGHT: usize = 3;
+
+enum Garbage {
+    Image(Handle<Image>),
+    View(Handle<ImageView>),
+    BindGroup(Handle<BindGroup>),
+}
+
+type AtlasPage = (Handle<Image>, Handle<ImageView>, Handle<BindGroup>);
+
+// Scratch images are capped so a big batch of regions doesn't need one huge allocation.
+const MAX_SCRATCH_HEIGHT: u32 = 4096;
+
+// Pixels for parts of an existing image, stacked in a scratch image. `upload_images`
+// blits each region over.
+struct PendingUpload {
+    scratch: (Handle<Image>, Handle<ImageView>),
+    dst: Handle<ImageView>,
+    // The region in the scratch image and where it goes in `dst`.
+    regions: Vec<(Rect2D, Rect2D)>,
+}
+
+// Everything shoyu uploads is RGBA8 except coverage glyph pages.
+fn texel_size(format: Format) -> usize {
+    match format {
+        Format::R8Uint => 1,
+        _ => 4,
+    }
+}
+
+// The parts of an entry's texture options its sampler depends on. Textures that sample
+// the same way share one sampler.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct SamplerKey {
+    min_filter: TextureFilter,
+    mag_filter: TextureFilter,
+    mipmaps: bool,
+    wrap: TextureWrap,
+}
+
+impl From<&TextureJSON> for SamplerKey {
+    fn from(texture: &TextureJSON) -> Self {
+        Self {
+            min_filter: texture.min_filter,
+            mag_filter: texture.mag_filter,
+            mipmaps: texture.mipmaps,
+            wrap: texture.wrap,
+        }
+    }
 }
 
 impl ResourceManager {
@@ -52,10 +116,17 @@ impl ResourceManager {
             },
         ];
 
-        let s_indices: [u32; 6] = [
-            0, 1, 2, // First triangle
-            2, 3, 0, // Second triangle
-        ];
+        // Single quads use the first six. Batches like tile map chunks use the rest.
+        let quads = TILEMAP_CHUNK_TILES * TILEMAP_CHUNK_TILES;
+        let s_indices: Vec<u32> = (0..quads)
+            .flat_map(|q| {
+                [
+                    0, 1, 2, // First triangle
+                    2, 3, 0, // Second triangle
+                ]
+                .map(|i| q * 4 + i)
+            })
+            .collect();
 
         let vertices = ctx
             .make_buffer(&BufferInfo {
@@ -97,13 +168,41 @@ impl ResourceManager {
             })
             .expect("Unable to make sampler!");
 
+        // Distance field fonts need interpolated samples.
+        let linear_sampler = ctx
+            .make_sampler(&SamplerInfo {
+                border_color: BorderColor::TransparentBlack,
+                min_filter: Filter::Linear,
+                mag_filter: Filter::Linear,
+                mipmap_mode: SamplerMipmapMode::Nearest,
+                ..Default::default()
+            })
+            .expect("Unable to make sampler!");
+
+        // Entries without texture options sample like they always have.
+        let mut samplers = HashMap::new();
+        samplers.insert(SamplerKey::from(&TextureJSON::default()), sampler);
+
         Self {
             ctx,
             sampler,
+            linear_sampler,
+            samplers,
             database,
             sprites: Default::default(),
             sprite_sheets: Default::default(),
+            tilemaps: Default::default(),
             fonts: Default::default(),
+            sprite_keys: Default::default(),
+            sprite_sheet_keys: Default::default(),
+            font_keys: Default::default(),
+            atlas_users: Default::default(),
+            atlas_rects: Default::default(),
+            uploads: Vec::new(),
+            upload_views: Vec::new(),
+            frame: 0,
+            garbage: Vec::new(),
+            drops: channel(),
             vertices,
             indices,
             allocator,
@@ -120,6 +219,44 @@ impl ResourceManager {
         &self.gfx
     }
 
+    // Makes a sampler the first time a combination of options is asked for.
+    fn sampler_for(&mut self, texture: &TextureJSON) -> Result<Handle<Sampler>, Error> {
+        let key = SamplerKey::from(texture);
+        if let Some(sampler) = self.samplers.get(&key) {
+            return Ok(*sampler);
+        }
+
+        let filter = |f: TextureFilter| match f {
+            TextureFilter::Nearest => Filter::Nearest,
+            TextureFilter::Linear => Filter::Linear,
+        };
+        let wrap = match key.wrap {
+            TextureWrap::Repeat => SamplerAddressMode::Repeat,
+            TextureWrap::MirroredRepeat => SamplerAddressMode::MirroredRepeat,
+            TextureWrap::ClampToEdge => SamplerAddressMode::ClampToEdge,
+            TextureWrap::ClampToBorder => SamplerAddressMode::ClampToBorder,
+        };
+        let mipmap_mode = match key.mipmaps && key.min_filter == TextureFilter::Linear {
+            true => SamplerMipmapMode::Linear,
+            false => SamplerMipmapMode::Nearest,
+        };
+
+        let sampler = unsafe {
+            (*self.ctx).make_sampler(&SamplerInfo {
+                border_color: BorderColor::TransparentBlack,
+                min_filter: filter(key.min_filter),
+                mag_filter: filter(key.mag_filter),
+                mipmap_mode,
+                address_mode_u: wrap,
+                address_mode_v: wrap,
+                address_mode_w: wrap,
+                ..Default::default()
+            })?
+        };
+        self.samplers.insert(key, sampler);
+        Ok(sampler)
+    }
+
     pub fn allocator(&mut self) -> &mut DynamicAllocator {
         &mut self.allocator
     }
@@ -132,6 +269,10 @@ impl ResourceManager {
         self.indices
     }
 
+    pub fn database(&mut self) -> &mut Database {
+        &mut self.database
+    }
+
     pub fn fetch_sprite(&mut self, handle: Handle<Sprite>) -> Option<&mut Sprite> {
         Some(self.sprites.get_mut_ref(handle)?)
     }
@@ -144,217 +285,1081 @@ impl ResourceManager {
         Some(self.sprite_sheets.get_mut_ref(handle)?)
     }
 
+    pub fn fetch_tilemap(&mut self, handle: Handle<TileMap>) -> Option<&mut TileMap> {
+        Some(self.tilemaps.get_mut_ref(handle)?)
+    }
+
     pub fn make_font(&mut self, info: &FontInfo) -> Handle<Font> {
-        let img: *const TTFont = self
+        self.try_make_font(info).unwrap()
+    }
+
+    /// Same as `make_font`, but a font that doesn't load, or a failed GPU allocation, comes
+    /// back as an error.
+    pub fn try_make_font(&mut self, info: &FontInfo) -> Result<Handle<Font>, Error> {
+        let font = self.build_font(info)?;
+        let handle = self.fonts.insert(font).ok_or(Error::SlotError())?;
+        self.font_keys
+            .entry(info.db_key.to_string())
+            .or_default()
+            .push((handle, info.name.to_string()));
+        Ok(handle)
+    }
+
+    fn build_font(&mut self, info: &FontInfo) -> Result<Font, Error> {
+        let font = self
             .database
-            .fetch_ttf(info.db_key)
-            .unwrap()
+            .fetch_ttf(info.db_key)?
             .loaded
-            .as_ref()
-            .unwrap();
+            .clone()
+            .ok_or_else(|| unloaded(info.db_key))?;
+
+        let ttf = font.lock().unwrap();
+        let dim = [ttf.atlas_width, ttf.atlas_height];
+        let sdf = ttf.mode != GlyphMode::Coverage;
+        let pages = ttf
+            .pages
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                self.make_font_page(&format!("{} page {}", info.name, i), dim, sdf, &p.bitmap)
+            })
+            .collect::<Result<_, _>>()?;
+        drop(ttf);
+
+        Ok(Font { dim, pages, font })
+    }
+
+    // Distance field pages go up as RGBA8 since they have to be filterable. Every channel
+    // holds the same value.
+    fn make_font_page(
+        &self,
+        name: &str,
+        dim: [u32; 2],
+        sdf: bool,
+        bitmap: &[u8],
+    ) -> Result<FontPage, Error> {
+        let (format, sampler, bytes) = match sdf {
+            true => (
+                Format::RGBA8,
+                self.linear_sampler,
+                bitmap.iter().flat_map(|&v| [v; 4]).collect(),
+            ),
+            false => (Format::R8Uint, self.sampler, bitmap.to_vec()),
+        };
 
         unsafe {
-            let size = ((*img).atlas_width, (*img).atlas_height);
-            let initial_data = (*img).atlas.as_ref().unwrap();
-            let spr = (*self.ctx)
-                .make_image(&ImageInfo {
-                    debug_name: info.name,
-                    dim: [size.0, size.1, 1],
-                    format: Format::R8Uint,
-                    mip_levels: 1,
-                    initial_data: Some(initial_data),
-                })
-                .unwrap();
+            let image = (*self.ctx).make_image(&ImageInfo {
+                debug_name: name,
+                dim: [dim[0], dim[1], 1],
+                format,
+                mip_levels: 1,
+                initial_data: Some(&bytes),
+            })?;
 
-            let spr_view = (*self.ctx)
-                .make_image_view(&ImageViewInfo {
-                    debug_name: info.name,
-                    img: spr,
-                    ..Default::default()
-                })
-                .unwrap();
-
-            return self
-                .fonts
-                .insert(Font {
-                    dim: [size.0, size.1],
-                    atlas: spr,
-                    atlas_view: spr_view,
-                    bg: (*self.ctx)
-                        .make_bind_group(&BindGroupInfo {
-                            debug_name: info.name,
-                            layout: self.gfx.text_bg_layout,
-                            bindings: &[
-                                BindingInfo {
-                                    resource: ShaderResource::Dynamic(&self.allocator),
-                                    binding: 1,
-                                },
-                                BindingInfo {
-                                    resource: ShaderResource::SampledImage(spr_view, self.sampler),
-                                    binding: 2,
-                                },
-                            ],
-                            ..Default::default()
-                        })
-                        .unwrap(),
-                    font: img,
+            let view = (*self.ctx).make_image_view(&ImageViewInfo {
+                debug_name: name,
+                img: image,
+                ..Default::default()
+            })?;
+
+            let bg = (*self.ctx).make_bind_group(&BindGroupInfo {
+                debug_name: name,
+                layout: self.gfx.text_bg_layout,
+                bindings: &[
+                    BindingInfo {
+                        resource: ShaderResource::Dynamic(&self.allocator),
+                        binding: 1,
+                    },
+                    BindingInfo {
+                        resource: ShaderResource::SampledImage(view, sampler),
+                        binding: 2,
+                    },
+                ],
+                ..Default::default()
+            })?;
+
+            Ok(FontPage {
+                image,
+                view,
+                bg,
+            })
+        }
+    }
+
+    /// Uploads the glyphs fonts rasterized since the last call. Records blit commands, so it
+    /// has to run before the frame's render pass starts. Glyphs drawn for the first time
+    /// therefore show up one frame late.
+    pub fn upload_glyphs(&mut self, cmd: &mut FramedCommandList) {
+        // Every handle made from the same entry shares one TTFont, so drain it once.
+        let keys: Vec<Vec<(Handle<Font>, String)>> = self.font_keys.values().cloned().collect();
+        for fonts in keys {
+            let ttf = match fonts
+                .iter()
+                .find_map(|(h, _)| self.fonts.get_ref(*h).map(|f| f.font.clone()))
+            {
+                Some(f) => f,
+                None => continue,
+            };
+
+            let dirty = ttf.lock().unwrap().take_dirty();
+            if dirty.is_empty() {
+                continue;
+            }
+
+            for (handle, name) in fonts {
+                self.upload_font_regions(handle, &name, &dirty);
+            }
+        }
+
+        self.upload_images(cmd);
+    }
+
+    fn upload_font_regions(
+        &mut self,
+        handle: Handle<Font>,
+        name: &str,
+        dirty: &[(u32, Rect2D)],
+    ) {
+        let (ttf, dim, resident) = match self.fonts.get_ref(handle) {
+            Some(f) => (f.font.clone(), f.dim, f.pages.len()),
+            None => return,
+        };
+
+        let ttf = ttf.lock().unwrap();
+        let glyph_pages = &ttf.pages;
+        let sdf = ttf.mode != GlyphMode::Coverage;
+
+        // New pages are created with their glyphs already in them.
+        let added: Result<Vec<FontPage>, Error> = glyph_pages[resident..]
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let page_name = format!("{} page {}", name, resident + i);
+                self.make_font_page(&page_name, dim, sdf, &p.bitmap)
+            })
+            .collect();
+        let added = match added {
+            Ok(pages) => pages,
+            Err(e) => {
+                println!("Unable to upload glyphs for font {}: {}", name, e);
+                return;
+            }
+        };
+
+        let font = self.fonts.get_mut_ref(handle).unwrap();
+        font.pages.extend(added);
+
+        // Each changed rect goes over on its own instead of the whole page.
+        let (format, texel) = match sdf {
+            true => (Format::RGBA8, 4),
+            false => (Format::R8Uint, 1),
+        };
+        let stride = dim[0] as usize;
+        for (page, glyph_page) in glyph_pages.iter().enumerate().take(resident) {
+            let rects: Vec<(Rect2D, Vec<u8>)> = dirty
+                .iter()
+                .filter(|(p, _)| *p as usize == page)
+                .map(|&(_, rect)| {
+                    let mut pixels = Vec::with_capacity((rect.w * rect.h) as usize * texel);
+                    for row in rect.y as usize..(rect.y + rect.h) as usize {
+                        let offset = row * stride + rect.x as usize;
+                        let src = &glyph_page.bitmap[offset..offset + rect.w as usize];
+                        pixels.extend(src.iter().flat_map(|&v| std::iter::repeat_n(v, texel)));
+                    }
+                    (rect, pixels)
                 })
-                .unwrap();
+                .collect();
+            if rects.is_empty() {
+                continue;
+            }
+
+            let view = self.fonts.get_ref(handle).unwrap().pages[page].view;
+            let regions: Vec<(Rect2D, &[u8])> =
+                rects.iter().map(|(r, p)| (*r, p.as_slice())).collect();
+            let page_name = format!("{} page {}", name, page);
+            if let Err(e) = self.queue_upload(&page_name, view, format, &regions) {
+                println!("Unable to upload glyphs for font {}: {}", name, e);
+            }
         }
     }
 
     pub fn make_sprite(&mut self, info: &SpriteInfo) -> Handle<Sprite> {
+        self.try_make_sprite(info).unwrap()
+    }
+
+    /// Same as `make_sprite`, but an image that doesn't load, or a failed GPU allocation,
+    /// comes back as an error.
+    pub fn try_make_sprite(&mut self, info: &SpriteInfo) -> Result<Handle<Sprite>, Error> {
+        let sprite = self.build_sprite(info)?;
+        let handle = self.sprites.insert(sprite).ok_or(Error::SlotError())?;
+        self.sprite_keys
+            .entry(info.db_key.to_string())
+            .or_default()
+            .push((handle, info.name.to_string()));
+        Ok(handle)
+    }
+
+    fn build_sprite(&mut self, info: &SpriteInfo) -> Result<Sprite, Error> {
+        let texture = self.database.fetch_sprite(info.db_key)?.texture();
+        let sampler = self.sampler_for(&texture)?;
         let img = self
             .database
-            .fetch_sprite(info.db_key)
-            .unwrap()
+            .fetch_sprite(info.db_key)?
             .loaded
             .as_ref()
-            .unwrap();
+            .ok_or_else(|| unloaded(info.db_key))?;
+        let (size, format, mips) = (img.size, img.format, img.mips.clone());
         unsafe {
-            let spr = (*self.ctx)
-                .make_image(&ImageInfo {
-                    debug_name: info.name,
-                    dim: [img.size[0], img.size[1], 1],
-                    format: img.format,
-                    mip_levels: 1,
-                    initial_data: Some(&img.bytes),
-                })
-                .unwrap();
+            let spr = (*self.ctx).make_image(&ImageInfo {
+                debug_name: info.name,
+                dim: [img.size[0], img.size[1], 1],
+                format: img.format,
+                mip_levels: img.mip_levels(),
+                initial_data: Some(&img.bytes),
+            })?;
+            self.queue_mips(info.name, spr, size, format, &mips)?;
 
-            let spr_view = (*self.ctx)
-                .make_image_view(&ImageViewInfo {
-                    debug_name: info.name,
-                    img: spr,
-                    ..Default::default()
-                })
-                .unwrap();
+            let spr_view = (*self.ctx).make_image_view(&ImageViewInfo {
+                debug_name: info.name,
+                img: spr,
+                ..Default::default()
+            })?;
+
+            let bg = (*self.ctx).make_bind_group(&BindGroupInfo {
+                debug_name: info.name,
+                layout: self.gfx.bg_layout,
+                bindings: &[
+                    BindingInfo {
+                        resource: ShaderResource::Dynamic(&self.allocator),
+                        binding: 0,
+                    },
+                    BindingInfo {
+                        resource: ShaderResource::Dynamic(&self.allocator),
+                        binding: 1,
+                    },
+                    BindingInfo {
+                        resource: ShaderResource::SampledImage(spr_view, sampler),
+                        binding: 2,
+                    },
+                ],
+                ..Default::default()
+            })?;
+
+            Ok(Sprite {
+                premultiplied: texture.premultiply_alpha,
+                dim: size,
+                handle: spr,
+                view: spr_view,
+                bg,
+                uv: FRect2D {
+                    x: 0.0,
+                    y: 0.0,
+                    w: 1.0,
+                    h: 1.0,
+                },
+            })
+        }
+    }
+
+    // Pages can't have mipmaps, they'd bleed neighbouring sprites into each other.
+    fn make_atlas_page(
+        &mut self,
+        name: &str,
+        dim: [u32; 2],
+        bytes: &[u8],
+        texture: &TextureJSON,
+    ) -> Result<AtlasPage, Error> {
+        let sampler = self.sampler_for(&TextureJSON {
+            mipmaps: false,
+            ..*texture
+        })?;
+        unsafe {
+            let img = (*self.ctx).make_image(&ImageInfo {
+                debug_name: name,
+                dim: [dim[0], dim[1], 1],
+                format: texture.format(),
+                mip_levels: 1,
+                initial_data: Some(bytes),
+            })?;
+
+            let view = (*self.ctx).make_image_view(&ImageViewInfo {
+                debug_name: name,
+                img,
+                ..Default::default()
+            })?;
+
+            let bg = (*self.ctx).make_bind_group(&BindGroupInfo {
+                debug_name: name,
+                layout: self.gfx.bg_layout,
+                bindings: &[
+                    BindingInfo {
+                        resource: ShaderResource::Dynamic(&self.allocator),
+                        binding: 0,
+                    },
+                    BindingInfo {
+                        resource: ShaderResource::Dynamic(&self.allocator),
+                        binding: 1,
+                    },
+                    BindingInfo {
+                        resource: ShaderResource::SampledImage(view, sampler),
+                        binding: 2,
+                    },
+                ],
+                ..Default::default()
+            })?;
+
+            Ok((img, view, bg))
+        }
+    }
+
+    /// Packs the given database sprites into as few atlas pages as possible. Returns one
+    /// handle per key, in order. The handles draw exactly like ones from `make_sprite`.
+    /// Sprites only share a page with sprites that have the same texture options. Pages
+    /// never have mipmaps.
+    pub fn make_sprite_atlas(&mut self, info: &SpriteAtlasInfo) -> Vec<Handle<Sprite>> {
+        self.try_make_sprite_atlas(info).unwrap()
+    }
+
+    /// Same as `make_sprite_atlas`, but fails as a whole if any of the images doesn't load.
+    pub fn try_make_sprite_atlas(
+        &mut self,
+        info: &SpriteAtlasInfo,
+    ) -> Result<Vec<Handle<Sprite>>, Error> {
+        // Each image is fetched once, fetching again could have the budget evict it.
+        let mut sprites: Vec<(TextureJSON, [u32; 2], Vec<u8>)> = Vec::new();
+        for k in info.db_keys {
+            let entry = self.database.fetch_sprite(k)?;
+            let texture = TextureJSON {
+                mipmaps: false,
+                ..entry.texture()
+            };
+            let img = entry.loaded.as_ref().ok_or_else(|| unloaded(k))?;
+            sprites.push((texture, img.size, img.bytes.clone()));
+        }
 
-            return self
+        // Tallest first keeps the skyline flat.
+        let mut order: Vec<usize> = (0..sprites.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(sprites[i].1[1]));
+
+        let mut pages: Vec<(RectPacker, Vec<u8>, TextureJSON)> = Vec::new();
+        let mut placements = vec![(0, Rect2D::default()); sprites.len()];
+        for i in order {
+            let (texture, [w, h], ref pixels) = sprites[i];
+            let spot = pages
+                .iter_mut()
+                .enumerate()
+                .filter(|(_, (_, _, t))| *t == texture)
+                .find_map(|(p, (packer, _, _))| Some((p, packer.insert(w, h)?)));
+
+            let (page, rect) = match spot {
+                Some(s) => s,
+                None => {
+                    // Oversized sprites get a page of their own.
+                    let pw = info.page_size[0].max(w + info.padding);
+                    let ph = info.page_size[1].max(h + info.padding);
+                    let mut packer = RectPacker::new(pw, ph, info.padding);
+                    let rect = packer.insert(w, h).unwrap();
+                    pages.push((packer, vec![0; (pw * ph * 4) as usize], texture));
+                    (pages.len() - 1, rect)
+                }
+            };
+
+            let (packer, bytes, _) = &mut pages[page];
+            let stride = packer.width() as usize * 4;
+            let row_len = w as usize * 4;
+            for row in 0..h as usize {
+                let dst = (rect.y as usize + row) * stride + rect.x as usize * 4;
+                bytes[dst..dst + row_len]
+                    .copy_from_slice(&pixels[row * row_len..(row + 1) * row_len]);
+            }
+
+            placements[i] = (page, rect);
+        }
+
+        let uploaded: Vec<_> = pages
+            .iter()
+            .enumerate()
+            .map(|(p, (packer, bytes, texture))| {
+                let name = format!("{} page {}", info.name, p);
+                let dim = [packer.width(), packer.height()];
+                Ok((self.make_atlas_page(&name, dim, bytes, texture)?, dim))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let mut handles = Vec::with_capacity(sprites.len());
+        for (i, (page, rect)) in placements.into_iter().enumerate() {
+            let ((img, view, bg), dim) = uploaded[page];
+            let texture = pages[page].2;
+            let handle = self
                 .sprites
                 .insert(Sprite {
-                    dim: [img.size[0], img.size[1]],
-                    handle: spr,
-                    view: spr_view,
-                    bg: (*self.ctx)
-                        .make_bind_group(&BindGroupInfo {
-                            debug_name: info.name,
-                            layout: self.gfx.bg_layout,
-                            bindings: &[
-                                BindingInfo {
-                                    resource: ShaderResource::Dynamic(&self.allocator),
-                                    binding: 0,
-                                },
-                                BindingInfo {
-                                    resource: ShaderResource::Dynamic(&self.allocator),
-                                    binding: 1,
-                                },
-                                BindingInfo {
-                                    resource: ShaderResource::SampledImage(spr_view, self.sampler),
-                                    binding: 2,
-                                },
-                            ],
-                            ..Default::default()
-                        })
-                        .unwrap(),
+                    dim: [rect.w, rect.h],
+                    handle: img,
+                    view,
+                    bg,
+                    uv: FRect2D {
+                        x: rect.x as f32 / dim[0] as f32,
+                        y: rect.y as f32 / dim[1] as f32,
+                        w: (rect.x + rect.w) as f32 / dim[0] as f32,
+                        h: (rect.y + rect.h) as f32 / dim[1] as f32,
+                    },
+                    premultiplied: texture.premultiply_alpha,
                 })
-                .unwrap();
+                .ok_or(Error::SlotError())?;
+            *self.atlas_users.entry(img).or_default() += 1;
+            self.atlas_rects.insert(handle, (rect, texture.format()));
+
+            self.sprite_keys
+                .entry(info.db_keys[i].to_string())
+                .or_default()
+                .push((handle, format!("{} {}", info.name, info.db_keys[i])));
+            handles.push(handle);
         }
+
+        Ok(handles)
     }
 
     pub fn make_sprite_sheet(&mut self, info: &SpriteSheetInfo) -> Handle<SpriteSheet> {
-        let mut hashed = HashMap::new();
-        {
+        self.try_make_sprite_sheet(info).unwrap()
+    }
+
+    /// Same as `make_sprite_sheet`, but a sheet that doesn't load, or a failed GPU
+    /// allocation, comes back as an error.
+    pub fn try_make_sprite_sheet(
+        &mut self,
+        info: &SpriteSheetInfo,
+    ) -> Result<Handle<SpriteSheet>, Error> {
+        let sheet = self.build_sprite_sheet(info)?;
+        let handle = self.sprite_sheets.insert(sheet).ok_or(Error::SlotError())?;
+        self.sprite_sheet_keys
+            .entry(info.db_key.to_string())
+            .or_default()
+            .push((handle, info.name.to_string()));
+        Ok(handle)
+    }
+
+    fn build_sprite_sheet(&mut self, info: &SpriteSheetInfo) -> Result<SpriteSheet, Error> {
+        let (hashed, layouts) = {
             let dim = self
                 .database
-                .fetch_sprite_sheet(info.db_key)
-                .unwrap()
+                .fetch_sprite_sheet(info.db_key)?
                 .loaded
                 .as_ref()
-                .unwrap()
+                .ok_or_else(|| unloaded(info.db_key))?
                 .size;
 
-            let sprites = &self.database.fetch_sprite_sheet(info.db_key).unwrap().cfg;
-            if let Some(spr) = sprites.sprites.as_ref() {
-                hashed = spr
-                    .into_iter()
-                    .map(|x| {
-                        (
-                            x.id,
-                            FRect2D {
-                                x: x.bounds.x as f32 / dim[0] as f32,
-                                y: x.bounds.y as f32 / dim[1] as f32,
-                                w: x.bounds.x as f32 / dim[0] as f32 + x.bounds.w as f32 / dim[0] as f32,
-                                h: x.bounds.y as f32 / dim[1] as f32 + x.bounds.h as f32 / dim[1] as f32,
-                            },
-                        )
-                    })
-                    .collect::<HashMap<u32, FRect2D>>();
-            }
+            let sprites = self.database.fetch_sprite_sheet(info.db_key)?.sprites();
+            let layouts = sprites
+                .iter()
+                .filter(|x| x.trim.is_some() || x.rotated == Some(true))
+                .map(|x| {
+                    let rotated = x.rotated == Some(true);
+                    let (w, h) = match rotated {
+                        true => (x.bounds.h as f32, x.bounds.w as f32),
+                        false => (x.bounds.w as f32, x.bounds.h as f32),
+                    };
+
+                    let (offset, source) = match x.trim.as_ref() {
+                        Some(t) => (
+                            [t.offset[0] as f32, t.offset[1] as f32],
+                            [t.source_size[0] as f32, t.source_size[1] as f32],
+                        ),
+                        None => ([0.0, 0.0], [w, h]),
+                    };
+
+                    (
+                        x.id,
+                        SpriteFrameLayout {
+                            offset: [offset[0] / source[0], offset[1] / source[1]],
+                            size: [w / source[0], h / source[1]],
+                            rotated,
+                        },
+                    )
+                })
+                .collect::<HashMap<u32, SpriteFrameLayout>>();
+
+            let hashed = sprites
+                .into_iter()
+                .map(|x| {
+                    (
+                        x.id,
+                        FRect2D {
+                            x: x.bounds.x as f32 / dim[0] as f32,
+                            y: x.bounds.y as f32 / dim[1] as f32,
+                            w: x.bounds.x as f32 / dim[0] as f32 + x.bounds.w as f32 / dim[0] as f32,
+                            h: x.bounds.y as f32 / dim[1] as f32 + x.bounds.h as f32 / dim[1] as f32,
+                        },
+                    )
+                })
+                .collect::<HashMap<u32, FRect2D>>();
+
+            (hashed, layouts)
+        };
+        if hashed.is_empty() {
+            return Err(Error::loading("sprite sheet has no sprites").with_entry(info.db_key));
         }
-        assert!(!hashed.is_empty());
+
+        let animations = self
+            .database
+            .fetch_sprite_sheet(info.db_key)?
+            .cfg
+            .animations
+            .iter()
+            .flatten()
+            .map(|a| (a.name.clone(), a.clone()))
+            .collect();
+        let texture = self.database.fetch_sprite_sheet(info.db_key)?.texture();
+        let sampler = self.sampler_for(&texture)?;
 
         unsafe {
             let img = self
                 .database
-                .fetch_sprite_sheet(info.db_key)
-                .unwrap()
+                .fetch_sprite_sheet(info.db_key)?
                 .loaded
                 .as_ref()
-                .unwrap();
+                .ok_or_else(|| unloaded(info.db_key))?;
+            let (size, format, mips) = (img.size, img.format, img.mips.clone());
+
+            let spr = (*self.ctx).make_image(&ImageInfo {
+                debug_name: info.name,
+                dim: [img.size[0], img.size[1], 1],
+                format: img.format,
+                mip_levels: img.mip_levels(),
+                initial_data: Some(&img.bytes),
+            })?;
+            self.queue_mips(info.name, spr, size, format, &mips)?;
+
+            let spr_view = (*self.ctx).make_image_view(&ImageViewInfo {
+                debug_name: info.name,
+                img: spr,
+                ..Default::default()
+            })?;
+
+            let bg = (*self.ctx).make_bind_group(&BindGroupInfo {
+                debug_name: info.name,
+                layout: self.gfx.bg_layout,
+                bindings: &[
+                    BindingInfo {
+                        resource: ShaderResource::Dynamic(&self.allocator),
+                        binding: 0,
+                    },
+                    BindingInfo {
+                        resource: ShaderResource::Dynamic(&self.allocator),
+                        binding: 1,
+                    },
+                    BindingInfo {
+                        resource: ShaderResource::SampledImage(spr_view, sampler),
+                        binding: 2,
+                    },
+                ],
+                ..Default::default()
+            })?;
+
+            Ok(SpriteSheet {
+                premultiplied: texture.premultiply_alpha,
+                dim: size,
+                handle: spr,
+                sprites: hashed,
+                layouts,
+                animations,
+                view: spr_view,
+                bg,
+            })
+        }
+    }
+
+    /// Makes a sprite sheet for each of the map's tilesets and cuts its tile layers into
+    /// chunks for `Renderer2D::draw_tilemap`. Editing the map needs a new handle, but the
+    /// tileset images hot reload like any sprite sheet.
+    pub fn make_tilemap(&mut self, info: &TileMapInfo) -> Handle<TileMap> {
+        self.try_make_tilemap(info).unwrap()
+    }
+
+    /// Same as `make_tilemap`, but a map or tileset that doesn't load comes back as an error.
+    pub fn try_make_tilemap(&mut self, info: &TileMapInfo) -> Result<Handle<TileMap>, Error> {
+        let map = self.database.fetch_tilemap(info.db_key)?.clone();
+
+        let mut sheets = Vec::new();
+        for tileset in &map.tilesets {
+            let name = format!("{} {}", info.name, tileset.name);
+            match self.try_make_sprite_sheet(&SpriteSheetInfo {
+                name: &name,
+                db_key: &tileset.sheet,
+            }) {
+                Ok(sheet) => sheets.push(sheet),
+                Err(e) => {
+                    for sheet in sheets {
+                        self.release_sprite_sheet(sheet);
+                    }
+                    return Err(e.with_entry(info.db_key));
+                }
+            }
+        }
+
+        let chunks = {
+            let frames: Vec<&HashMap<u32, FRect2D>> = sheets
+                .iter()
+                .map(|s| &self.sprite_sheets.get_ref(*s).unwrap().sprites)
+                .collect();
+            build_tilemap_chunks(&map, &frames)
+        };
+
+        let tilemap = TileMap {
+            size: [
+                (map.size[0] * map.tile_size[0]) as f32,
+                (map.size[1] * map.tile_size[1]) as f32,
+            ],
+            sheets,
+            layers: map
+                .layers
+                .iter()
+                .map(|l| (l.name().to_string(), l.visible()))
+                .collect(),
+            chunks,
+        };
+
+        self.tilemaps.insert(tilemap).ok_or(Error::SlotError())
+    }
+
+    /// Non-blocking variant of `make_sprite`. Queues a background load for the entry and
+    /// only creates the GPU resources once the database reports it as loaded.
+    pub fn make_sprite_if_ready(
+        &mut self,
+        info: &SpriteInfo,
+    ) -> Option<Result<Handle<Sprite>, Error>> {
+        self.database.poll_loads();
+        if !self.database.is_sprite_loaded(info.db_key) {
+            return self.request_pending(AssetKind::Sprite, info.db_key);
+        }
+
+        Some(self.try_make_sprite(info))
+    }
+
+    /// Non-blocking variant of `make_sprite_sheet`.
+    pub fn make_sprite_sheet_if_ready(
+        &mut self,
+        info: &SpriteSheetInfo,
+    ) -> Option<Result<Handle<SpriteSheet>, Error>> {
+        self.database.poll_loads();
+        if !self.database.is_sprite_sheet_loaded(info.db_key) {
+            return self.request_pending(AssetKind::SpriteSheet, info.db_key);
+        }
+
+        Some(self.try_make_sprite_sheet(info))
+    }
+
+    /// Non-blocking variant of `make_font`.
+    pub fn make_font_if_ready(&mut self, info: &FontInfo) -> Option<Result<Handle<Font>, Error>> {
+        self.database.poll_loads();
+        if !self.database.is_ttf_loaded(info.db_key) {
+            return self.request_pending(AssetKind::TTF, info.db_key);
+        }
+
+        Some(self.try_make_font(info))
+    }
+
+    // Queues a load for an entry that isn't resident yet. A failed load is reported instead
+    // of being queued again every frame.
+    fn request_pending<T>(&mut self, kind: AssetKind, name: &str) -> Option<Result<T, Error>> {
+        if let Some(e) = self.database.load_failure(kind, name) {
+            return Some(Err(e));
+        }
+
+        let requested = match kind {
+            AssetKind::Sprite => self.database.request_sprite(name),
+            AssetKind::SpriteSheet => self.database.request_sprite_sheet(name),
+            AssetKind::TTF => self.database.request_ttf(name),
+            AssetKind::Sound => self.database.request_sound(name),
+        };
+
+        match requested {
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    /// Polls the database for edited assets and rebuilds the GPU resources of every handle
+    /// made from them, so existing handles keep working. No-op unless
+    /// `Database::enable_hot_reload` was called.
+    pub fn hot_reload(&mut self) {
+        let reloaded = match self.database.poll_hot_reload() {
+            Ok(r) => r,
+            Err(e) => {
+                println!("Hot reload failed: {}", e);
+                return;
+            }
+        };
+
+        // Replaced resources are released like any other. A handle whose rebuild fails keeps
+        // drawing what it drew before.
+        for entry in reloaded {
+            match entry.kind {
+                AssetKind::Sprite => {
+                    for (handle, name) in self
+                        .sprite_keys
+                        .get(&entry.name)
+                        .cloned()
+                        .unwrap_or_default()
+                    {
+                        if let Some(&(rect, format)) = self.atlas_rects.get(&handle) {
+                            self.reload_atlas_sprite(handle, &name, &entry.name, rect, format);
+                            continue;
+                        }
+
+                        let sprite = match self.build_sprite(&SpriteInfo {
+                            name: &name,
+                            db_key: &entry.name,
+                        }) {
+                            Ok(sprite) => sprite,
+                            Err(e) => {
+                                println!("Hot reload of {} failed: {}", name, e);
+                                continue;
+                            }
+                        };
+                        if let Some(slot) = self.sprites.get_mut_ref(handle) {
+                            let old = std::mem::replace(slot, sprite);
+                            self.release_image((old.handle, old.view, old.bg));
+                        }
+                    }
+                }
+                AssetKind::SpriteSheet => {
+                    for (handle, name) in self
+                        .sprite_sheet_keys
+                        .get(&entry.name)
+                        .cloned()
+                        .unwrap_or_default()
+                    {
+                        let sheet = match self.build_sprite_sheet(&SpriteSheetInfo {
+                            name: &name,
+                            db_key: &entry.name,
+                        }) {
+                            Ok(sheet) => sheet,
+                            Err(e) => {
+                                println!("Hot reload of {} failed: {}", name, e);
+                                continue;
+                            }
+                        };
+                        if let Some(slot) = self.sprite_sheets.get_mut_ref(handle) {
+                            let old = std::mem::replace(slot, sheet);
+                            self.release_image((old.handle, old.view, old.bg));
+                        }
+                    }
+                }
+                AssetKind::TTF => {
+                    for (handle, name) in self
+                        .font_keys
+                        .get(&entry.name)
+                        .cloned()
+                        .unwrap_or_default()
+                    {
+                        let font = match self.build_font(&FontInfo {
+                            name: &name,
+                            db_key: &entry.name,
+                        }) {
+                            Ok(font) => font,
+                            Err(e) => {
+                                println!("Hot reload of {} failed: {}", name, e);
+                                continue;
+                            }
+                        };
+                        if let Some(slot) = self.fonts.get_mut_ref(handle) {
+                            let old = std::mem::replace(slot, font);
+                            self.release_font_pages(old.pages);
+                        }
+                    }
+                }
+                // The GPU never sees sounds.
+                AssetKind::Sound => {}
+            }
+        }
+    }
+
+    // Atlas sprites share their page, so the new pixels go into the sprite's spot on it. An
+    // image that changed size doesn't fit there anymore and needs a new atlas.
+    fn reload_atlas_sprite(
+        &mut self,
+        handle: Handle<Sprite>,
+        name: &str,
+        db_key: &str,
+        rect: Rect2D,
+        format: Format,
+    ) {
+        let view = match self.sprites.get_ref(handle) {
+            Some(s) => s.view,
+            None => return,
+        };
+        let bytes = match self.database.fetch_sprite(db_key).map(|e| e.loaded.as_ref()) {
+            Ok(Some(img)) if img.size == [rect.w, rect.h] => img.bytes.clone(),
+            Ok(_) => {
+                println!("{} changed size, rebuild its atlas to see the change", name);
+                return;
+            }
+            Err(e) => {
+                println!("Hot reload of {} failed: {}", name, e);
+                return;
+            }
+        };
+
+        if let Err(e) = self.queue_upload(name, view, format, &[(rect, &bytes)]) {
+            println!("Hot reload of {} failed: {}", name, e);
+        }
+    }
+
+    // Stacks the regions' pixels in scratch images and queues one blit per region into
+    // `dst`. The pixels of a region are tightly packed rows in `dst`'s format.
+    fn queue_upload(
+        &mut self,
+        name: &str,
+        dst: Handle<ImageView>,
+        format: Format,
+        regions: &[(Rect2D, &[u8])],
+    ) -> Result<(), Error> {
+        let texel = texel_size(format);
+        let regions: Vec<_> = regions.iter().filter(|(r, _)| r.w > 0 && r.h > 0).collect();
 
-            let spr = (*self.ctx)
-                .make_image(&ImageInfo {
-                    debug_name: info.name,
-                    dim: [img.size[0], img.size[1], 1],
-                    format: img.format,
+        // A new scratch image starts whenever the current one would get too tall.
+        let mut chunks: Vec<&[&(Rect2D, &[u8])]> = Vec::new();
+        let (mut start, mut height) = (0, 0);
+        for (i, (rect, _)) in regions.iter().enumerate() {
+            if i > start && height + rect.h > MAX_SCRATCH_HEIGHT {
+                chunks.push(&regions[start..i]);
+                (start, height) = (i, 0);
+            }
+            height += rect.h;
+        }
+        if start < regions.len() {
+            chunks.push(&regions[start..]);
+        }
+
+        for chunk in chunks {
+            let width = chunk.iter().map(|(r, _)| r.w).max().unwrap_or(0);
+            let height: u32 = chunk.iter().map(|(r, _)| r.h).sum();
+            let mut bytes = vec![0u8; (width * height) as usize * texel];
+            let mut blits = Vec::with_capacity(chunk.len());
+            let mut y = 0;
+            for (rect, pixels) in chunk.iter() {
+                let row = rect.w as usize * texel;
+                for r in 0..rect.h as usize {
+                    let start = (y as usize + r) * width as usize * texel;
+                    bytes[start..start + row].copy_from_slice(&pixels[r * row..(r + 1) * row]);
+                }
+
+                let src = Rect2D {
+                    x: 0,
+                    y,
+                    w: rect.w,
+                    h: rect.h,
+                };
+                blits.push((src, *rect));
+                y += rect.h;
+            }
+
+            let scratch_name = format!("{} upload", name);
+            let scratch = unsafe {
+                let img = (*self.ctx).make_image(&ImageInfo {
+                    debug_name: &scratch_name,
+                    dim: [width, height, 1],
+                    format,
                     mip_levels: 1,
-                    initial_data: Some(&img.bytes),
-                })
-                .unwrap();
+                    initial_data: Some(&bytes),
+                })?;
+                let view = (*self.ctx).make_image_view(&ImageViewInfo {
+                    debug_name: &scratch_name,
+                    img,
+                    ..Default::default()
+                })?;
+                (img, view)
+            };
+
+            self.uploads.push(PendingUpload {
+                scratch,
+                dst,
+                regions: blits,
+            });
+        }
 
-            let spr_view = (*self.ctx)
-                .make_image_view(&ImageViewInfo {
-                    debug_name: info.name,
-                    img: spr,
+        Ok(())
+    }
+
+    // `make_image` only fills the first level, the rest go in through a view of each.
+    fn queue_mips(
+        &mut self,
+        name: &str,
+        image: Handle<Image>,
+        size: [u32; 2],
+        format: Format,
+        mips: &[Vec<u8>],
+    ) -> Result<(), Error> {
+        for (i, pixels) in mips.iter().enumerate() {
+            let mip_level = i as u32 + 1;
+            let view = unsafe {
+                (*self.ctx).make_image_view(&ImageViewInfo {
+                    debug_name: name,
+                    img: image,
+                    mip_level,
                     ..Default::default()
-                })
-                .unwrap();
-
-            return self
-                .sprite_sheets
-                .insert(SpriteSheet {
-                    dim: [img.size[0], img.size[1]],
-                    handle: spr,
-                    sprites: hashed,
-                    view: spr_view,
-                    bg: (*self.ctx)
-                        .make_bind_group(&BindGroupInfo {
-                            debug_name: info.name,
-                            layout: self.gfx.bg_layout,
-                            bindings: &[
-                                BindingInfo {
-                                    resource: ShaderResource::Dynamic(&self.allocator),
-                                    binding: 0,
-                                },
-                                BindingInfo {
-                                    resource: ShaderResource::Dynamic(&self.allocator),
-                                    binding: 1,
-                                },
-                                BindingInfo {
-                                    resource: ShaderResource::SampledImage(spr_view, self.sampler),
-                                    binding: 2,
-                                },
-                            ],
-                            ..Default::default()
-                        })
-                        .unwrap(),
-                })
-                .unwrap();
+                })?
+            };
+            self.upload_views.push(view);
+
+            let [w, h] = size.map(|s| (s >> mip_level).max(1));
+            self.queue_upload(name, view, format, &[(Rect2D { x: 0, y: 0, w, h }, pixels)])?;
+        }
+        Ok(())
+    }
+
+    /// Blits pixels queued since the last call into the images they belong to. Records
+    /// commands, so like `upload_glyphs` it has to run before the frame's render pass.
+    pub fn upload_images(&mut self, cmd: &mut FramedCommandList) {
+        for upload in std::mem::take(&mut self.uploads) {
+            let (img, view) = upload.scratch;
+            cmd.append(|list| {
+                for &(src_region, dst_region) in &upload.regions {
+                    list.blit(ImageBlit {
+                        src: view,
+                        dst: upload.dst,
+                        filter: Filter::Nearest,
+                        src_region,
+                        dst_region,
+                    });
+                }
+            });
+
+            // The frame that copies from the scratch image has to finish first.
+            self.defer(Garbage::View(view));
+            self.defer(Garbage::Image(img));
+        }
+
+        for view in std::mem::take(&mut self.upload_views) {
+            self.defer(Garbage::View(view));
+        }
+    }
+
+    /// Wraps a handle so it's released once the last clone of the result is dropped. The
+    /// plain handle must not be released by hand afterwards.
+    pub fn share<T: SharedResource>(&self, handle: Handle<T>) -> Shared<T> {
+        Shared::new(handle, self.drops.0.clone())
+    }
+
+    /// Frees the sprite. Its GPU resources are destroyed once the frames in flight are done
+    /// with them; atlas pages once the last sprite on them is released.
+    pub fn release_sprite(&mut self, handle: Handle<Sprite>) {
+        let (img, view, bg) = match self.sprites.get_ref(handle) {
+            Some(s) => (s.handle, s.view, s.bg),
+            None => return,
+        };
+
+        self.sprites.release(handle);
+        self.atlas_rects.remove(&handle);
+        forget(&mut self.sprite_keys, handle);
+        self.release_image((img, view, bg));
+    }
+
+    pub fn release_sprite_sheet(&mut self, handle: Handle<SpriteSheet>) {
+        let (img, view, bg) = match self.sprite_sheets.get_ref(handle) {
+            Some(s) => (s.handle, s.view, s.bg),
+            None => return,
+        };
+
+        self.sprite_sheets.release(handle);
+        forget(&mut self.sprite_sheet_keys, handle);
+        self.release_image((img, view, bg));
+    }
+
+    /// Frees the map along with the sprite sheets made for its tilesets.
+    pub fn release_tilemap(&mut self, handle: Handle<TileMap>) {
+        let sheets = match self.tilemaps.get_ref(handle) {
+            Some(m) => m.sheets.clone(),
+            None => return,
+        };
+
+        self.tilemaps.release(handle);
+        for sheet in sheets {
+            self.release_sprite_sheet(sheet);
+        }
+    }
+
+    /// Frees the font's glyph pages. The database keeps the font itself.
+    pub fn release_font(&mut self, handle: Handle<Font>) {
+        let pages = match self.fonts.get_mut_ref(handle) {
+            Some(f) => std::mem::take(&mut f.pages),
+            None => return,
+        };
+
+        self.fonts.release(handle);
+        forget(&mut self.font_keys, handle);
+        self.release_font_pages(pages);
+    }
+
+    fn release_image(&mut self, (img, view, bg): AtlasPage) {
+        if let Some(users) = self.atlas_users.get_mut(&img) {
+            *users -= 1;
+            if *users > 0 {
+                return;
+            }
+
+            self.atlas_users.remove(&img);
+        }
+
+        self.defer(Garbage::BindGroup(bg));
+        self.defer(Garbage::View(view));
+        self.defer(Garbage::Image(img));
+    }
+
+    fn release_font_pages(&mut self, pages: Vec<FontPage>) {
+        for page in pages {
+            self.defer(Garbage::BindGroup(page.bg));
+            self.defer(Garbage::View(page.view));
+            self.defer(Garbage::Image(page.image));
         }
     }
 
-    pub fn release_sprite(&mut self, _handle: Handle<Sprite>) {}
+    fn defer(&mut self, garbage: Garbage) {
+        self.garbage.push((self.frame, garbage));
+    }
+
+    /// Releases whatever lost its last `Shared` handle and destroys resources that no frame
+    /// in flight can still be using. `Renderer2D` calls this at the start of every frame.
+    pub fn collect_garbage(&mut self) {
+        while let Ok(dropped) = self.drops.1.try_recv() {
+            match dropped {
+                Dropped::Sprite(h) => self.release_sprite(h),
+                Dropped::SpriteSheet(h) => self.release_sprite_sheet(h),
+                Dropped::Font(h) => self.release_font(h),
+                Dropped::TileMap(h) => self.release_tilemap(h),
+            }
+        }
+
+        // A frame's commands are only known to be done once its slot in the command list
+        // comes around again, which takes one frame more than the number in flight.
+        self.frame += 1;
+        let frame = self.frame;
+        let (done, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.garbage)
+            .into_iter()
+            .partition(|(released, _)| frame > released + FRAMES_IN_FLIGHT);
+        self.garbage = pending;
+
+        for (_, garbage) in done {
+            unsafe {
+                match garbage {
+                    Garbage::Image(h) => (*self.ctx).destroy_image(h),
+                    Garbage::View(h) => (*self.ctx).destroy_image_view(h),
+                    Garbage::BindGroup(h) => (*self.ctx).destroy_bind_group(h),
+                }
+            }
+        }
+    }
+}
+
+// Drops a released handle from the hot reload bookkeeping.
+fn forget<T>(keys: &mut HashMap<String, Vec<(Handle<T>, String)>>, handle: Handle<T>) {
+    for handles in keys.values_mut() {
+        handles.retain(|(h, _)| *h != handle);
+    }
+
+    keys.retain(|_, handles| !handles.is_empty());
+}
 
-    pub fn release_sprite_sheet(&mut self, _handle: Handle<SpriteSheet>) {}
+fn unloaded(db_key: &str) -> Error {
+    Error::loading("entry was fetched but holds no data").with_entry(db_key)
 }
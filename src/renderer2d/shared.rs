@@ -0,0 +1,99 @@
+use super::types::*;
+use dashi::utils::*;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+/// A handle whose last `Shared` clone went away.
+#[doc(hidden)]
+pub enum Dropped {
+    Sprite(Handle<Sprite>),
+    SpriteSheet(Handle<SpriteSheet>),
+    Font(Handle<Font>),
+    TileMap(Handle<TileMap>),
+}
+
+/// Resources that can be held through a `Shared` handle.
+pub trait SharedResource: Sized {
+    #[doc(hidden)]
+    fn dropped(handle: Handle<Self>) -> Dropped;
+}
+
+impl SharedResource for Sprite {
+    fn dropped(handle: Handle<Self>) -> Dropped {
+        Dropped::Sprite(handle)
+    }
+}
+
+impl SharedResource for SpriteSheet {
+    fn dropped(handle: Handle<Self>) -> Dropped {
+        Dropped::SpriteSheet(handle)
+    }
+}
+
+impl SharedResource for Font {
+    fn dropped(handle: Handle<Self>) -> Dropped {
+        Dropped::Font(handle)
+    }
+}
+
+impl SharedResource for TileMap {
+    fn dropped(handle: Handle<Self>) -> Dropped {
+        Dropped::TileMap(handle)
+    }
+}
+
+struct Owner<T: SharedResource> {
+    handle: Handle<T>,
+    drops: Sender<Dropped>,
+}
+
+impl<T: SharedResource> Drop for Owner<T> {
+    fn drop(&mut self) {
+        // The manager being gone already means there's nothing left to release.
+        let _ = self.drops.send(T::dropped(self.handle));
+    }
+}
+
+/// Reference-counted handle from `ResourceManager::share`. Clones keep the resource alive,
+/// and it's released once the last one is dropped. Draw with `handle()`.
+pub struct Shared<T: SharedResource> {
+    owner: Arc<Owner<T>>,
+}
+
+impl<T: SharedResource> Shared<T> {
+    pub(crate) fn new(handle: Handle<T>, drops: Sender<Dropped>) -> Self {
+        Self {
+            owner: Arc::new(Owner { handle, drops }),
+        }
+    }
+
+    pub fn handle(&self) -> Handle<T> {
+        self.owner.handle
+    }
+
+    /// Number of `Shared` clones currently holding the resource.
+    pub fn users(&self) -> usize {
+        Arc::strong_count(&self.owner)
+    }
+}
+
+impl<T: SharedResource> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self {
+            owner: self.owner.clone(),
+        }
+    }
+}
+
+#[test]
+fn test_shared() {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let font = Shared::<Font>::new(Default::default(), tx);
+    let scene_b = font.clone();
+    assert_eq!(scene_b.users(), 2);
+
+    drop(font);
+    assert!(rx.try_recv().is_err());
+    drop(scene_b);
+    assert!(matches!(rx.try_recv(), Ok(Dropped::Font(_))));
+}
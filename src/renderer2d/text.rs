@@ -0,0 +1,398 @@
+use super::StyledRun;
+use crate::database::TTFont;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+    /// Stretches the spaces so every line but the last of a paragraph fills the width.
+    Justify,
+}
+
+/// Which part of the text block lines up with the top of the layout box.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TextAnchor {
+    #[default]
+    Top,
+    Middle,
+    Bottom,
+    /// The first line's baseline sits on the top of the box.
+    Baseline,
+}
+
+/// Describes the box text gets laid out in. All sizes are in font pixels, the same units
+/// `measure_text` returns.
+#[derive(Clone, Copy, Debug)]
+pub struct TextLayoutInfo {
+    /// Lines wrap at word boundaries once they'd get wider than this.
+    pub width: Option<f32>,
+    /// Height of the box, used for vertical anchoring.
+    pub height: Option<f32>,
+    pub align: TextAlign,
+    pub anchor: TextAnchor,
+    /// Multiplier on the font's line height.
+    pub line_spacing: f32,
+}
+
+impl Default for TextLayoutInfo {
+    fn default() -> Self {
+        Self {
+            width: None,
+            height: None,
+            align: TextAlign::Left,
+            anchor: TextAnchor::Top,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+/// A character placed by the layout. `x` is the pen position and `y` the baseline, both
+/// relative to the top left of the layout box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionedGlyph {
+    pub ch: char,
+    pub index: usize,
+    pub x: f32,
+    pub y: f32,
+    pub scale: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextLine {
+    pub width: f32,
+    pub baseline: f32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TextLayout {
+    pub glyphs: Vec<PositionedGlyph>,
+    pub lines: Vec<TextLine>,
+    pub size: [f32; 2],
+}
+
+// Vertical metrics the layout works from, in pixels.
+struct LineMetrics {
+    ascent: f32,
+    descent: f32,
+    line_height: f32,
+}
+
+// Horizontal metrics in pixels. `scale` is looked up by character index so styled runs
+// can size parts of the text differently.
+struct Advances<'a> {
+    advance: &'a dyn Fn(char) -> f32,
+    kern: &'a dyn Fn(char, char) -> f32,
+    scale: &'a dyn Fn(usize) -> f32,
+}
+
+impl Advances<'_> {
+    // How far the pen moves for `ch`, including the kerning against the previous character.
+    fn step(&self, prev: Option<char>, index: usize, ch: char) -> f32 {
+        let kern = prev.map_or(0.0, |p| (self.kern)(p, ch));
+        (kern + (self.advance)(ch)) * (self.scale)(index)
+    }
+
+    fn width(&self, chars: &[(usize, char)]) -> f32 {
+        let mut width = 0.0;
+        let mut prev = None;
+        for &(index, ch) in chars {
+            width += self.step(prev, index, ch);
+            prev = Some(ch);
+        }
+
+        width
+    }
+
+    fn max_scale(&self, chars: &[(usize, char)]) -> f32 {
+        chars
+            .iter()
+            .map(|(i, _)| (self.scale)(*i))
+            .fold(0.0, f32::max)
+    }
+}
+
+struct BrokenLine {
+    chars: Vec<(usize, char)>,
+    width: f32,
+    last_in_paragraph: bool,
+}
+
+fn trim_spaces(chars: &[(usize, char)]) -> &[(usize, char)] {
+    let end = chars
+        .iter()
+        .rposition(|(_, c)| *c != ' ')
+        .map_or(0, |p| p + 1);
+    &chars[..end]
+}
+
+fn break_lines(text: &str, max_width: Option<f32>, advances: &Advances) -> Vec<BrokenLine> {
+    let fits = |chars: &[(usize, char)]| match max_width {
+        Some(w) => advances.width(chars) <= w,
+        None => true,
+    };
+
+    let mut lines = Vec::new();
+    let mut push = |chars: Vec<(usize, char)>, last_in_paragraph: bool| {
+        lines.push(BrokenLine {
+            width: advances.width(&chars),
+            chars,
+            last_in_paragraph,
+        });
+    };
+
+    let indexed: Vec<(usize, char)> = text.chars().enumerate().collect();
+    for paragraph in indexed.split(|(_, c)| *c == '\n') {
+        // Words keep their trailing space. It only gets dropped at the end of a line.
+        let mut line: Vec<(usize, char)> = Vec::new();
+        for word in paragraph.split_inclusive(|(_, c)| *c == ' ') {
+            let mut candidate = line.clone();
+            candidate.extend_from_slice(word);
+            if fits(trim_spaces(&candidate)) {
+                line = candidate;
+                continue;
+            }
+
+            if !trim_spaces(&line).is_empty() {
+                push(trim_spaces(&line).to_vec(), false);
+                line.clear();
+            }
+
+            // Words wider than the box get broken wherever they run out of room.
+            for &c in word {
+                line.push(c);
+                if trim_spaces(&line).len() > 1 && !fits(trim_spaces(&line)) {
+                    line.pop();
+                    push(trim_spaces(&line).to_vec(), false);
+                    line.clear();
+                    line.push(c);
+                }
+            }
+        }
+
+        push(trim_spaces(&line).to_vec(), true);
+    }
+
+    lines
+}
+
+fn layout_with(
+    text: &str,
+    info: &TextLayoutInfo,
+    metrics: &LineMetrics,
+    advances: &Advances,
+) -> TextLayout {
+    let broken = break_lines(text, info.width, advances);
+
+    // Lines are as tall as their largest character. Empty lines keep the base size.
+    let scales: Vec<f32> = broken
+        .iter()
+        .map(|l| match l.chars.is_empty() {
+            true => 1.0,
+            false => advances.max_scale(&l.chars),
+        })
+        .collect();
+
+    let step = metrics.line_height * info.line_spacing;
+    let mut baselines = Vec::with_capacity(broken.len());
+    for (i, s) in scales.iter().enumerate() {
+        baselines.push(match i {
+            0 => metrics.ascent * s,
+            _ => baselines[i - 1] + step * s.max(scales[i - 1]),
+        });
+    }
+
+    let widest = broken.iter().map(|l| l.width).fold(0.0, f32::max);
+    let box_width = info.width.unwrap_or(widest);
+    let text_height = match baselines.last() {
+        Some(b) => b - metrics.descent * scales[scales.len() - 1],
+        None => 0.0,
+    };
+
+    let box_height = info.height.unwrap_or(0.0);
+    let top = match info.anchor {
+        TextAnchor::Top => 0.0,
+        TextAnchor::Middle => (box_height - text_height) / 2.0,
+        TextAnchor::Bottom => box_height - text_height,
+        TextAnchor::Baseline => -baselines.first().copied().unwrap_or(0.0),
+    };
+
+    let mut layout = TextLayout {
+        size: [widest, text_height],
+        ..Default::default()
+    };
+
+    for (line, baseline) in broken.iter().zip(baselines) {
+        let baseline = top + baseline;
+        let slack = box_width - line.width;
+        let spaces = line.chars.iter().filter(|(_, c)| *c == ' ').count();
+
+        let (mut pen, stretch) = match info.align {
+            TextAlign::Left => (0.0, 0.0),
+            TextAlign::Center => (slack / 2.0, 0.0),
+            TextAlign::Right => (slack, 0.0),
+            TextAlign::Justify if line.last_in_paragraph || spaces == 0 => (0.0, 0.0),
+            TextAlign::Justify => (0.0, slack.max(0.0) / spaces as f32),
+        };
+
+        let mut prev = None;
+        for &(index, ch) in &line.chars {
+            if let Some(p) = prev {
+                pen += (advances.kern)(p, ch) * (advances.scale)(index);
+            }
+
+            layout.glyphs.push(PositionedGlyph {
+                ch,
+                index,
+                x: pen,
+                y: baseline,
+                scale: (advances.scale)(index),
+            });
+
+            pen += (advances.advance)(ch) * (advances.scale)(index);
+            if ch == ' ' {
+                pen += stretch;
+            }
+            prev = Some(ch);
+        }
+
+        layout.lines.push(TextLine {
+            width: line.width + stretch * spaces as f32,
+            baseline,
+        });
+    }
+
+    layout
+}
+
+fn font_metrics(font: &TTFont) -> LineMetrics {
+    LineMetrics {
+        ascent: font.ascent,
+        descent: font.descent,
+        line_height: font.line_height(),
+    }
+}
+
+/// Breaks `text` into lines and places every character. Newlines always start a new line.
+pub fn layout_text(font: &TTFont, text: &str, info: &TextLayoutInfo) -> TextLayout {
+    let advances = Advances {
+        advance: &|c| font.advance(c),
+        kern: &|a, b| font.kerning(a, b),
+        scale: &|_| 1.0,
+    };
+
+    layout_with(text, info, &font_metrics(font), &advances)
+}
+
+/// Lays out styled runs as one block of text. Glyph indices count characters across all
+/// the runs, in order.
+pub fn layout_runs(font: &TTFont, runs: &[StyledRun], info: &TextLayoutInfo) -> TextLayout {
+    let text: String = runs.iter().map(|r| r.text.as_str()).collect();
+    let scales: Vec<f32> = runs
+        .iter()
+        .flat_map(|r| std::iter::repeat_n(r.scale, r.text.chars().count()))
+        .collect();
+
+    let advances = Advances {
+        advance: &|c| font.advance(c),
+        kern: &|a, b| font.kerning(a, b),
+        scale: &|i| scales[i],
+    };
+
+    layout_with(&text, info, &font_metrics(font), &advances)
+}
+
+/// Size of the laid out text in font pixels, without drawing anything.
+pub fn measure_text(font: &TTFont, text: &str, info: &TextLayoutInfo) -> [f32; 2] {
+    layout_text(font, text, info).size
+}
+
+#[test]
+fn test_text_layout() {
+    // Monospaced: every character is 10px wide, lines are 20px apart.
+    let metrics = LineMetrics {
+        ascent: 15.0,
+        descent: -5.0,
+        line_height: 20.0,
+    };
+    let advances = Advances {
+        advance: &|_| 10.0,
+        kern: &|_, _| 0.0,
+        scale: &|_| 1.0,
+    };
+    let layout = |text: &str, info: &TextLayoutInfo| layout_with(text, info, &metrics, &advances);
+
+    let single = layout("hello world", &Default::default());
+    assert_eq!(single.lines.len(), 1);
+    assert_eq!(single.size, [110.0, 20.0]);
+    assert_eq!(single.glyphs[6].x, 60.0);
+    assert_eq!(single.glyphs[6].index, 6);
+
+    let wrapped = layout(
+        "aa bb cc\ndd",
+        &TextLayoutInfo {
+            width: Some(55.0),
+            align: TextAlign::Right,
+            ..Default::default()
+        },
+    );
+    let widths: Vec<f32> = wrapped.lines.iter().map(|l| l.width).collect();
+    assert_eq!(widths, vec![50.0, 20.0, 20.0]);
+    assert_eq!(wrapped.lines[2].baseline, 55.0);
+    // "cc" is right aligned on its own line.
+    let c = wrapped.glyphs.iter().find(|g| g.ch == 'c').unwrap();
+    assert_eq!((c.x, c.y), (35.0, 35.0));
+    assert_eq!(c.index, 6);
+
+    let justified = layout(
+        "aa b cc d",
+        &TextLayoutInfo {
+            width: Some(60.0),
+            align: TextAlign::Justify,
+            ..Default::default()
+        },
+    );
+    let b = justified.glyphs.iter().find(|g| g.ch == 'b').unwrap();
+    assert_eq!(b.x, 50.0);
+    assert_eq!(justified.lines[0].width, 60.0);
+    // The last line of a paragraph stays left aligned.
+    let d = justified.glyphs.iter().find(|g| g.ch == 'd').unwrap();
+    assert_eq!(d.x, 30.0);
+
+    let centered = layout(
+        "x\ny",
+        &TextLayoutInfo {
+            height: Some(100.0),
+            anchor: TextAnchor::Middle,
+            line_spacing: 1.5,
+            ..Default::default()
+        },
+    );
+    assert_eq!(centered.size[1], 50.0);
+    assert_eq!(centered.lines[0].baseline, 25.0 + 15.0);
+    assert_eq!(centered.lines[1].baseline, 25.0 + 15.0 + 30.0);
+
+    let long = layout(
+        "abcdefgh",
+        &TextLayoutInfo {
+            width: Some(30.0),
+            ..Default::default()
+        },
+    );
+    assert_eq!(long.lines.len(), 3);
+
+    // Styled runs: the second word is drawn twice as large.
+    let scaled = Advances {
+        advance: &|_| 10.0,
+        kern: &|_, _| 0.0,
+        scale: &|i| if i >= 3 { 2.0 } else { 1.0 },
+    };
+    let runs = layout_with("ab\ncd\nef", &Default::default(), &metrics, &scaled);
+    assert_eq!(runs.lines[0].width, 20.0);
+    assert_eq!(runs.lines[1].width, 40.0);
+    assert_eq!(runs.glyphs[3].x, 20.0);
+    assert_eq!(runs.glyphs[3].scale, 2.0);
+    let baselines: Vec<f32> = runs.lines.iter().map(|l| l.baseline).collect();
+    assert_eq!(baselines, vec![15.0, 55.0, 95.0]);
+}
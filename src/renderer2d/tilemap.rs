@@ -0,0 +1,188 @@
+use super::types::*;
+use crate::database::*;
+use dashi::*;
+use std::collections::HashMap;
+
+/// Tiles along each side of the square chunks tile layers are cut into. Every chunk is one
+/// draw per tileset it uses, and the index buffer holds enough quads for a full chunk.
+pub const TILEMAP_CHUNK_TILES: u32 = 8;
+
+// Corners in the order the quad's vertices go: top left, bottom left, bottom right, top right.
+fn tile_tex_coords(uv: &FRect2D, tile: u32) -> [[f32; 2]; 4] {
+    let mut tex = [[uv.x, uv.y], [uv.x, uv.h], [uv.w, uv.h], [uv.w, uv.y]];
+
+    // Tiled flips diagonally first, then horizontally, then vertically.
+    if tile & TILE_FLIP_DIAGONAL != 0 {
+        tex.swap(1, 3);
+    }
+    if tile & TILE_FLIP_HORIZONTAL != 0 {
+        tex.swap(0, 3);
+        tex.swap(1, 2);
+    }
+    if tile & TILE_FLIP_VERTICAL != 0 {
+        tex.swap(0, 1);
+        tex.swap(2, 3);
+    }
+
+    tex
+}
+
+/// Cuts the map's tile layers into chunks of quads. `frames` are the sprite sheet frames of
+/// each tileset, in the map's tileset order. Tiles without a frame are left out.
+pub fn build_tilemap_chunks(
+    map: &TiledMap,
+    frames: &[&HashMap<u32, FRect2D>],
+) -> Vec<TileMapChunk> {
+    let mut chunks = Vec::new();
+    let [cell_w, cell_h] = map.tile_size;
+
+    for (index, layer) in map.layers.iter().enumerate() {
+        let layer = match layer {
+            TiledLayer::Tiles(t) => t,
+            TiledLayer::Objects(_) => continue,
+        };
+
+        let [w, h] = layer.size;
+        for cy in (0..h).step_by(TILEMAP_CHUNK_TILES as usize) {
+            for cx in (0..w).step_by(TILEMAP_CHUNK_TILES as usize) {
+                // Tilesets in the order they first show up, so the output is stable.
+                let mut by_sheet: Vec<(usize, TileMapChunk)> = Vec::new();
+                for y in cy..(cy + TILEMAP_CHUNK_TILES).min(h) {
+                    for x in cx..(cx + TILEMAP_CHUNK_TILES).min(w) {
+                        let tile = layer.tile(x, y);
+                        let (sheet, local) = match map.tileset_of(tile) {
+                            Some(t) => t,
+                            None => continue,
+                        };
+                        let uv = match frames.get(sheet).and_then(|f| f.get(&local)) {
+                            Some(uv) => uv,
+                            None => continue,
+                        };
+
+                        // Tiles bigger than a cell hang off its bottom left corner, like in Tiled.
+                        let [tw, th] = map.tilesets[sheet].tile_size;
+                        let x0 = layer.offset[0] + (x * cell_w) as f32;
+                        let y1 = layer.offset[1] + ((y + 1) * cell_h) as f32;
+                        let (x1, y0) = (x0 + tw as f32, y1 - th as f32);
+
+                        let chunk = match by_sheet.iter().position(|(s, _)| *s == sheet) {
+                            Some(i) => &mut by_sheet[i].1,
+                            None => {
+                                by_sheet.push((
+                                    sheet,
+                                    TileMapChunk {
+                                        layer: index,
+                                        sheet,
+                                        bounds: FRect2D {
+                                            x: x0,
+                                            y: y0,
+                                            w: x1,
+                                            h: y1,
+                                        },
+                                        vertices: Vec::new(),
+                                    },
+                                ));
+                                &mut by_sheet.last_mut().unwrap().1
+                            }
+                        };
+
+                        let b = &mut chunk.bounds;
+                        (b.x, b.y, b.w, b.h) = (b.x.min(x0), b.y.min(y0), b.w.max(x1), b.h.max(y1));
+
+                        let tex = tile_tex_coords(uv, tile);
+                        let corners = [[x0, y0], [x0, y1], [x1, y1], [x1, y0]];
+                        chunk
+                            .vertices
+                            .extend(corners.iter().zip(tex).map(|(p, t)| Vertex {
+                                position: *p,
+                                tex_coords: t,
+                            }));
+                    }
+                }
+
+                chunks.extend(by_sheet.into_iter().map(|(_, c)| c));
+            }
+        }
+    }
+
+    chunks
+}
+
+#[test]
+fn test_tilemap_chunks() {
+    let tileset = |name: &str, first_gid: u32, tile_size: [u32; 2]| TiledTileset {
+        name: name.to_string(),
+        first_gid,
+        sheet: name.to_string(),
+        tile_size,
+        tile_count: 4,
+        columns: 2,
+        properties: Default::default(),
+        tile_properties: Default::default(),
+    };
+
+    // Ten columns so the layer spans two chunks across.
+    let mut tiles = vec![0; 20];
+    tiles[0] = 1;
+    tiles[1] = 5;
+    tiles[9] = 2 | TILE_FLIP_HORIZONTAL;
+    tiles[10] = 3;
+    let map = TiledMap {
+        size: [10, 2],
+        tile_size: [16, 16],
+        layers: vec![
+            TiledLayer::Objects(TiledObjectLayer {
+                name: "things".to_string(),
+                offset: [0.0, 0.0],
+                visible: true,
+                objects: Vec::new(),
+                properties: Default::default(),
+            }),
+            TiledLayer::Tiles(TiledTileLayer {
+                name: "floor".to_string(),
+                size: [10, 2],
+                offset: [0.0, 4.0],
+                opacity: 1.0,
+                visible: true,
+                tiles,
+                properties: Default::default(),
+            }),
+        ],
+        tilesets: vec![
+            tileset("ground", 1, [16, 16]),
+            tileset("trees", 5, [16, 32]),
+        ],
+        properties: Default::default(),
+    };
+
+    let frame = |x: f32| FRect2D {
+        x,
+        y: 0.0,
+        w: x + 0.5,
+        h: 0.5,
+    };
+    let ground: HashMap<u32, FRect2D> = (0..4).map(|i| (i, frame(i as f32))).collect();
+    let trees: HashMap<u32, FRect2D> = [(0, frame(10.0))].into_iter().collect();
+
+    let chunks = build_tilemap_chunks(&map, &[&ground, &trees]);
+    assert_eq!(chunks.len(), 3);
+    assert!(chunks.iter().all(|c| c.layer == 1));
+
+    // First chunk, ground tiles: (0, 0) and (0, 1).
+    assert_eq!(chunks[0].sheet, 0);
+    assert_eq!(chunks[0].vertices.len(), 8);
+    assert_eq!(chunks[0].vertices[0].position, [0.0, 4.0]);
+    assert_eq!(chunks[0].vertices[0].tex_coords, [0.0, 0.0]);
+    let b = chunks[0].bounds;
+    assert_eq!([b.x, b.y, b.w, b.h], [0.0, 4.0, 16.0, 36.0]);
+
+    // The tall tree sticks up out of its cell.
+    assert_eq!(chunks[1].sheet, 1);
+    assert_eq!(chunks[1].vertices[0].position, [16.0, -12.0]);
+    assert_eq!(chunks[1].vertices[2].position, [32.0, 20.0]);
+
+    // Second chunk across, with the flipped tile mirrored.
+    assert_eq!(chunks[2].vertices[0].position, [144.0, 4.0]);
+    assert_eq!(chunks[2].vertices[0].tex_coords, [1.5, 0.0]);
+    assert_eq!(chunks[2].vertices[3].tex_coords, [1.0, 0.0]);
+}
@@ -1,9 +1,18 @@
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 use crate::database::font::*;
+use crate::database::SpriteSheetJSONAnimation;
 use dashi::utils::*;
 use dashi::*;
 
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 2],
+    pub tex_coords: [f32; 2],
+}
+
 pub struct FontInfo<'a> {
     pub name: &'a str,
     pub db_key: &'a str,
@@ -14,11 +23,23 @@ pub struct SpriteInfo<'a> {
     pub db_key: &'a str,
 }
 
+pub struct SpriteAtlasInfo<'a> {
+    pub name: &'a str,
+    pub db_keys: &'a [&'a str],
+    pub page_size: [u32; 2],
+    pub padding: u32,
+}
+
 pub struct Sprite {
     pub dim: [u32; 2],
     pub handle: Handle<Image>,
     pub view: Handle<ImageView>,
     pub bg: Handle<BindGroup>,
+    // Region of `handle` holding the sprite. Min corner in x/y and max corner in w/h, like
+    // `SpriteSheet::sprites`. Atlas sprites share their page's image and bind group.
+    pub uv: FRect2D,
+    /// The image was loaded with premultiplied alpha.
+    pub premultiplied: bool,
 }
 
 pub struct SpriteSheetInfo<'a> {
@@ -26,21 +47,65 @@ pub struct SpriteSheetInfo<'a> {
     pub db_key: &'a str,
 }
 
+/// How a trimmed or rotated frame maps back onto its original size. Offset and size are
+/// fractions of the untrimmed frame, so they scale with whatever size it is drawn at.
+#[derive(Clone, Copy, Debug)]
+pub struct SpriteFrameLayout {
+    pub offset: [f32; 2],
+    pub size: [f32; 2],
+    pub rotated: bool,
+}
+
 pub struct SpriteSheet {
     pub dim: [u32; 2],
     pub handle: Handle<Image>,
     pub view: Handle<ImageView>,
     pub bg: Handle<BindGroup>,
     pub sprites: HashMap<u32, FRect2D>,
+    pub layouts: HashMap<u32, SpriteFrameLayout>,
+    pub animations: HashMap<String, SpriteSheetJSONAnimation>,
+    /// The image was loaded with premultiplied alpha.
+    pub premultiplied: bool,
 }
 
-pub struct Font {
-    pub dim: [u32; 2],
-    pub atlas: Handle<Image>,
-    pub atlas_view: Handle<ImageView>,
-    pub font: *const TTFont,
+pub struct TileMapInfo<'a> {
+    pub name: &'a str,
+    pub db_key: &'a str,
+}
+
+/// Quads for the tiles of one layer inside one chunk that come from the same tileset.
+pub struct TileMapChunk {
+    /// Index into `TileMap::layers`.
+    pub layer: usize,
+    /// Index into `TileMap::sheets`.
+    pub sheet: usize,
+    /// Map pixels the quads cover. Min corner in x/y and max corner in w/h.
+    pub bounds: FRect2D,
+    /// Four per tile, positioned in map pixels.
+    pub vertices: Vec<Vertex>,
+}
+
+pub struct TileMap {
+    /// In pixels, at a scale of 1.
+    pub size: [f32; 2],
+    /// Made from the map's tilesets, in the same order.
+    pub sheets: Vec<Handle<SpriteSheet>>,
+    /// Name and visibility of each of the map's layers, object layers included.
+    pub layers: Vec<(String, bool)>,
+    pub chunks: Vec<TileMapChunk>,
+}
+
+/// One glyph atlas page on the GPU. Newly rasterized glyphs are blitted into it rect by
+/// rect.
+pub struct FontPage {
+    pub image: Handle<Image>,
+    pub view: Handle<ImageView>,
     pub bg: Handle<BindGroup>,
 }
 
-unsafe impl Send for Font {}
-unsafe impl Sync for Font {}
+pub struct Font {
+    pub dim: [u32; 2],
+    pub pages: Vec<FontPage>,
+    /// The same font the database entry holds, until the entry is reloaded or evicted.
+    pub font: Arc<Mutex<TTFont>>,
+}
@@ -1,3 +1,4 @@
+use crate::database::{Error, ErrorContext};
 use dashi::utils::*;
 use dashi::*;
 use serde::{Deserialize, Serialize};
@@ -55,31 +56,73 @@ impl Canvas {
         self.color_views[idx as usize]
     }
 
+    /// Checks a canvas description without creating anything. Returns what's wrong with it,
+    /// including format values that don't parse or don't suit the attachment they're on.
+    pub fn validate_json(json: &str) -> Vec<String> {
+        let info: CanvasJSONInfo = match serde_json::from_str(json) {
+            Ok(info) => info,
+            Err(e) => return vec![format!("Failed to read Canvas from JSON: {}", e)],
+        };
+
+        let mut problems = Vec::new();
+        if info.viewport.area.w <= 0.0 || info.viewport.area.h <= 0.0 {
+            problems.push(format!(
+                "viewport is {}x{}, it needs a positive size",
+                info.viewport.area.w, info.viewport.area.h
+            ));
+        }
+
+        if info.color_attachments.is_empty() {
+            problems.push("there are no color attachments to draw into".to_string());
+        }
+
+        for a in &info.color_attachments {
+            if matches!(a.format, Format::D24S8) {
+                problems.push(format!(
+                    "color attachment '{}' uses the depth format {:?}",
+                    a.name, a.format
+                ));
+            }
+        }
+
+        if let Some(a) = info.depth_stencil.as_ref() {
+            if !matches!(a.format, Format::D24S8) {
+                problems.push(format!(
+                    "depth attachment '{}' uses the color format {:?}",
+                    a.name, a.format
+                ));
+            }
+        }
+
+        problems
+    }
+
     pub fn from_json(ctx: &mut Context, path: &str) -> Self {
-        let json_data = fs::read_to_string(path).expect("Failed to read JSON for Canvas!");
-        let info: CanvasJSONInfo =
-            serde_json::from_str(&json_data).expect("Failed to read Canvas from JSON!");
+        Self::try_from_json(ctx, path).unwrap()
+    }
+
+    /// Same as `from_json`, but a missing or malformed file, or a failed GPU allocation,
+    /// comes back as an error.
+    pub fn try_from_json(ctx: &mut Context, path: &str) -> Result<Self, Error> {
+        let json_data = fs::read_to_string(path).with_path(path)?;
+        let info: CanvasJSONInfo = serde_json::from_str(&json_data).with_path(path)?;
 
         // Fn to convert CanvasAttachment -> tuple (img, view, dashi attachment)
-        let mut attach_to_tuple = |a: CanvasAttachment| {
-            let img = ctx
-                .make_image(&ImageInfo {
-                    debug_name: &a.name,
-                    dim: [info.viewport.area.w as u32, info.viewport.area.h as u32, 1],
-                    format: a.format,
-                    mip_levels: 1,
-                    initial_data: None,
-                })
-                .unwrap();
-
-            let view = ctx
-                .make_image_view(&ImageViewInfo {
-                    debug_name: &a.name,
-                    img,
-                    layer: 0,
-                    mip_level: 0,
-                })
-                .unwrap();
+        let mut attach_to_tuple = |a: CanvasAttachment| -> Result<_, Error> {
+            let img = ctx.make_image(&ImageInfo {
+                debug_name: &a.name,
+                dim: [info.viewport.area.w as u32, info.viewport.area.h as u32, 1],
+                format: a.format,
+                mip_levels: 1,
+                initial_data: None,
+            })?;
+
+            let view = ctx.make_image_view(&ImageViewInfo {
+                debug_name: &a.name,
+                img,
+                layer: 0,
+                mip_level: 0,
+            })?;
 
             let attachment = Attachment {
                 view,
@@ -91,42 +134,40 @@ impl Canvas {
                 clear_color: a.clear_color,
             };
 
-            return (img, view, attachment);
+            return Ok((img, view, attachment));
         };
 
         let colors: Vec<(Handle<Image>, Handle<ImageView>, Attachment)> = info
             .color_attachments
             .into_iter()
             .map(|a| attach_to_tuple(a))
-            .collect();
+            .collect::<Result<_, _>>()?;
 
         let (imgs, views, attachs): (Vec<_>, Vec<_>, Vec<_>) = colors.iter().cloned().unzip3();
 
         let (depth, _view, depth_attach) = match info.depth_stencil {
             Some(a) => {
-                let (img, view, attachment) = attach_to_tuple(a);
+                let (img, view, attachment) = attach_to_tuple(a)?;
                 (Some(img), Some(view), Some(attachment))
             }
             None => (None, None, None),
         };
 
-        let render_pass = ctx
-            .make_render_pass(&RenderPassInfo {
-                debug_name: "Shoyu Canvas Render Pass",
-                viewport: info.viewport,
-                color_attachments: &attachs,
-                depth_stencil_attachment: depth_attach.as_ref(),
-            })
-            .unwrap();
+        let render_pass = ctx.make_render_pass(&RenderPassInfo {
+            debug_name: "Shoyu Canvas Render Pass",
+            viewport: info.viewport,
+            color_attachments: &attachs,
+            depth_stencil_attachment: depth_attach.as_ref(),
+        })?;
 
-        Self {
+        Ok(Self {
             viewport: info.viewport,
             color_images: imgs,
             depth,
             render_pass,
             name: info.name,
             color_views: views,
-        }
+        })
     }
 
     pub fn new_static(
@@ -4,5 +4,7 @@ pub mod hotbuffer;
 pub use hotbuffer::*;
 pub mod image;
 pub use image::*;
+pub mod rect_packer;
+pub use rect_packer::*;
 pub mod timer;
 pub use timer::*;
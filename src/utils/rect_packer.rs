@@ -0,0 +1,142 @@
+use dashi::Rect2D;
+
+#[derive(Clone, Copy)]
+struct SkylineNode {
+    x: u32,
+    y: u32,
+    w: u32,
+}
+
+/// Skyline bottom-left rectangle packer. Places each rect as low as possible, then as
+/// narrow a gap as possible.
+pub struct RectPacker {
+    width: u32,
+    height: u32,
+    padding: u32,
+    skyline: Vec<SkylineNode>,
+}
+
+impl RectPacker {
+    pub fn new(width: u32, height: u32, padding: u32) -> Self {
+        Self {
+            width,
+            height,
+            padding,
+            skyline: vec![SkylineNode {
+                x: 0,
+                y: 0,
+                w: width,
+            }],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // Height the rect would sit at if its left edge starts at skyline node `idx`.
+    fn fit(&self, idx: usize, w: u32, h: u32) -> Option<u32> {
+        let x = self.skyline[idx].x;
+        if x + w > self.width {
+            return None;
+        }
+
+        let mut y = 0;
+        let mut remaining = w as i64;
+        let mut i = idx;
+        while remaining > 0 {
+            let node = self.skyline.get(i)?;
+            y = y.max(node.y);
+            if y + h > self.height {
+                return None;
+            }
+
+            remaining -= node.w as i64;
+            i += 1;
+        }
+
+        Some(y)
+    }
+
+    fn place(&mut self, idx: usize, x: u32, y: u32, w: u32) {
+        self.skyline.insert(idx, SkylineNode { x, y, w });
+
+        // Cut away the nodes now covered by the new one.
+        let i = idx + 1;
+        while i < self.skyline.len() {
+            let prev_end = self.skyline[i - 1].x + self.skyline[i - 1].w;
+            let node = &mut self.skyline[i];
+            if node.x >= prev_end {
+                break;
+            }
+
+            let shrink = prev_end - node.x;
+            if node.w <= shrink {
+                self.skyline.remove(i);
+            } else {
+                node.x += shrink;
+                node.w -= shrink;
+                break;
+            }
+        }
+
+        // Merge neighbours at the same height.
+        let mut i = 0;
+        while i + 1 < self.skyline.len() {
+            if self.skyline[i].y == self.skyline[i + 1].y {
+                self.skyline[i].w += self.skyline[i + 1].w;
+                self.skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Reserves a `w` x `h` region. Returns `None` when it doesn't fit anymore.
+    pub fn insert(&mut self, w: u32, h: u32) -> Option<Rect2D> {
+        let (pw, ph) = (w + self.padding, h + self.padding);
+
+        let mut best: Option<(usize, u32, u32)> = None;
+        for idx in 0..self.skyline.len() {
+            if let Some(y) = self.fit(idx, pw, ph) {
+                let width = self.skyline[idx].w;
+                let better = match best {
+                    None => true,
+                    Some((_, by, bw)) => y < by || (y == by && width < bw),
+                };
+
+                if better {
+                    best = Some((idx, y, width));
+                }
+            }
+        }
+
+        let (idx, y, _) = best?;
+        let x = self.skyline[idx].x;
+        self.place(idx, x, y + ph, pw);
+
+        Some(Rect2D { x, y, w, h })
+    }
+}
+
+#[test]
+fn test_rect_packer() {
+    let mut packer = RectPacker::new(64, 64, 1);
+    let mut placed: Vec<Rect2D> = Vec::new();
+    for (w, h) in [(30, 20), (30, 20), (10, 40), (20, 10), (12, 12), (5, 5)] {
+        let r = packer.insert(w, h).unwrap();
+        assert!(r.x + r.w <= 64 && r.y + r.h <= 64);
+        for o in &placed {
+            let apart = r.x >= o.x + o.w || o.x >= r.x + r.w || r.y >= o.y + o.h || o.y >= r.y + r.h;
+            assert!(apart);
+        }
+        placed.push(r);
+    }
+
+    assert!(packer.insert(65, 1).is_none());
+    assert!(packer.insert(60, 60).is_none());
+}
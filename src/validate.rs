@@ -0,0 +1,90 @@
+use crate::database::{Database, FileSystem, Severity, ValidationIssue};
+use crate::renderer2d::ParticleSystem;
+use crate::utils::Canvas;
+use serde::Serialize;
+use std::fmt;
+
+/// Everything wrong with a database directory, as found by `validate_database`.
+#[derive(Serialize, Clone, Debug)]
+pub struct ValidationReport {
+    pub path: String,
+    pub errors: usize,
+    pub warnings: usize,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    fn new(path: &str, issues: Vec<ValidationIssue>) -> Self {
+        let count = |s: Severity| issues.iter().filter(|i| i.severity == s).count();
+        Self {
+            path: path.to_string(),
+            errors: count(Severity::Error),
+            warnings: count(Severity::Warning),
+            issues,
+        }
+    }
+
+    /// True when there's nothing that would break at runtime. Warnings don't count.
+    pub fn is_ok(&self) -> bool {
+        self.errors == 0
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Validating {}", self.path)?;
+        for issue in &self.issues {
+            writeln!(f, "  {}", issue)?;
+        }
+
+        write!(f, "{} error(s), {} warning(s)", self.errors, self.warnings)
+    }
+}
+
+/// Loads the database at `path` and checks every image, font and particle file it refers
+/// to, along with its `canvas.json`. `path` may be a directory or a pack archive.
+pub fn validate_database(path: &str) -> ValidationReport {
+    let mut issues = Vec::new();
+    match Database::new(path) {
+        Ok(mut db) => {
+            issues.extend(db.validate());
+            let cfgs: Vec<(&dyn FileSystem, &str)> = db
+                .particle_system_cfgs()
+                .into_iter()
+                .map(|(mount, path)| (mount.fs.as_ref(), path))
+                .collect();
+            if !cfgs.is_empty() {
+                let fs = db.filesystem().as_ref();
+                issues.extend(ParticleSystem::validate_cfgs(fs, &cfgs));
+            }
+            issues.extend(validate_canvas(db.filesystem().as_ref()));
+        }
+        Err(e) => {
+            let msg = format!("doesn't load: {}", e);
+            issues.push(ValidationIssue::error("database", "shoyu.json", msg));
+        }
+    }
+
+    ValidationReport::new(path, issues)
+}
+
+// Read through the database's file system, so it's found inside pack archives too.
+fn validate_canvas(fs: &dyn FileSystem) -> Vec<ValidationIssue> {
+    let json = fs
+        .read("canvas.json")
+        .and_then(|bytes| Ok(String::from_utf8(bytes)?));
+    match json {
+        Ok(json) => Canvas::validate_json(&json)
+            .into_iter()
+            .map(|msg| ValidationIssue::error("canvas", "canvas.json", msg))
+            .collect(),
+        Err(e) => {
+            let msg = format!("can't read canvas.json: {}", e);
+            vec![ValidationIssue::error("canvas", "canvas.json", msg)]
+        }
+    }
+}